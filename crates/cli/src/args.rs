@@ -55,6 +55,15 @@ pub struct Args {
     )]
     pub reorg_buffer: u64,
 
+    /// Keep collecting new chunks as the chain advances,
+    /// each chunk is saved once it is past the reorg buffer
+    #[arg(long, help_heading = "Content Options", verbatim_doc_comment)]
+    pub follow: bool,
+
+    /// Seconds between polls for new blocks when following
+    #[arg(long, default_value_t = 12, value_name = "SECONDS", help_heading = "Content Options")]
+    pub poll_interval: u64,
+
     /// Columns to include alongside the defaults,
     /// use `all` to include all available columns
     #[arg(short, long, value_name="COLS", num_args(0..), verbatim_doc_comment, help_heading="Content Options")]
@@ -86,8 +95,9 @@ pub struct Args {
     #[arg(long, help_heading = "Content Options")]
     pub exclude_failed: bool,
 
-    /// RPC url [default: 1. MESC 2. ETH_RPC_URL]
-    #[arg(short, long, help_heading = "Source Options")]
+    /// RPC url, comma-separated urls for a pool of endpoints
+    /// [default: 1. MESC 2. ETH_RPC_URL]
+    #[arg(short, long, help_heading = "Source Options", verbatim_doc_comment)]
     pub rpc: Option<String>,
 
     /// Network name [default: name of eth_getChainId]
@@ -114,6 +124,38 @@ pub struct Args {
     #[arg(long, value_name = "M", help_heading = "Acquisition Options")]
     pub max_concurrent_chunks: Option<u64>,
 
+    /// Max requests per JSON-RPC batch of state reads [default: no batching]
+    #[arg(long, value_name = "N", help_heading = "Acquisition Options")]
+    pub max_batch_size: Option<u64>,
+
+    /// Adapt concurrency and rate to rate limit errors and latency of the
+    /// node, up to --max-concurrent-requests and --requests-per-second,
+    /// requires a single http rpc url
+    #[arg(long, help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub adaptive_limits: bool,
+
+    /// Cache responses of blocks beyond the reorg buffer, or of finalized
+    /// blocks without a reorg buffer, in directory
+    #[arg(long, value_name = "DIR", help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub cache_dir: Option<String>,
+
+    /// Max size of response cache in MB
+    #[arg(long, default_value_t = 10_000, value_name = "MB", help_heading = "Acquisition Options")]
+    pub cache_size: u64,
+
+    /// Max retries for chunks that fail with a retryable error
+    #[arg(long, default_value_t = 0, value_name = "R", help_heading = "Acquisition Options")]
+    pub chunk_retries: u32,
+
+    /// Initial backoff before retrying a chunk (ms)
+    #[arg(long, default_value_t = 1000, value_name = "B", help_heading = "Acquisition Options")]
+    pub chunk_retry_backoff: u64,
+
+    /// Error kinds that trigger chunk retries
+    /// [default: provider, task_failed, rpc, too_many_requests, not_found]
+    #[arg(long, value_name = "KINDS", num_args(1..), help_heading = "Acquisition Options", verbatim_doc_comment)]
+    pub chunk_retry_errors: Option<Vec<String>>,
+
     /// Chunk collection order (normal, reverse, random)
     #[arg(long, help_heading = "Acquisition Options")]
     pub chunk_order: Option<String>,
@@ -126,6 +168,11 @@ pub struct Args {
     #[arg(long)]
     pub remember: bool,
 
+    /// Resume an interrupted job using its checkpoint journal,
+    /// without datatypes, resumes the latest incomplete job
+    #[arg(long, verbatim_doc_comment)]
+    pub resume: bool,
+
     /// Extra verbosity
     #[arg(short, long)]
     pub verbose: bool,
@@ -146,8 +193,9 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub partition_by: Option<Vec<String>>,
 
-    /// Directory for output files
-    #[arg(short, long, default_value = ".", help_heading = "Output Options")]
+    /// Directory for output files, or object store url such as
+    /// s3://bucket/prefix or file:///dir
+    #[arg(short, long, default_value = ".", help_heading = "Output Options", verbatim_doc_comment)]
     pub output_dir: String,
 
     /// Subdirectories for output files
@@ -155,6 +203,15 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options", verbatim_doc_comment, num_args(1..))]
     pub subdirs: Vec<String>,
 
+    /// Layout of output files, `flat` or `hive`
+    /// hive uses key=value directories for each partition
+    #[arg(long, default_value = "flat", help_heading = "Output Options", verbatim_doc_comment)]
+    pub layout: String,
+
+    /// Number of blocks per block_bucket directory of hive layout
+    #[arg(long, default_value_t = 10000, value_name = "N_BLOCKS", help_heading = "Output Options")]
+    pub block_bucket_size: u64,
+
     /// Label to add to each filename
     #[arg(long, help_heading = "Output Options")]
     pub label: Option<String>,
@@ -171,6 +228,30 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub json: bool,
 
+    /// Save as newline-delimited json instead of parquet
+    #[arg(long, visible_alias = "jsonl", help_heading = "Output Options")]
+    pub ndjson: bool,
+
+    /// Save into sqlite database instead of parquet
+    #[arg(long, help_heading = "Output Options")]
+    pub sqlite: bool,
+
+    /// Save as arrow ipc (feather) instead of parquet
+    #[arg(long, visible_alias = "ipc", help_heading = "Output Options")]
+    pub arrow: bool,
+
+    /// Load into postgres database at URL instead of writing files
+    #[arg(long, value_name = "URL", help_heading = "Output Options")]
+    pub postgres: Option<String>,
+
+    /// Split partitions into files of at most N rows
+    #[arg(long, value_name = "N", help_heading = "Output Options")]
+    pub max_rows_per_file: Option<usize>,
+
+    /// Split partitions into files of about SIZE each, e.g. 512MB
+    #[arg(long, value_name = "SIZE", help_heading = "Output Options")]
+    pub max_file_size: Option<String>,
+
     /// Number of rows per row group in parquet file
     #[arg(long, value_name = "GROUP_SIZE", help_heading = "Output Options")]
     pub row_group_size: Option<usize>,
@@ -183,9 +264,23 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub no_stats: bool,
 
+    /// Target size of data pages in parquet file, e.g. 1MB
+    #[arg(long, value_name = "SIZE", help_heading = "Output Options")]
+    pub page_size: Option<String>,
+
+    /// Dictionary encode columns in parquet file
+    #[arg(long, value_name = "COLUMN", num_args(1..), help_heading = "Output Options")]
+    pub dictionary: Vec<String>,
+
+    /// Write bloom filters for columns in parquet file
+    /// [default: *address *hash]
+    #[arg(long, value_name = "COLUMN", num_args(0..), help_heading = "Output Options", verbatim_doc_comment)]
+    pub bloom_filter: Option<Vec<String>>,
+
     /// Compression algorithm and level
-    #[arg(long, help_heading="Output Options", value_name="NAME [#]", num_args(1..=2), default_value = "lz4")]
-    pub compression: Vec<String>,
+    /// [default: lz4, arrow files are uncompressed]
+    #[arg(long, help_heading="Output Options", value_name="NAME [#]", num_args(1..=2), verbatim_doc_comment)]
+    pub compression: Option<Vec<String>>,
 
     /// Directory to save summary report
     /// [default: {output_dir}/.cryo/reports]
@@ -196,6 +291,17 @@ pub struct Args {
     #[arg(long, help_heading = "Output Options")]
     pub no_report: bool,
 
+    /// Avoid maintaining a manifest of written files
+    #[arg(long, help_heading = "Output Options")]
+    pub no_manifest: bool,
+
+    /// Commit files of each run to a delta table log after all
+    /// of its partitions complete, requires parquet output, u64
+    /// columns are typed as long, so values above 2^63-1 are
+    /// refused
+    #[arg(long, help_heading = "Output Options", verbatim_doc_comment)]
+    pub delta: bool,
+
     /// Address(es)
     #[arg(long, help_heading = "Dataset-specific Options", num_args(1..))]
     pub address: Option<Vec<String>>,
@@ -318,7 +424,41 @@ fn get_after_str() -> String {
       <white><bold>cryo help</bold></white>"#
     );
     let post_subcommands = " <DATASET(S)>         display info about a dataset";
-    format!("{}{}{}", header, subcommands, post_subcommands)
+    let verify_subcommand = cstr!(
+        r#"
+      <white><bold>cryo verify-canonical</bold></white>"#
+    );
+    let post_verify_subcommand = " <DATASET(S)> re-collect chunks that were reorged";
+    let audit_subcommand = cstr!(
+        r#"
+      <white><bold>cryo audit</bold></white>"#
+    );
+    let post_audit_subcommand =
+        " [DIR]                   find gaps and damaged files in output dir";
+    let compact_subcommand = cstr!(
+        r#"
+      <white><bold>cryo compact</bold></white>"#
+    );
+    let post_compact_subcommand = " <DATASET(S)>      merge chunk files into --chunk-size files";
+    let inspect_subcommand = cstr!(
+        r#"
+      <white><bold>cryo inspect</bold></white>"#
+    );
+    let post_inspect_subcommand = " <FILE>            print provenance metadata of output file";
+    format!(
+        "{}{}{}{}{}{}{}{}{}{}{}",
+        header,
+        subcommands,
+        post_subcommands,
+        verify_subcommand,
+        post_verify_subcommand,
+        audit_subcommand,
+        post_audit_subcommand,
+        compact_subcommand,
+        post_compact_subcommand,
+        inspect_subcommand,
+        post_inspect_subcommand
+    )
 }
 
 fn get_datatype_help() -> &'static str {
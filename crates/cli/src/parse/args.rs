@@ -1,11 +1,16 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use cryo_freeze::{ExecutionEnv, FileOutput, ParseError, Query, Source};
+use cryo_freeze::{
+    get_provenance, Datatype, ExecutionEnv, FileOutput, ParseError, Query, Source, Table,
+};
 
 use crate::args::Args;
 use clap_cryo::Parser;
 
-use super::{execution, file_output, query, source};
+use super::{execution, file_output, query, schemas, source};
 
 /// parse options for running freeze
 pub async fn parse_args(
@@ -13,11 +18,38 @@ pub async fn parse_args(
 ) -> Result<(Query, Source, FileOutput, ExecutionEnv), ParseError> {
     let source = source::parse_source(args).await?;
     let query = query::parse_query(args, Arc::new(source.clone())).await?;
-    let sink = file_output::parse_file_output(args, &source)?;
+    let mut sink = file_output::parse_file_output(args, &source)?;
     let env = execution::parse_execution_env(args, query.n_tasks() as u64)?;
+    sink.metadata.extend(get_provenance(&query, &source, &env));
+    if sink.delta {
+        // each delta table needs its own directory
+        let datatypes: Vec<Datatype> =
+            query.datatypes.iter().flat_map(|datatype| datatype.datatypes()).collect();
+        let dirs: HashSet<_> =
+            datatypes.iter().map(|datatype| sink.get_dataset_dir(*datatype)).collect();
+        if dirs.len() < datatypes.len() {
+            return Err(ParseError::ParseError(
+                "--delta requires a directory per datatype, use --layout hive or --subdirs datatype"
+                    .to_string(),
+            ))
+        }
+    }
     Ok((query, source, sink, env))
 }
 
+/// parse options for compacting existing output files, which does not require an rpc
+pub(crate) fn parse_compact_args(
+    args: &Args,
+) -> Result<(HashMap<Datatype, Table>, FileOutput), ParseError> {
+    if file_output::parse_output_url(args).is_some() {
+        return Err(ParseError::ParseError("compact requires a local output dir".to_string()))
+    }
+    let (_, schemas) = schemas::parse_schemas(args)?;
+    let file_prefix = args.network_name.clone().unwrap_or_default();
+    let sink = file_output::parse_file_output_with_prefix(args, file_prefix)?;
+    Ok((schemas, sink))
+}
+
 /// parse command string
 #[allow(dead_code)]
 pub async fn parse_str(command: &str) -> Result<Args, ParseError> {
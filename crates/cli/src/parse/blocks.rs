@@ -407,6 +407,9 @@ mod tests {
             provider: provider.into(),
             semaphore: Arc::new(None),
             rate_limiter: Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
             chain_id: 1,
             inner_request_size: 1,
             max_concurrent_chunks: None,
@@ -473,6 +476,9 @@ mod tests {
             semaphore: Arc::new(None),
             max_concurrent_chunks: Some(1),
             rate_limiter: Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
             labels: cryo_freeze::SourceLabels::default(),
         });
         for (test, res) in tests {
@@ -542,6 +548,9 @@ mod tests {
             provider: provider.into(),
             semaphore: Arc::new(None),
             rate_limiter: Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
             chain_id: 1,
             inner_request_size: 1,
             max_concurrent_chunks: Some(1),
@@ -1,5 +1,8 @@
 use crate::args::Args;
-use cryo_freeze::{ExecutionEnv, ExecutionEnvBuilder, ParseError};
+use cryo_freeze::{
+    ExecutionEnv, ExecutionEnvBuilder, ParseError, PartitionRetryPolicy, ERROR_KINDS,
+};
+use std::time::Duration;
 
 pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<ExecutionEnv, ParseError> {
     let args_str =
@@ -17,6 +20,8 @@ pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<Execution
         .verbose(verbose)
         .report(!args.no_report)
         .report_dir(args.report_dir.clone())
+        .resume(args.resume)
+        .retry(parse_retry_policy(args)?)
         .args(args_str);
 
     let builder = if !args.no_verbose {
@@ -29,3 +34,28 @@ pub(crate) fn parse_execution_env(args: &Args, n_tasks: u64) -> Result<Execution
 
     Ok(builder.build())
 }
+
+fn parse_retry_policy(args: &Args) -> Result<PartitionRetryPolicy, ParseError> {
+    let default = PartitionRetryPolicy::default();
+    let retryable = match &args.chunk_retry_errors {
+        Some(kinds) => {
+            for kind in kinds.iter() {
+                if !ERROR_KINDS.contains(&kind.as_str()) {
+                    return Err(ParseError::ParseError(format!(
+                        "invalid --chunk-retry-errors kind: {}, choose from {}",
+                        kind,
+                        ERROR_KINDS.join(", ")
+                    )))
+                }
+            }
+            kinds.clone()
+        }
+        None => default.retryable,
+    };
+    Ok(PartitionRetryPolicy {
+        max_attempts: args.chunk_retries + 1,
+        initial_backoff: Duration::from_millis(args.chunk_retry_backoff),
+        retryable,
+        ..default
+    })
+}
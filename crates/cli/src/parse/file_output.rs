@@ -1,13 +1,29 @@
 use crate::args::Args;
-use cryo_freeze::{FileFormat, FileOutput, ParseError, Source, SubDir};
+use cryo_freeze::{FileFormat, FileOutput, OutputLayout, ParseError, Source, SubDir};
 use polars::prelude::*;
 use std::fs;
 
 pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutput, ParseError> {
-    // process output directory
-    std::fs::create_dir_all(args.output_dir.clone())
+    let mut output =
+        parse_file_output_with_prefix(args, parse_network_name(args, source.chain_id))?;
+    output.metadata.insert("chain_id".to_string(), source.chain_id.to_string());
+    Ok(output)
+}
+
+/// parse file output for a given file prefix, for commands that do not connect to an rpc
+pub(crate) fn parse_file_output_with_prefix(
+    args: &Args,
+    file_prefix: String,
+) -> Result<FileOutput, ParseError> {
+    // process output directory, files for object stores are staged in a local directory
+    let output_url = parse_output_url(args);
+    let output_dir: std::path::PathBuf = match output_url {
+        Some(_) => std::env::temp_dir().join(format!("cryo_staging_{}", std::process::id())),
+        None => args.output_dir.clone().into(),
+    };
+    std::fs::create_dir_all(&output_dir)
         .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
-    let output_dir = std::fs::canonicalize(args.output_dir.clone()).map_err(|_e| {
+    let output_dir = std::fs::canonicalize(output_dir).map_err(|_e| {
         ParseError::ParseError("Failed to canonicalize output directory".to_string())
     })?;
     match fs::create_dir_all(&output_dir) {
@@ -17,34 +33,88 @@ pub(crate) fn parse_file_output(args: &Args, source: &Source) -> Result<FileOutp
 
     let label = &args.label;
 
-    let parquet_compression = parse_compression(&args.compression)?;
-
-    let row_group_size = parse_row_group_size(
-        args.row_group_size,
-        args.n_row_groups,
-        Some(args.chunk_size as usize),
-    );
+    if args.n_row_groups == Some(0) {
+        return Err(ParseError::ParseError("--n-row-groups must be greater than 0".to_string()))
+    }
+    let parquet_page_size = args
+        .page_size
+        .as_deref()
+        .map(|size| parse_byte_size(size, "--page-size").map(|size| size as usize))
+        .transpose()?;
+    let parquet_bloom_filter_columns = match &args.bloom_filter {
+        Some(columns) if columns.is_empty() => vec!["*address".to_string(), "*hash".to_string()],
+        Some(columns) => columns.clone(),
+        None => vec![],
+    };
 
     let format = parse_output_format(args)?;
-    let file_prefix = parse_network_name(args, source.chain_id);
+
+    // arrow files stay uncompressed unless asked otherwise, so that they can be memory-mapped
+    let (parquet_compression, ipc_compression) = match (&format, &args.compression) {
+        (FileFormat::Arrow, Some(compression)) => {
+            (ParquetCompression::Uncompressed, parse_ipc_compression(compression)?)
+        }
+        (FileFormat::Arrow, None) => (ParquetCompression::Uncompressed, None),
+        (_, Some(compression)) => (parse_compression(compression)?, None),
+        (_, None) => (ParquetCompression::Lz4Raw, None),
+    };
+
+    if args.max_rows_per_file == Some(0) {
+        return Err(ParseError::ParseError("--max-rows-per-file must be greater than 0".to_string()))
+    }
+    let max_file_size = args
+        .max_file_size
+        .as_deref()
+        .map(|size| parse_byte_size(size, "--max-file-size"))
+        .transpose()?;
+
+    if output_url.is_some() &&
+        (args.sqlite || args.max_rows_per_file.is_some() || max_file_size.is_some())
+    {
+        return Err(ParseError::ParseError(
+            "object store output does not support sqlite or splitting files".to_string(),
+        ))
+    }
 
     let subdirs = parse_subdirs(args);
+    let layout = parse_layout(args)?;
+    if args.delta && (format != FileFormat::Parquet || args.sqlite || output_url.is_some()) {
+        return Err(ParseError::ParseError(
+            "--delta requires parquet output in a local output dir".to_string(),
+        ))
+    }
 
     let output = FileOutput {
         output_dir,
         subdirs,
+        layout,
+        manifest: !args.no_manifest && output_url.is_none(),
         parquet_statistics: !args.no_stats,
+        delta: args.delta,
         overwrite: args.overwrite,
-        prefix: file_prefix,
+        max_rows_per_file: args.max_rows_per_file,
+        max_file_size,
+        prefix: file_prefix.clone(),
         format,
         suffix: label.clone(),
         parquet_compression,
-        row_group_size,
+        ipc_compression,
+        row_group_size: args.row_group_size,
+        n_row_groups: args.n_row_groups,
+        parquet_page_size,
+        parquet_dictionary_columns: args.dictionary.clone(),
+        parquet_bloom_filter_columns,
+        metadata: [("network".to_string(), file_prefix)].into_iter().collect(),
     };
 
     Ok(output)
 }
 
+/// object store url of output directory, e.g. `s3://bucket/prefix` or `file:///data`
+pub(crate) fn parse_output_url(args: &Args) -> Option<&str> {
+    args.output_dir.contains("://").then_some(args.output_dir.as_str())
+}
+
 pub(crate) fn parse_subdirs(args: &Args) -> Vec<SubDir> {
     let mut subdirs = Vec::new();
     for arg in args.subdirs.iter() {
@@ -59,6 +129,26 @@ pub(crate) fn parse_subdirs(args: &Args) -> Vec<SubDir> {
     subdirs
 }
 
+pub(crate) fn parse_layout(args: &Args) -> Result<OutputLayout, ParseError> {
+    match args.layout.as_str() {
+        "flat" => Ok(OutputLayout::Flat),
+        "hive" => {
+            if !args.subdirs.is_empty() {
+                return Err(ParseError::ParseError(
+                    "--subdirs cannot be used with hive layout".to_string(),
+                ))
+            }
+            if args.block_bucket_size == 0 {
+                return Err(ParseError::ParseError(
+                    "--block-bucket-size must be greater than 0".to_string(),
+                ))
+            }
+            Ok(OutputLayout::Hive { block_bucket_size: args.block_bucket_size })
+        }
+        _ => Err(ParseError::ParseError("invalid --layout, use flat or hive".to_string())),
+    }
+}
+
 pub(crate) fn parse_network_name(args: &Args, chain_id: u64) -> String {
     match &args.network_name {
         Some(name) => name.clone(),
@@ -88,19 +178,47 @@ pub(crate) fn parse_network_name(args: &Args, chain_id: u64) -> String {
     }
 }
 
+/// parse size in bytes with optional unit, e.g. 1000000, 500KB, 512MB, or 2GB
+fn parse_byte_size(size: &str, flag: &str) -> Result<u64, ParseError> {
+    let size = size.trim().to_uppercase();
+    let digits = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match size[digits.len()..].trim_end_matches('B') {
+        "" => 1,
+        "K" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        "KI" => 1 << 10,
+        "MI" => 1 << 20,
+        "GI" => 1 << 30,
+        _ => return Err(ParseError::ParseError(format!("invalid {}: {}", flag, size))),
+    };
+    match digits.trim().parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n * multiplier),
+        _ => Err(ParseError::ParseError(format!("invalid {}: {}", flag, size))),
+    }
+}
+
 pub(crate) fn parse_output_format(args: &Args) -> Result<FileFormat, ParseError> {
-    match (args.csv, args.json) {
-        (true, true) => {
-            Err(ParseError::ParseError("choose one of parquet, csv, or json".to_string()))
-        }
-        (true, _) => Ok(FileFormat::Csv),
-        (_, true) => Ok(FileFormat::Json),
-        (false, false) => Ok(FileFormat::Parquet),
+    let flags = [
+        (args.csv, FileFormat::Csv),
+        (args.json, FileFormat::Json),
+        (args.ndjson, FileFormat::Ndjson),
+        (args.arrow, FileFormat::Arrow),
+    ];
+    let formats: Vec<FileFormat> =
+        flags.into_iter().filter(|(flag, _)| *flag).map(|(_, format)| format).collect();
+    // sqlite output is written by a database sink rather than in a file format
+    match (formats.as_slice(), args.sqlite) {
+        ([], _) => Ok(FileFormat::Parquet),
+        ([format], false) => Ok(format.clone()),
+        _ => Err(ParseError::ParseError(
+            "choose one of parquet, csv, json, ndjson, arrow, or sqlite".to_string(),
+        )),
     }
 }
 
-fn parse_compression(input: &Vec<String>) -> Result<ParquetCompression, ParseError> {
-    match input.as_slice() {
+fn parse_compression(input: &[String]) -> Result<ParquetCompression, ParseError> {
+    match input {
         [algorithm] if algorithm.as_str() == "uncompressed" => Ok(ParquetCompression::Uncompressed),
         [algorithm] if algorithm.as_str() == "snappy" => Ok(ParquetCompression::Snappy),
         [algorithm] if algorithm.as_str() == "lzo" => Ok(ParquetCompression::Lzo),
@@ -135,14 +253,16 @@ fn parse_compression(input: &Vec<String>) -> Result<ParquetCompression, ParseErr
     }
 }
 
-fn parse_row_group_size(
-    row_group_size: Option<usize>,
-    n_row_groups: Option<usize>,
-    chunk_size: Option<usize>,
-) -> Option<usize> {
-    match (row_group_size, n_row_groups, chunk_size) {
-        (Some(row_group_size), _, _) => Some(row_group_size),
-        (_, Some(n_row_groups), Some(cs)) => Some((cs + n_row_groups - 1) / n_row_groups),
-        _ => None,
+fn parse_ipc_compression(input: &[String]) -> Result<Option<IpcCompression>, ParseError> {
+    match input {
+        [algorithm] if algorithm.as_str() == "uncompressed" => Ok(None),
+        [algorithm] if algorithm.as_str() == "lz4" => Ok(Some(IpcCompression::LZ4)),
+        // arrow ipc does not use compression levels
+        [algorithm] | [algorithm, _] if algorithm.as_str() == "zstd" => {
+            Ok(Some(IpcCompression::ZSTD))
+        }
+        _ => Err(ParseError::ParseError(
+            "arrow compression must be one of uncompressed, lz4, or zstd".to_string(),
+        )),
     }
 }
@@ -10,6 +10,7 @@ mod source;
 mod timestamps;
 
 pub use args::*;
+pub(crate) use file_output::parse_output_url;
 #[allow(unused_imports)]
 pub use query::*;
 use schemas::*;
@@ -32,9 +32,12 @@ pub(crate) fn parse_schemas(
     // parse inputs
     let datatypes = parse_datatypes(&args.datatype)?;
     let sort = parse_sort_columns(&args.sort, &datatypes)?;
-    let u256_types = parse_u256_types(args)?;
     let output_format = file_output::parse_output_format(args)?;
-    let binary_column_format = match args.hex | (output_format != FileFormat::Parquet) {
+    let u256_types = parse_u256_types(args, &output_format)?;
+    let binary_output = args.postgres.is_some() ||
+        args.sqlite ||
+        matches!(output_format, FileFormat::Parquet | FileFormat::Arrow);
+    let binary_column_format = match args.hex | !binary_output {
         true => ColumnEncoding::Hex,
         false => ColumnEncoding::Binary,
     };
@@ -84,28 +87,30 @@ pub(crate) fn parse_schemas(
     Ok((datatypes, schemas?))
 }
 
-fn parse_u256_types(args: &Args) -> Result<Vec<U256Type>, ParseError> {
-    args.u256_types.as_ref().map_or(
-        Ok(vec![U256Type::Binary, U256Type::String, U256Type::F64]),
-        |raw_u256_types| {
-            raw_u256_types
-                .iter()
-                .map(|raw| {
-                    let lower_case = raw.to_lowercase();
-                    match lower_case.as_str() {
-                        "binary" => Ok(U256Type::Binary),
-                        "string" | "str" => Ok(U256Type::String),
-                        "f32" | "float32" => Ok(U256Type::F32),
-                        "f64" | "float64" | "float" => Ok(U256Type::F64),
-                        "u32" | "uint32" => Ok(U256Type::U32),
-                        "u64" | "uint64" => Ok(U256Type::U64),
-                        "decimal128" | "d128" => Ok(U256Type::Decimal128),
-                        _ => Err(ParseError::ParseError(format!("invalid u256 type: {}", raw))),
-                    }
-                })
-                .collect()
-        },
-    )
+fn parse_u256_types(args: &Args, output_format: &FileFormat) -> Result<Vec<U256Type>, ParseError> {
+    // ndjson records keep u256 values only as lossless decimal strings by default
+    let default_u256_types = match output_format {
+        FileFormat::Ndjson => vec![U256Type::String],
+        _ => vec![U256Type::Binary, U256Type::String, U256Type::F64],
+    };
+    args.u256_types.as_ref().map_or(Ok(default_u256_types), |raw_u256_types| {
+        raw_u256_types
+            .iter()
+            .map(|raw| {
+                let lower_case = raw.to_lowercase();
+                match lower_case.as_str() {
+                    "binary" => Ok(U256Type::Binary),
+                    "string" | "str" => Ok(U256Type::String),
+                    "f32" | "float32" => Ok(U256Type::F32),
+                    "f64" | "float64" | "float" => Ok(U256Type::F64),
+                    "u32" | "uint32" => Ok(U256Type::U32),
+                    "u64" | "uint64" => Ok(U256Type::U64),
+                    "decimal128" | "d128" => Ok(U256Type::Decimal128),
+                    _ => Err(ParseError::ParseError(format!("invalid u256 type: {}", raw))),
+                }
+            })
+            .collect()
+    })
 }
 
 fn ensure_included_columns(
@@ -1,61 +1,133 @@
 use std::env;
 
 use crate::args::Args;
-use cryo_freeze::{sources::ProviderWrapper, ParseError, Source, SourceLabels};
+use cryo_freeze::{
+    sources::ProviderWrapper, AdaptiveLimiter, AdaptiveRetryPolicy, BatchHttp, ParseError,
+    PoolEndpoint, RequestCache, RpcPool, Source, SourceLabels,
+};
 use ethers::prelude::*;
 use governor::{Quota, RateLimiter};
 use polars::prelude::*;
-use std::num::NonZeroU32;
+use std::{num::NonZeroU32, path::Path, str::FromStr};
 
 pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
     // parse network info
-    let rpc_url = parse_rpc_url(args)?;
-    let (provider, chain_id): (ProviderWrapper, u64) = if rpc_url.starts_with("http") {
-        let provider = Provider::<RetryClient<Http>>::new_client(
-            &rpc_url,
+    let rpc_urls = parse_rpc_urls(args)?;
+    let max_concurrent_requests = args.max_concurrent_requests.unwrap_or(100);
+    let max_batch_size = args.max_batch_size.unwrap_or(1) as usize;
+    let n_permits = max_concurrent_requests as usize * rpc_urls.len();
+
+    // adaptive limits replace the fixed concurrency and rate limits of the source, and are
+    // applied by the http transport so that they count each attempt of a request
+    let adaptive_limiter = match args.adaptive_limits {
+        true if rpc_urls.len() > 1 => {
+            let message = "--adaptive-limits is not supported with multiple rpc endpoints";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+        true if !rpc_urls[0].starts_with("http") => {
+            let message = "--adaptive-limits requires an http rpc url";
+            return Err(ParseError::ParseError(message.to_string()))
+        }
+        true => {
+            let max_requests_per_second = args.requests_per_second.map(|x| x as u64);
+            Some(Arc::new(AdaptiveLimiter::new(n_permits as u64, max_requests_per_second)))
+        }
+        false => None,
+    };
+
+    // batching transports apply rate limits and adaptive limits per http request
+    let batch_transport = rpc_urls.len() == 1 && (max_batch_size > 1 || adaptive_limiter.is_some());
+
+    let (provider, chain_id): (ProviderWrapper, u64) = if rpc_urls.len() > 1 {
+        parse_pool(&rpc_urls, args, max_concurrent_requests).await?
+    } else if batch_transport && rpc_urls[0].starts_with("http") {
+        let max_requests_per_second = match adaptive_limiter {
+            Some(_) => None,
+            None => args.requests_per_second,
+        };
+        let mut transport = BatchHttp::new(&rpc_urls[0], max_batch_size, max_requests_per_second)
+            .map_err(|e| ParseError::ParseError(e.to_string()))?;
+        if let Some(limiter) = &adaptive_limiter {
+            transport = transport.with_adaptive_limiter(limiter.clone());
+        }
+        let client = RetryClient::new(
+            transport,
+            get_retry_policy(&adaptive_limiter),
             args.max_retries,
             args.initial_backoff,
-        )
-        .map_err(|_e| ParseError::ParseError("could not connect to provider".to_string()))?;
+        );
+        let provider = Provider::new(client);
+        let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+        (provider.into(), chain_id)
+    } else if max_batch_size > 1 {
+        let message = "--max-batch-size requires an http rpc url";
+        return Err(ParseError::ParseError(message.to_string()))
+    } else if rpc_urls[0].starts_with("http") {
+        let transport = Http::from_str(&rpc_urls[0])
+            .map_err(|_e| ParseError::ParseError("could not connect to provider".to_string()))?;
+        let client = RetryClient::new(
+            transport,
+            Box::new(HttpRateLimitRetryPolicy),
+            args.max_retries,
+            args.initial_backoff,
+        );
+        let provider = Provider::new(client);
         let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
         (provider.into(), chain_id)
-    } else if rpc_url.starts_with("ws") {
-        let provider = Provider::<Ws>::connect(&rpc_url).await.map_err(|_| {
+    } else if rpc_urls[0].starts_with("ws") {
+        let provider = Provider::<Ws>::connect(&rpc_urls[0]).await.map_err(|_| {
             ParseError::ParseError("could not instantiate HTTP Provider".to_string())
         })?;
         let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
         (provider.into(), chain_id)
-    } else if rpc_url.ends_with(".ipc") {
-        let provider: Provider<Ipc> = Provider::connect_ipc(&rpc_url).await.map_err(|_| {
+    } else if rpc_urls[0].ends_with(".ipc") {
+        let provider: Provider<Ipc> = Provider::connect_ipc(&rpc_urls[0]).await.map_err(|_| {
             ParseError::ParseError("could not instantiate HTTP Provider".to_string())
         })?;
         let chain_id = provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
         (provider.into(), chain_id)
     } else {
-        return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)));
+        return Err(ParseError::ParseError(format!("invalid rpc url: {}", rpc_urls[0])));
     };
 
-    let rate_limiter = match args.requests_per_second {
-        Some(rate_limit) => match (NonZeroU32::new(1), NonZeroU32::new(rate_limit)) {
-            (Some(one), Some(value)) => {
-                let quota = Quota::per_second(value).allow_burst(one);
-                Some(RateLimiter::direct(quota))
+    // endpoints of pools and batching transports have their own rate limits
+    let transport_limited = batch_transport || rpc_urls.len() > 1;
+    let rate_limiter = match (args.requests_per_second, transport_limited, &adaptive_limiter) {
+        (Some(rate_limit), false, None) => {
+            match (NonZeroU32::new(1), NonZeroU32::new(rate_limit)) {
+                (Some(one), Some(value)) => {
+                    let quota = Quota::per_second(value).allow_burst(one);
+                    Some(RateLimiter::direct(quota))
+                }
+                _ => None,
             }
-            _ => None,
-        },
-        None => None,
+        }
+        _ => None,
     };
 
     // process concurrency info
-    let max_concurrent_requests = args.max_concurrent_requests.unwrap_or(100);
     let max_concurrent_chunks = match args.max_concurrent_chunks {
         Some(0) => None,
         Some(max) => Some(max),
         None => Some(4),
     };
 
-    let semaphore = tokio::sync::Semaphore::new(max_concurrent_requests as usize);
-    let semaphore = Arc::new(Some(semaphore));
+    let semaphore = match adaptive_limiter {
+        Some(_) => None,
+        None => Some(tokio::sync::Semaphore::new(n_permits)),
+    };
+    let semaphore = Arc::new(semaphore);
+
+    // responses of blocks within the reorg buffer are never cached
+    let request_cache = match &args.cache_dir {
+        Some(cache_dir) => {
+            let max_size = args.cache_size * 1_000_000;
+            let cache = RequestCache::open(Path::new(cache_dir), max_size, args.reorg_buffer)
+                .map_err(|e| ParseError::ParseError(e.to_string()))?;
+            Some(Arc::new(cache))
+        }
+        None => None,
+    };
 
     let output = Source {
         chain_id,
@@ -63,47 +135,140 @@ pub(crate) async fn parse_source(args: &Args) -> Result<Source, ParseError> {
         max_concurrent_chunks,
         semaphore,
         rate_limiter: rate_limiter.into(),
-        rpc_url,
+        adaptive_limiter,
+        request_cache,
+        capabilities: Default::default(),
+        rpc_url: rpc_urls.join(","),
         provider,
         labels: SourceLabels {
             max_concurrent_requests: args.requests_per_second.map(|x| x as u64),
             max_requests_per_second: args.requests_per_second.map(|x| x as u64),
             max_retries: Some(args.max_retries),
             initial_backoff: Some(args.initial_backoff),
+            max_batch_size: args.max_batch_size,
         },
     };
 
     Ok(output)
 }
 
-pub(crate) fn parse_rpc_url(args: &Args) -> Result<String, ParseError> {
-    // get MESC url
-    let mesc_url = if mesc::is_mesc_enabled() {
-        let endpoint = match &args.rpc {
-            Some(url) => mesc::get_endpoint_by_query(url, Some("cryo"))?,
-            None => mesc::get_default_endpoint(Some("cryo"))?,
-        };
-        endpoint.map(|endpoint| endpoint.url)
-    } else {
-        None
+/// create pool of http endpoints, each with its own concurrency and rate limits
+async fn parse_pool(
+    rpc_urls: &[String],
+    args: &Args,
+    max_concurrent_requests: u64,
+) -> Result<(ProviderWrapper, u64), ParseError> {
+    let mut endpoints = Vec::new();
+    let mut chain_id = None;
+    for rpc_url in rpc_urls.iter() {
+        if !rpc_url.starts_with("http") {
+            let message = format!("rpc pools only support http endpoints: {}", rpc_url);
+            return Err(ParseError::ParseError(message))
+        }
+
+        // every endpoint of the pool must serve the same chain
+        let provider = Provider::<Http>::try_from(rpc_url.as_str())
+            .map_err(|_e| ParseError::ParseError(format!("invalid rpc url: {}", rpc_url)))?;
+        let endpoint_chain_id =
+            provider.get_chainid().await.map_err(ParseError::ProviderError)?.as_u64();
+        match chain_id {
+            Some(chain_id) if chain_id != endpoint_chain_id => {
+                let message = "all rpc endpoints must have the same chain id";
+                return Err(ParseError::ParseError(message.to_string()))
+            }
+            _ => chain_id = Some(endpoint_chain_id),
+        }
+
+        let endpoint = PoolEndpoint::new(
+            rpc_url,
+            args.max_retries,
+            args.initial_backoff,
+            Some(max_concurrent_requests),
+            args.requests_per_second,
+            args.max_batch_size.unwrap_or(1) as usize,
+            Box::new(HttpRateLimitRetryPolicy),
+        )
+        .map_err(|e| ParseError::ParseError(e.to_string()))?;
+        endpoints.push(endpoint);
+    }
+    let pool = RpcPool::new(endpoints, args.max_retries, args.initial_backoff)
+        .map_err(|e| ParseError::ParseError(e.to_string()))?;
+    let chain_id = chain_id.ok_or(ParseError::ParseError("no rpc endpoints".to_string()))?;
+    Ok((Provider::new(pool).into(), chain_id))
+}
+
+/// retry policy of http providers, which reports rate limit errors to adaptive limiter if any
+fn get_retry_policy(
+    adaptive_limiter: &Option<Arc<AdaptiveLimiter>>,
+) -> Box<dyn RetryPolicy<HttpClientError>> {
+    match adaptive_limiter {
+        Some(limiter) => Box::new(AdaptiveRetryPolicy::new(limiter.clone())),
+        None => Box::new(HttpRateLimitRetryPolicy),
+    }
+}
+
+/// parse rpc urls, `--rpc` takes a comma-separated list of urls or MESC queries
+pub(crate) fn parse_rpc_urls(args: &Args) -> Result<Vec<String>, ParseError> {
+    let queries: Vec<&str> = match &args.rpc {
+        Some(rpc) => rpc.split(',').map(|query| query.trim()).filter(|q| !q.is_empty()).collect(),
+        None => vec![],
     };
 
-    // use ETH_RPC_URL if no MESC url found
-    let url = if let Some(url) = mesc_url {
-        url
-    } else if let Some(url) = &args.rpc {
-        url.clone()
-    } else if let Ok(url) = env::var("ETH_RPC_URL") {
-        url
+    let mut urls = Vec::new();
+    if queries.is_empty() {
+        // get MESC url
+        if mesc::is_mesc_enabled() {
+            if let Some(endpoint) = mesc::get_default_endpoint(Some("cryo"))? {
+                urls.extend(get_mesc_urls(endpoint)?);
+            }
+        }
+
+        // use ETH_RPC_URL if no MESC url found
+        if urls.is_empty() {
+            match env::var("ETH_RPC_URL") {
+                Ok(url) => urls.extend(url.split(',').map(|url| url.trim().to_string())),
+                Err(_) => {
+                    let message = "must provide --rpc or setup MESC or set ETH_RPC_URL";
+                    return Err(ParseError::ParseError(message.to_string()))
+                }
+            }
+        }
     } else {
-        let message = "must provide --rpc or setup MESC or set ETH_RPC_URL";
-        return Err(ParseError::ParseError(message.to_string()))
-    };
+        for query in queries.into_iter() {
+            let endpoint = match mesc::is_mesc_enabled() {
+                true => mesc::get_endpoint_by_query(query, Some("cryo"))?,
+                false => None,
+            };
+            match endpoint {
+                Some(endpoint) => urls.extend(get_mesc_urls(endpoint)?),
+                None => urls.push(query.to_string()),
+            }
+        }
+    }
 
     // prepend http or https if need be
-    if !url.starts_with("http") & !url.starts_with("ws") & !url.ends_with(".ipc") {
-        Ok("http://".to_string() + url.as_str())
-    } else {
-        Ok(url)
+    let urls = urls
+        .into_iter()
+        .map(|url| {
+            if !url.starts_with("http") & !url.starts_with("ws") & !url.ends_with(".ipc") {
+                "http://".to_string() + url.as_str()
+            } else {
+                url
+            }
+        })
+        .collect();
+    Ok(urls)
+}
+
+/// urls of MESC endpoint, endpoints with a `pool` list of endpoint names in their metadata
+/// resolve to the urls of those endpoints
+fn get_mesc_urls(endpoint: mesc::Endpoint) -> Result<Vec<String>, ParseError> {
+    match endpoint.endpoint_metadata.get("pool").and_then(|pool| pool.as_array()) {
+        Some(pool) => pool
+            .iter()
+            .filter_map(|name| name.as_str())
+            .map(|name| Ok(mesc::get_endpoint_by_name(name)?.url))
+            .collect(),
+        None => Ok(vec![endpoint.url]),
     }
 }
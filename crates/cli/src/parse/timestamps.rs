@@ -330,8 +330,8 @@ mod tests {
     use ethers::prelude::*;
 
     async fn setup_source() -> Source {
-        let rpc_url = match crate::parse::source::parse_rpc_url(&Args::default()) {
-            Ok(url) => url,
+        let rpc_url = match crate::parse::source::parse_rpc_urls(&Args::default()) {
+            Ok(urls) => urls[0].clone(),
             Err(_) => std::process::exit(0),
         };
         let max_retry = 5;
@@ -351,6 +351,9 @@ mod tests {
             provider: provider.into(),
             semaphore: Arc::new(Some(semaphore)),
             rate_limiter: Arc::new(rate_limiter),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
             chain_id: 1,
             inner_request_size: 1,
             max_concurrent_chunks: None,
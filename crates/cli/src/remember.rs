@@ -3,6 +3,9 @@
 // - only one default command is remembered for each directory
 // - remembered commands are only activated when datatypes are omitted
 // - can add `--dry` or any other additional arguments to override remembered arguments
+//
+// using --resume without datatypes recalls the command of the latest incomplete report
+// - incomplete reports are left behind by jobs that were interrupted before finishing
 
 use crate::args::Args;
 use cryo_freeze::ParseError;
@@ -55,3 +58,42 @@ pub(crate) fn load_remembered_command(cryo_dir: PathBuf) -> Result<RememberedCom
 pub(crate) fn get_remembered_command_path(cryo_dir: PathBuf) -> Result<PathBuf, ParseError> {
     Ok(cryo_dir.join(REMEMBER_FILENAME))
 }
+
+#[derive(Deserialize)]
+pub(crate) struct InterruptedCommand {
+    pub(crate) cli_command: Option<Vec<String>>,
+    pub(crate) args: Option<String>,
+}
+
+pub(crate) fn load_interrupted_command(
+    report_dir: PathBuf,
+) -> Result<(Vec<String>, Args), ParseError> {
+    let message = "no incomplete report found to resume, specify datasets to collect";
+    let entries =
+        std::fs::read_dir(report_dir).map_err(|_| ParseError::ParseError(message.to_string()))?;
+    let path = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with("incomplete_") && name.ends_with(".json"))
+                .unwrap_or(false)
+        })
+        .max()
+        .ok_or(ParseError::ParseError(message.to_string()))?;
+
+    let mut contents = String::new();
+    let mut file = File::open(path)
+        .map_err(|_| ParseError::ParseError("could not open incomplete report".to_string()))?;
+    file.read_to_string(&mut contents)
+        .map_err(|_| ParseError::ParseError("could not read incomplete report".to_string()))?;
+    let report: InterruptedCommand = serde_json::from_str(&contents).map_err(|_| {
+        ParseError::ParseError("could not deserialize incomplete report".to_string())
+    })?;
+    let args = report
+        .args
+        .ok_or(ParseError::ParseError("incomplete report does not record args".to_string()))?;
+    let args: Args = serde_json::from_str(&args)
+        .map_err(|_| ParseError::ParseError("could not deserialize report args".to_string()))?;
+    Ok((report.cli_command.unwrap_or_default(), args))
+}
@@ -2,8 +2,14 @@ use crate::{args, parse, remember};
 use clap_cryo::Parser;
 use color_print::cstr;
 use colored::Colorize;
-use cryo_freeze::{err, CollectError, ExecutionEnv, FreezeSummary};
-use std::{sync::Arc, time::SystemTime};
+use cryo_freeze::{
+    err, CollectError, CompactOptions, ExecutionEnv, FollowOptions, FreezeSummary, ObjectStoreSink,
+    PostgresSink, Query, Sink, Source, SqliteSink,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 /// run cli
 pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -11,10 +17,41 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     if args.datatype.first() == Some(&"help".to_string()) {
         return handle_help_subcommands(args).await
     }
+    if args.datatype.first() == Some(&"verify-canonical".to_string()) {
+        return handle_verify_canonical(args).await
+    }
+    if args.datatype.first() == Some(&"audit".to_string()) {
+        return handle_audit(args)
+    }
+    if args.datatype.first() == Some(&"compact".to_string()) {
+        return handle_compact(args)
+    }
+    if args.datatype.first() == Some(&"inspect".to_string()) {
+        return handle_inspect(args)
+    }
 
     let cryo_dir: std::path::PathBuf = args.output_dir.clone().into();
     let cryo_dir = cryo_dir.join(".cryo");
 
+    // resume interrupted command
+    let args = if args.datatype.is_empty() && args.resume {
+        let report_dir = match &args.report_dir {
+            Some(report_dir) => report_dir.clone(),
+            None => cryo_dir.join("reports"),
+        };
+        let (command, interrupted) = remember::load_interrupted_command(report_dir)?;
+        println!(
+            "{} {} {}",
+            "resuming interrupted command:".truecolor(170, 170, 170),
+            "cryo".bold().white(),
+            command.into_iter().skip(1).collect::<Vec<_>>().join(" ").white().bold()
+        );
+        println!();
+        args.merge_with_precedence(interrupted)
+    } else {
+        args
+    };
+
     // remember previous command
     let args = if args.datatype.is_empty() {
         let remembered = remember::load_remembered_command(cryo_dir.clone())?;
@@ -51,7 +88,42 @@ pub async fn run(args: args::Args) -> Result<Option<FreezeSummary>, CollectError
     let source = Arc::new(source);
     let env = ExecutionEnv { t_start_parse, ..env };
     let env = env.set_start_time();
-    cryo_freeze::freeze(&query, &source, &sink, &env).await
+    match (&args.postgres, parse::parse_output_url(&args)) {
+        (Some(url), _) => {
+            let sink = PostgresSink::connect(url, &sink).await?;
+            run_query(&args, &query, &source, &sink, &env).await
+        }
+        (None, Some(url)) => {
+            let sink = ObjectStoreSink::new(url, sink)?;
+            run_query(&args, &query, &source, &sink, &env).await
+        }
+        (None, None) if args.sqlite => {
+            let sink = SqliteSink::new(&sink);
+            run_query(&args, &query, &source, &sink, &env).await
+        }
+        (None, None) => run_query(&args, &query, &source, &sink, &env).await,
+    }
+}
+
+async fn run_query<S: Sink + Clone + 'static>(
+    args: &args::Args,
+    query: &Query,
+    source: &Source,
+    sink: &S,
+    env: &ExecutionEnv,
+) -> Result<Option<FreezeSummary>, CollectError> {
+    if args.follow {
+        if args.n_chunks.is_some() {
+            return Err(err("--follow cannot be used with --n-chunks"))
+        }
+        let options = FollowOptions {
+            chunk_size: args.chunk_size,
+            poll_interval: Duration::from_secs(args.poll_interval),
+        };
+        cryo_freeze::follow(query, source, sink, env, &options).await?;
+        return Ok(None)
+    }
+    cryo_freeze::freeze(query, source, sink, env).await
 }
 
 async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
@@ -98,3 +170,151 @@ async fn handle_help_subcommands(args: args::Args) -> Result<Option<FreezeSummar
     }
     Ok(None)
 }
+
+async fn handle_verify_canonical(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    if args.datatype.len() == 1 {
+        return Err(err("specify datatype(s) to verify"))
+    }
+    let args = args::Args { datatype: args.datatype[1..].to_vec(), ..args };
+    let (query, source, sink, env) = match parse::parse_args(&args).await {
+        Ok(opts) => opts,
+        Err(e) => return Err(e.into()),
+    };
+    let summary = cryo_freeze::verify_canonical(&query, Arc::new(source), &sink, &env).await?;
+    if env.verbose >= 1 {
+        if !summary.reorged.is_empty() || !summary.errored.is_empty() {
+            println!();
+        }
+        println!("{} files canonical", summary.canonical.len());
+        println!("{} files reorged", summary.reorged.len());
+        println!("{} files repaired", summary.repaired.len());
+        if !summary.unverifiable.is_empty() {
+            println!(
+                "{} files without block_hash could not be verified",
+                summary.unverifiable.len()
+            );
+        }
+    }
+    for (path, e) in summary.errored.iter() {
+        eprintln!("could not verify {}: {}", path.to_string_lossy(), e);
+    }
+    Ok(None)
+}
+
+fn handle_audit(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let dir = match args.datatype.as_slice() {
+        [_] => args.output_dir.clone(),
+        [_, dir] => dir.clone(),
+        _ => return Err(err("audit takes a single directory")),
+    };
+    let report = cryo_freeze::audit(std::path::Path::new(&dir))?;
+
+    for (key, dataset) in report.datasets.iter() {
+        let (first, last) = match (dataset.files.first(), dataset.files.last()) {
+            (Some(first), Some(last)) => (first.start_block, last.end_block),
+            _ => continue,
+        };
+        let name = match &key.label {
+            Some(label) => format!("{} {} ({})", key.network, key.datatype, label),
+            None => format!("{} {}", key.network, key.datatype),
+        };
+        println!(
+            "{}: {} files, blocks {} to {}",
+            name.bold().white(),
+            dataset.files.len(),
+            first,
+            last
+        );
+        for (start, end) in dataset.gaps.iter() {
+            println!("- missing blocks {} to {}", start, end);
+        }
+        for (first, second) in dataset.overlaps.iter() {
+            println!(
+                "- overlapping files {} and {}",
+                first.to_string_lossy(),
+                second.to_string_lossy()
+            );
+        }
+        if !dataset.gaps.is_empty() {
+            println!("- to fill gaps: --blocks {}", dataset.fill_blocks_args().join(" "));
+        }
+    }
+
+    let problems = [
+        ("unreadable files", &report.unreadable),
+        ("files with zero rows", &report.empty),
+        ("files not matching manifest", &report.truncated),
+        ("leftover _tmp files", &report.tmp_files),
+    ];
+    for (name, paths) in problems.iter() {
+        if !paths.is_empty() {
+            println!();
+            println!("{}:", name.bold().white());
+            for path in paths.iter() {
+                println!("- {}", path.to_string_lossy());
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn handle_inspect(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    let path = match args.datatype.as_slice() {
+        [_, path] => path.clone(),
+        _ => return Err(err("inspect takes a single file")),
+    };
+    let provenance = cryo_freeze::read_provenance(std::path::Path::new(&path))?;
+    if provenance.is_empty() {
+        println!("no provenance metadata in {}", path);
+    }
+    for (key, value) in provenance.iter() {
+        // json values such as the cli command and schema are printed as their items
+        match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(serde_json::Value::Array(items)) if key == "cli_command" => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| item.as_str().unwrap_or_default().to_string())
+                    .collect();
+                println!("{}: {}", key.bold().white(), items.join(" "));
+            }
+            Ok(serde_json::Value::Array(items)) => {
+                println!("{}:", key.bold().white());
+                for item in items.iter() {
+                    println!("- {}", item);
+                }
+            }
+            _ => println!("{}: {}", key.bold().white(), value),
+        }
+    }
+    Ok(None)
+}
+
+fn handle_compact(args: args::Args) -> Result<Option<FreezeSummary>, CollectError> {
+    if args.datatype.len() == 1 {
+        return Err(err("specify datatype(s) to compact"))
+    }
+    let args = args::Args { datatype: args.datatype[1..].to_vec(), ..args };
+    let (schemas, sink) = parse::parse_compact_args(&args)?;
+    let options = CompactOptions { chunk_size: args.chunk_size, align: args.align, dry: args.dry };
+    let summary = cryo_freeze::compact(&schemas, &sink, &options)?;
+
+    let verb = if args.dry { "would merge" } else { "merged" };
+    for (merged, inputs) in summary.merged.iter() {
+        println!("{} {} files into {}", verb, inputs.len(), merged.to_string_lossy());
+    }
+    let verb = if args.dry { "would remove" } else { "removed" };
+    for path in summary.superseded.iter() {
+        println!("{} superseded file {}", verb, path.to_string_lossy());
+    }
+    if !summary.overlapping.is_empty() {
+        println!();
+        println!("{}:", "skipped datasets with overlapping files".bold().white());
+        for path in summary.overlapping.iter() {
+            println!("- {}", path.to_string_lossy());
+        }
+    }
+    if summary.merged.is_empty() && summary.superseded.is_empty() {
+        println!("no files to compact");
+    }
+    Ok(None)
+}
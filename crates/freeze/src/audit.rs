@@ -0,0 +1,302 @@
+use crate::{err, manifest::MANIFEST_FILENAME, CollectError, Manifest};
+use polars::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// output file identified from its path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputFile {
+    /// path of file
+    pub path: PathBuf,
+    /// network name
+    pub network: String,
+    /// datatype name
+    pub datatype: String,
+    /// label and any non-block partition labels
+    pub label: Option<String>,
+    /// first block of file
+    pub start_block: u64,
+    /// last block of file
+    pub end_block: u64,
+    /// file extension
+    pub format: String,
+}
+
+/// files of a dataset that share network, datatype, and label
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DatasetKey {
+    /// network name
+    pub network: String,
+    /// datatype name
+    pub datatype: String,
+    /// label and any non-block partition labels
+    pub label: Option<String>,
+}
+
+/// audit of the files of a single dataset
+#[derive(Clone, Debug, Default)]
+pub struct DatasetAudit {
+    /// files sorted by block range
+    pub files: Vec<OutputFile>,
+    /// block ranges missing between files, inclusive
+    pub gaps: Vec<(u64, u64)>,
+    /// pairs of files with overlapping block ranges
+    pub overlaps: Vec<(PathBuf, PathBuf)>,
+}
+
+impl DatasetAudit {
+    /// `--blocks` arguments that would fill the gaps of dataset
+    pub fn fill_blocks_args(&self) -> Vec<String> {
+        self.gaps.iter().map(|(start, end)| format!("{}:{}", start, end + 1)).collect()
+    }
+}
+
+/// audit of an output directory
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    /// audits of each dataset
+    pub datasets: BTreeMap<DatasetKey, DatasetAudit>,
+    /// parquet files that could not be read
+    pub unreadable: Vec<PathBuf>,
+    /// files with zero rows for datatypes that have a row for every block
+    pub empty: Vec<PathBuf>,
+    /// files whose size does not match their manifest entry
+    pub truncated: Vec<PathBuf>,
+    /// leftover `_tmp` files of interrupted writes
+    pub tmp_files: Vec<PathBuf>,
+}
+
+/// audit output directory for missing or overlapping block ranges and damaged files
+pub fn audit(dir: &Path) -> Result<AuditReport, CollectError> {
+    let mut report = AuditReport::default();
+    let mut manifests: HashMap<PathBuf, Manifest> = HashMap::new();
+    let mut files = Vec::new();
+    for path in list_files(dir)?.into_iter() {
+        let filename = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if filename.ends_with("_tmp") {
+            report.tmp_files.push(path);
+        } else if filename == MANIFEST_FILENAME {
+            manifests.insert(path.clone(), Manifest::read(&path)?);
+        } else if let Some(file) = parse_output_path(&path) {
+            files.push(file);
+        }
+    }
+
+    for file in files.into_iter() {
+        // check integrity of file
+        if file.format == "parquet" {
+            match count_parquet_rows(&file.path) {
+                Some(0) if has_row_per_block(&file.datatype) => {
+                    report.empty.push(file.path.clone())
+                }
+                Some(_) => {}
+                None => report.unreadable.push(file.path.clone()),
+            }
+        }
+        // hive files are nested below the manifest of their dataset directory
+        for ancestor in file.path.ancestors().skip(1) {
+            let manifest_path = ancestor.join(MANIFEST_FILENAME);
+            match manifests.get(&manifest_path) {
+                Some(manifest) if manifest.get_entry(&manifest_path, &file.path).is_some() => {
+                    if !manifest.is_intact(&manifest_path, &file.path) {
+                        report.truncated.push(file.path.clone());
+                    }
+                    break
+                }
+                _ => {}
+            }
+        }
+
+        let key = DatasetKey {
+            network: file.network.clone(),
+            datatype: file.datatype.clone(),
+            label: file.label.clone(),
+        };
+        report.datasets.entry(key).or_default().files.push(file);
+    }
+
+    for dataset in report.datasets.values_mut() {
+        dataset.files.sort_by_key(|file| (file.start_block, file.end_block));
+        let (gaps, overlaps) = find_gaps_and_overlaps(&dataset.files);
+        dataset.gaps = gaps;
+        dataset.overlaps = overlaps;
+    }
+
+    Ok(report)
+}
+
+/// parse output file path into its components, the inverse of `FileOutput::get_path`
+///
+/// recognizes both flat and hive layouts, only for files partitioned by block range
+pub fn parse_output_path(path: &Path) -> Option<OutputFile> {
+    let format = path.extension()?.to_str()?.to_string();
+    let stem = path.file_stem()?.to_str()?;
+
+    // hive layout, e.g.
+    // network=ethereum/datatype=logs/block_bucket=00010000/part-00010000_to_00010999.parquet
+    if let Some(range) = stem.strip_prefix("part-") {
+        let (start_block, end_block) = parse_block_range(range)?;
+        let mut network = None;
+        let mut datatype = None;
+        let mut labels = Vec::new();
+        for component in path.parent()?.components() {
+            let component = component.as_os_str().to_str()?;
+            match component.split_once('=') {
+                Some(("network", value)) => network = Some(value.to_string()),
+                Some(("datatype", value)) => datatype = Some(value.to_string()),
+                Some(("block_bucket", _)) => {}
+                Some(_) => labels.push(component.to_string()),
+                None => {}
+            }
+        }
+        let label = if labels.is_empty() { None } else { Some(labels.join("__")) };
+        return Some(OutputFile {
+            path: path.to_path_buf(),
+            network: network?,
+            datatype: datatype?,
+            label,
+            start_block,
+            end_block,
+            format,
+        })
+    }
+
+    // flat layout, e.g. ethereum__logs__00010000_to_00010999.parquet
+    let pieces: Vec<&str> = stem.split("__").collect();
+    if pieces.len() < 3 {
+        return None
+    }
+    let mut range = None;
+    let mut labels = Vec::new();
+    for piece in pieces[2..].iter() {
+        match (range, parse_block_range(piece)) {
+            (None, Some(parsed)) => range = Some(parsed),
+            _ => labels.push(*piece),
+        }
+    }
+    let (start_block, end_block) = range?;
+    let label = if labels.is_empty() { None } else { Some(labels.join("__")) };
+    Some(OutputFile {
+        path: path.to_path_buf(),
+        network: pieces[0].to_string(),
+        datatype: pieces[1].to_string(),
+        label,
+        start_block,
+        end_block,
+        format,
+    })
+}
+
+fn parse_block_range(label: &str) -> Option<(u64, u64)> {
+    let (start, end) = label.split_once("_to_")?;
+    let start_block = start.parse().ok()?;
+    let end_block = end.parse().ok()?;
+    if end_block < start_block {
+        return None
+    }
+    Some((start_block, end_block))
+}
+
+type GapsAndOverlaps = (Vec<(u64, u64)>, Vec<(PathBuf, PathBuf)>);
+
+/// find missing ranges and overlapping files, files must be sorted by block range
+fn find_gaps_and_overlaps(files: &[OutputFile]) -> GapsAndOverlaps {
+    let mut gaps = Vec::new();
+    let mut overlaps = Vec::new();
+    let mut covering: Option<&OutputFile> = None;
+    for file in files.iter() {
+        if let Some(previous) = covering {
+            if file.start_block > previous.end_block + 1 {
+                gaps.push((previous.end_block + 1, file.start_block - 1));
+            } else if file.start_block <= previous.end_block {
+                overlaps.push((previous.path.clone(), file.path.clone()));
+            }
+        }
+        covering = match covering {
+            Some(previous) if previous.end_block >= file.end_block => Some(previous),
+            _ => Some(file),
+        };
+    }
+    (gaps, overlaps)
+}
+
+/// list output files in directory that are named for their block range
+pub(crate) fn list_output_files(dir: &Path) -> Result<Vec<OutputFile>, CollectError> {
+    Ok(list_files(dir)?.iter().filter_map(|path| parse_output_path(path)).collect())
+}
+
+fn has_row_per_block(datatype: &str) -> bool {
+    datatype == "blocks"
+}
+
+fn count_parquet_rows(path: &Path) -> Option<usize> {
+    let file = std::fs::File::open(path).ok()?;
+    ParquetReader::new(file).num_rows().ok()
+}
+
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, CollectError> {
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(dir).map_err(|_| err("could not read directory"))?;
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read directory entry"))?.path();
+        if path.is_dir() {
+            // skip journal and reports
+            if path.file_name().and_then(|name| name.to_str()) != Some(".cryo") {
+                files.extend(list_files(&path)?);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_file(path: &str, start_block: u64, end_block: u64) -> OutputFile {
+        parse_output_path(Path::new(path))
+            .filter(|file| file.start_block == start_block && file.end_block == end_block)
+            .expect("could not parse path")
+    }
+
+    #[test]
+    fn test_parse_output_path() {
+        let file = output_file("/data/ethereum__logs__00001000_to_00001999.parquet", 1000, 1999);
+        assert_eq!((file.network.as_str(), file.datatype.as_str()), ("ethereum", "logs"));
+        assert_eq!(file.label, None);
+
+        let file = output_file("/data/base__blocks__v2__00000000_to_00000999.csv", 0, 999);
+        assert_eq!(file.label.as_deref(), Some("v2"));
+        assert_eq!(file.format, "csv");
+
+        let path = "/data/network=ethereum/datatype=logs/block_bucket=00010000/contract=0xab/part-00010000_to_00010999.parquet";
+        let file = output_file(path, 10000, 10999);
+        assert_eq!((file.network.as_str(), file.datatype.as_str()), ("ethereum", "logs"));
+        assert_eq!(file.label.as_deref(), Some("contract=0xab"));
+
+        assert!(parse_output_path(Path::new("/data/ethereum__logs__latest.parquet")).is_none());
+        assert!(parse_output_path(Path::new("/data/notes.txt")).is_none());
+        let sidecar = "/data/ethereum__logs__00010000_to_00010999.json.provenance.json";
+        assert!(parse_output_path(Path::new(sidecar)).is_none());
+    }
+
+    #[test]
+    fn test_find_gaps_and_overlaps() {
+        let files = vec![
+            output_file("/d/ethereum__logs__00000000_to_00000999.parquet", 0, 999),
+            output_file("/d/ethereum__logs__00001000_to_00001999.parquet", 1000, 1999),
+            output_file("/d/ethereum__logs__00003000_to_00003999.parquet", 3000, 3999),
+            output_file("/d/ethereum__logs__00003500_to_00004499.parquet", 3500, 4499),
+        ];
+        let (gaps, overlaps) = find_gaps_and_overlaps(&files);
+        assert_eq!(gaps, vec![(2000, 2999)]);
+        assert_eq!(overlaps.len(), 1);
+
+        let dataset = DatasetAudit { files, gaps, overlaps };
+        assert_eq!(dataset.fill_blocks_args(), vec!["2000:3000".to_string()]);
+    }
+}
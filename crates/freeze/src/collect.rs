@@ -1,30 +1,107 @@
-use crate::{collect_partition, CollectError, Query, Source};
+use crate::{
+    collect_partition, collect_partition_stream, dataframes::SortableDataFrame, CollectError,
+    Datatype, Query, Source,
+};
+use futures::Stream;
 use polars::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::{mpsc, Semaphore};
 
-/// collect single dataframe
-pub async fn collect(query: Arc<Query>, source: Arc<Source>) -> Result<DataFrame, CollectError> {
+/// collect a dataframe for each datatype, merging the results of all partitions
+///
+/// partitions are collected concurrently, up to the max_concurrent_chunks of the source
+pub async fn collect(
+    query: Arc<Query>,
+    source: Arc<Source>,
+) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
     query.is_valid()?;
-    let datatype = if query.datatypes.len() != 1 {
-        return Err(CollectError::CollectError(
-            "collect() can only collect a single datatype".to_string(),
-        ))
-    } else {
-        query.datatypes[0].clone()
-    };
-    let partition = if query.partitions.len() != 1 {
-        return Err(CollectError::CollectError(
-            "collect() can only collect a single datatype".to_string(),
-        ))
-    } else {
-        query.partitions[0].clone()
-    };
-    let results = collect_partition(datatype, partition, query, source).await?;
-    if results.len() > 1 {
-        Err(CollectError::CollectError("collect() only returns single dataframes".to_string()))
-    } else {
-        match results.into_iter().next() {
-            Some((_datatype, df)) => Ok(df),
-            None => Err(CollectError::CollectError("no dataframe result returned".to_string())),
+    let semaphore = source.max_concurrent_chunks.map(|x| Arc::new(Semaphore::new(x as usize)));
+
+    // spawn task for each partition
+    let mut handles = Vec::new();
+    for datatype in query.datatypes.iter() {
+        for partition in query.partitions.iter() {
+            let datatype = datatype.clone();
+            let partition = partition.clone();
+            let query = query.clone();
+            let source = source.clone();
+            let semaphore = semaphore.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = match &semaphore {
+                    Some(semaphore) => Some(semaphore.acquire().await),
+                    None => None,
+                };
+                collect_partition(datatype, partition, query, source).await
+            }));
+        }
+    }
+
+    // gather dataframes of each datatype, in partition order
+    let mut dfs: HashMap<Datatype, Vec<DataFrame>> = HashMap::new();
+    for result in futures::future::join_all(handles).await.into_iter() {
+        let result = result.map_err(CollectError::TaskFailed)??;
+        for (datatype, df) in result.into_iter() {
+            dfs.entry(datatype).or_default().push(df);
+        }
+    }
+
+    // concatenate and sort dataframes of each datatype
+    let mut output = HashMap::new();
+    for (datatype, datatype_dfs) in dfs.into_iter() {
+        let mut datatype_dfs = datatype_dfs.into_iter();
+        let mut df = match datatype_dfs.next() {
+            Some(df) => df,
+            None => continue,
+        };
+        for other in datatype_dfs {
+            df.vstack_mut(&other)?;
+        }
+        df.align_chunks();
+        let df = match query.schemas.get(&datatype) {
+            Some(schema) => Ok(df).sort_by_schema(schema)?,
+            None => df,
+        };
+        output.insert(datatype, df);
+    }
+    Ok(output)
+}
+
+/// collect data as a stream, yielding a dataframe as each request completes
+///
+/// partitions are collected one at a time in query order, the stream ends after the first error
+pub fn collect_stream(
+    query: Arc<Query>,
+    source: Arc<Source>,
+) -> impl Stream<Item = Result<(Datatype, DataFrame), CollectError>> {
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(async move {
+        if let Err(e) = stream_partitions(query, source, sender.clone()).await {
+            // receiver is gone if the stream was dropped, so nothing to report to
+            let _ = sender.send(Err(e)).await;
+        }
+    });
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+}
+
+async fn stream_partitions(
+    query: Arc<Query>,
+    source: Arc<Source>,
+    sender: mpsc::Sender<Result<(Datatype, DataFrame), CollectError>>,
+) -> Result<(), CollectError> {
+    query.is_valid()?;
+    for datatype in query.datatypes.iter() {
+        for partition in query.partitions.iter() {
+            collect_partition_stream(
+                datatype.clone(),
+                partition.clone(),
+                query.clone(),
+                source.clone(),
+                sender.clone(),
+            )
+            .await?
         }
     }
+    Ok(())
 }
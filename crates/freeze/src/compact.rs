@@ -0,0 +1,417 @@
+use crate::{
+    audit::{list_output_files, OutputFile},
+    dataframes::{self, SortableDataFrame},
+    delta, err, manifest,
+    manifest::MANIFEST_FILENAME,
+    provenance, BlockChunk, CollectError, Datatype, FileFormat, FileOutput, Partition, Table,
+};
+use polars::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// options for compacting chunk files
+#[derive(Clone, Debug)]
+pub struct CompactOptions {
+    /// max number of blocks in each compacted file
+    pub chunk_size: u64,
+    /// only merge files within the same aligned range of chunk_size blocks
+    pub align: bool,
+    /// only report which files would be merged
+    pub dry: bool,
+}
+
+/// summary of compacting chunk files
+#[derive(Debug, Default)]
+pub struct CompactSummary {
+    /// merged files, along with the files that they replaced
+    pub merged: Vec<(PathBuf, Vec<PathBuf>)>,
+    /// files removed because a merged file already covers them
+    pub superseded: Vec<PathBuf>,
+    /// files with partially overlapping block ranges, which are left untouched
+    pub overlapping: Vec<PathBuf>,
+}
+
+/// merge consecutive chunk files of each datatype into larger files
+///
+/// input files are only deleted after the merged file has been written and read back, and input
+/// files left over from an interrupted compaction are removed when compaction is re-run
+pub fn compact(
+    schemas: &HashMap<Datatype, Table>,
+    sink: &FileOutput,
+    options: &CompactOptions,
+) -> Result<CompactSummary, CollectError> {
+    if sink.format != FileFormat::Parquet {
+        return Err(err("can only compact parquet outputs"))
+    }
+    if options.chunk_size == 0 {
+        return Err(err("chunk size must be greater than 0"))
+    }
+
+    // group files by dataset and directory
+    let mut datasets: BTreeMap<_, Vec<OutputFile>> = BTreeMap::new();
+    for file in list_output_files(&sink.output_dir)?.into_iter() {
+        if file.format != "parquet" {
+            continue
+        }
+        if schemas.keys().any(|datatype| datatype.name() == file.datatype) {
+            let dir = file.path.parent().map(|dir| dir.to_path_buf());
+            let key = (file.network.clone(), file.datatype.clone(), file.label.clone(), dir);
+            datasets.entry(key).or_default().push(file);
+        }
+    }
+
+    let mut summary = CompactSummary::default();
+    for ((_, name, _, _), mut files) in datasets.into_iter() {
+        let datatype = match schemas.keys().find(|datatype| datatype.name() == name) {
+            Some(datatype) => *datatype,
+            None => continue,
+        };
+        files.sort_by_key(|file| (file.start_block, std::cmp::Reverse(file.end_block)));
+        let files = remove_superseded(files, &mut summary, options)?;
+        if has_partial_overlaps(&files) {
+            summary.overlapping.extend(files.into_iter().map(|file| file.path));
+            continue
+        }
+        for group in group_files(files, options).into_iter() {
+            if group.len() < 2 {
+                continue
+            }
+            let merged_path = merged_path(&group)?;
+            if !options.dry {
+                merge_files(&group, &merged_path, datatype, schemas.get(&datatype), sink)?;
+            }
+            summary.merged.push((merged_path, group.into_iter().map(|file| file.path).collect()));
+        }
+    }
+    Ok(summary)
+}
+
+/// remove files whose block range is contained in the range of another file
+fn remove_superseded(
+    files: Vec<OutputFile>,
+    summary: &mut CompactSummary,
+    options: &CompactOptions,
+) -> Result<Vec<OutputFile>, CollectError> {
+    let mut kept: Vec<OutputFile> = Vec::new();
+    for file in files.into_iter() {
+        match kept.last() {
+            Some(covering) if covering.end_block >= file.end_block => {
+                // only trust covering file if it can be read
+                if read_parquet(&covering.path).is_err() {
+                    kept.push(file);
+                    continue
+                }
+                if !options.dry {
+                    // remove file from delta table before deleting it
+                    if let Some(table_dir) = find_delta_table(&file.path) {
+                        delta::commit(&table_dir, &[], &[file.path.clone()])?;
+                    }
+                    std::fs::remove_file(&file.path)
+                        .map_err(|_| err("could not remove superseded file"))?;
+                    remove_from_manifest(&[file.path.clone()])?;
+                }
+                summary.superseded.push(file.path)
+            }
+            _ => kept.push(file),
+        }
+    }
+    Ok(kept)
+}
+
+fn has_partial_overlaps(files: &[OutputFile]) -> bool {
+    files.windows(2).any(|pair| pair[1].start_block <= pair[0].end_block)
+}
+
+/// group contiguous files so that each group spans at most chunk_size blocks
+fn group_files(files: Vec<OutputFile>, options: &CompactOptions) -> Vec<Vec<OutputFile>> {
+    let mut groups: Vec<Vec<OutputFile>> = Vec::new();
+    for file in files.into_iter() {
+        let fits = match groups.last().and_then(|group| group.first().zip(group.last())) {
+            Some((first, last)) => {
+                let contiguous = file.start_block == last.end_block + 1;
+                let span = file.end_block - first.start_block + 1;
+                let same_window = !options.align ||
+                    first.start_block / options.chunk_size ==
+                        file.end_block / options.chunk_size;
+                contiguous && span <= options.chunk_size && same_window
+            }
+            None => false,
+        };
+        match (fits, groups.last_mut()) {
+            (true, Some(group)) => group.push(file),
+            _ => groups.push(vec![file]),
+        }
+    }
+    groups
+}
+
+/// path of merged file, named like the first file of group but for the combined range
+fn merged_path(group: &[OutputFile]) -> Result<PathBuf, CollectError> {
+    let (first, last) = match (group.first(), group.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err(err("cannot merge empty group of files")),
+    };
+    let old_label = format!("{:0>8}_to_{:0>8}", first.start_block, first.end_block);
+    let new_label = format!("{:0>8}_to_{:0>8}", first.start_block, last.end_block);
+    let filename = first
+        .path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(err("could not get filename"))?
+        .replace(&old_label, &new_label);
+    Ok(first.path.with_file_name(filename))
+}
+
+/// metadata entries that describe a merged file rather than the collection of its inputs
+const MERGED_FILE_KEYS: [&str; 4] = ["cryo_version", "min_block", "max_block", "schema"];
+
+fn merge_files(
+    group: &[OutputFile],
+    merged_path: &Path,
+    datatype: Datatype,
+    schema: Option<&Table>,
+    sink: &FileOutput,
+) -> Result<(), CollectError> {
+    // read, concatenate, and sort inputs
+    let mut dfs = group.iter().map(|file| read_parquet(&file.path));
+    let mut df = dfs.next().ok_or(err("cannot merge empty group of files"))??;
+    for other in dfs {
+        df.vstack_mut(&other?)?;
+    }
+    df.align_chunks();
+    let mut df = match schema {
+        Some(schema) => Ok(df).sort_by_schema(schema)?,
+        None => df,
+    };
+
+    // write merged file and verify it before removing inputs
+    let (start_block, end_block) = match (group.first(), group.last()) {
+        (Some(first), Some(last)) => (first.start_block, last.end_block),
+        _ => return Err(err("cannot merge empty group of files")),
+    };
+    let partition = Partition {
+        block_numbers: Some(vec![BlockChunk::Range(start_block, end_block)]),
+        ..Default::default()
+    };
+    let inputs_provenance: Vec<BTreeMap<String, String>> = group
+        .iter()
+        .map(|file| provenance::read_provenance(&file.path).unwrap_or_default())
+        .collect();
+    // only entries describing the merged file itself are taken from the sink, whose static
+    // metadata would otherwise replace the provenance of the inputs
+    let mut metadata = common_provenance(&inputs_provenance);
+    metadata.insert("compacted_at".to_string(), chrono::Utc::now().to_rfc3339());
+    metadata.extend(
+        sink.file_metadata(&partition, &df)
+            .into_iter()
+            .filter(|(key, _)| MERGED_FILE_KEYS.contains(&key.as_str())),
+    );
+    dataframes::df_to_file(&mut df, merged_path, sink, &metadata)
+        .map_err(|_| err("could not write merged file"))?;
+    if read_parquet(merged_path)?.height() != df.height() {
+        return Err(err("merged file does not match its inputs"))
+    }
+    let inputs: Vec<PathBuf> = group.iter().map(|file| file.path.clone()).collect();
+    if let Some(manifest_path) = find_manifest(merged_path) {
+        manifest::record_file(&manifest_path, merged_path, datatype, &partition, &df)?;
+    }
+    // replace inputs by merged file in delta table before deleting inputs
+    if let Some(table_dir) = find_delta_table(merged_path) {
+        delta::commit(&table_dir, &[merged_path.to_path_buf()], &inputs)?;
+    }
+    for input in inputs.iter() {
+        std::fs::remove_file(input).map_err(|_| err("could not remove merged input file"))?;
+    }
+    remove_from_manifest(&inputs)
+}
+
+/// provenance entries that all input files agree on, so that merged files do not claim e.g. the
+/// command or collection time of only one of their inputs
+fn common_provenance(inputs: &[BTreeMap<String, String>]) -> BTreeMap<String, String> {
+    let Some((first, others)) = inputs.split_first() else { return BTreeMap::new() };
+    first
+        .iter()
+        .filter(|(key, value)| others.iter().all(|other| other.get(*key) == Some(*value)))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn read_parquet(path: &Path) -> Result<DataFrame, CollectError> {
+    let file = std::fs::File::open(path).map_err(|_| err("could not open file"))?;
+    Ok(ParquetReader::new(file).finish()?)
+}
+
+/// delta table of the dataset directory containing path, if one exists
+fn find_delta_table(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join(delta::DELTA_LOG_DIRNAME).is_dir())
+        .map(|dir| dir.to_path_buf())
+}
+
+/// manifest of the dataset directory containing path, if one exists
+fn find_manifest(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).map(|dir| dir.join(MANIFEST_FILENAME)).find(|path| path.exists())
+}
+
+fn remove_from_manifest(paths: &[PathBuf]) -> Result<(), CollectError> {
+    match paths.first().and_then(|path| find_manifest(path)) {
+        Some(manifest_path) => manifest::remove_files(&manifest_path, paths),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::parse_output_path;
+
+    fn output_file(dir: &Path, start_block: u64, end_block: u64) -> OutputFile {
+        let filename = format!("ethereum__logs__{:0>8}_to_{:0>8}.parquet", start_block, end_block);
+        parse_output_path(&dir.join(filename)).expect("could not parse path")
+    }
+
+    fn options(chunk_size: u64, align: bool) -> CompactOptions {
+        CompactOptions { chunk_size, align, dry: false }
+    }
+
+    fn ranges(groups: &[Vec<OutputFile>]) -> Vec<Vec<(u64, u64)>> {
+        groups
+            .iter()
+            .map(|group| group.iter().map(|file| (file.start_block, file.end_block)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_group_files() {
+        let dir = Path::new("/data");
+        let files = || {
+            vec![
+                output_file(dir, 0, 99),
+                output_file(dir, 100, 199),
+                output_file(dir, 200, 299),
+                output_file(dir, 400, 499),
+                output_file(dir, 500, 599),
+            ]
+        };
+
+        // groups stop at gaps and at chunk size
+        let groups = group_files(files(), &options(250, false));
+        assert_eq!(
+            ranges(&groups),
+            vec![vec![(0, 99), (100, 199)], vec![(200, 299)], vec![(400, 499), (500, 599)]]
+        );
+
+        // aligned groups also stop at multiples of chunk size
+        let groups = group_files(files(), &options(500, true));
+        assert_eq!(
+            ranges(&groups),
+            vec![vec![(0, 99), (100, 199), (200, 299)], vec![(400, 499)], vec![(500, 599)]]
+        );
+    }
+
+    #[test]
+    fn test_merged_path() {
+        let dir = Path::new("/data");
+        let group = vec![output_file(dir, 0, 99), output_file(dir, 100, 199)];
+        assert_eq!(
+            merged_path(&group).unwrap(),
+            dir.join("ethereum__logs__00000000_to_00000199.parquet")
+        );
+        assert!(merged_path(&[]).is_err());
+    }
+
+    #[test]
+    fn test_remove_superseded() {
+        let dir = std::env::temp_dir().join(format!("cryo_compact_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files =
+            vec![output_file(&dir, 0, 199), output_file(&dir, 0, 99), output_file(&dir, 100, 199)];
+        for file in files.iter() {
+            let mut df = df!("block_number" => [file.start_block, file.end_block]).unwrap();
+            let f = std::fs::File::create(&file.path).unwrap();
+            ParquetWriter::new(f).finish(&mut df).unwrap();
+        }
+
+        // dry runs only report superseded files
+        let mut summary = CompactSummary::default();
+        let dry = CompactOptions { dry: true, ..options(1000, false) };
+        let kept = remove_superseded(files.clone(), &mut summary, &dry).unwrap();
+        assert_eq!(ranges(&[kept]), vec![vec![(0, 199)]]);
+        assert_eq!(summary.superseded.len(), 2);
+        assert!(files.iter().all(|file| file.path.exists()));
+
+        let mut summary = CompactSummary::default();
+        remove_superseded(files.clone(), &mut summary, &options(1000, false)).unwrap();
+        assert!(files[0].path.exists());
+        assert!(!files[1].path.exists() && !files[2].path.exists());
+
+        // unreadable covering files are not trusted
+        std::fs::write(&files[0].path, b"not parquet").unwrap();
+        let mut summary = CompactSummary::default();
+        let kept = remove_superseded(files[..2].to_vec(), &mut summary, &dry).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(summary.superseded.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_files() {
+        let dir = std::env::temp_dir().join(format!("cryo_compact_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let group = vec![output_file(&dir, 0, 99), output_file(&dir, 100, 199)];
+        let input_sink = FileOutput {
+            metadata: [("network", "ethereum"), ("chain_id", "1")]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ..FileOutput::new_test(dir.clone(), FileFormat::Parquet)
+        };
+        for file in group.iter() {
+            let mut df = df!("block_number" => [file.start_block, file.end_block]).unwrap();
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(file.start_block, file.end_block)]),
+                ..Default::default()
+            };
+            let metadata = input_sink.file_metadata(&partition, &df);
+            dataframes::df_to_file(&mut df, &file.path, &input_sink, &metadata).unwrap();
+        }
+
+        // compacting without a network name must keep the network of the inputs
+        let sink = FileOutput {
+            metadata: [("network".to_string(), String::new())].into_iter().collect(),
+            ..FileOutput::new_test(dir.clone(), FileFormat::Parquet)
+        };
+        let merged_path = merged_path(&group).unwrap();
+        merge_files(&group, &merged_path, Datatype::Logs, None, &sink).unwrap();
+        let merged = provenance::read_provenance(&merged_path).unwrap();
+        assert_eq!(merged.get("network").map(|s| s.as_str()), Some("ethereum"));
+        assert_eq!(merged.get("chain_id").map(|s| s.as_str()), Some("1"));
+        assert_eq!(merged.get("min_block").map(|s| s.as_str()), Some("0"));
+        assert_eq!(merged.get("max_block").map(|s| s.as_str()), Some("199"));
+        assert!(merged.contains_key("compacted_at"));
+        assert!(group.iter().all(|file| !file.path.exists()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_common_provenance() {
+        let provenance = |cli_command: &str, collected_at: &str| -> BTreeMap<String, String> {
+            [("chain_id", "1"), ("cli_command", cli_command), ("collected_at", collected_at)]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let inputs =
+            vec![provenance("cryo logs", "2024-01-01"), provenance("cryo logs", "2024-02-01")];
+        let common = common_provenance(&inputs);
+        assert_eq!(common.get("chain_id").map(|s| s.as_str()), Some("1"));
+        assert_eq!(common.get("cli_command").map(|s| s.as_str()), Some("cryo logs"));
+        assert_eq!(common.get("collected_at"), None);
+        assert!(common_provenance(&[]).is_empty());
+    }
+}
@@ -32,6 +32,10 @@ impl Dataset for AddressAppearances {
     fn default_sort() -> Option<Vec<&'static str>> {
         Some(vec!["block_number", "transaction_hash", "address", "relationship"])
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceBlock]
+    }
 }
 
 type BlockLogsTraces = (Block<TxHash>, Vec<Log>, Vec<Trace>);
@@ -43,7 +47,7 @@ impl CollectByBlock for AddressAppearances {
     async fn extract(request: Params, source: Arc<Source>, _: Arc<Query>) -> R<Self::Response> {
         let block_number = request.ethers_block_number()?;
         let block = source.get_block(request.block_number()?).await?;
-        let block = block.ok_or(CollectError::CollectError("block not found".to_string()))?;
+        let block = block.ok_or(CollectError::NotFound("block not found".to_string()))?;
         let filter = Filter {
             block_option: FilterBlockOption::Range {
                 from_block: Some(block_number),
@@ -75,18 +79,18 @@ impl CollectByTransaction for AddressAppearances {
 
         let block_number = tx_data
             .block_number
-            .ok_or_else(|| CollectError::CollectError("block not found".to_string()))?
+            .ok_or_else(|| CollectError::NotFound("block not found".to_string()))?
             .as_u64();
         let block = source
             .get_block(block_number)
             .await?
-            .ok_or(CollectError::CollectError("could not get block".to_string()))?;
+            .ok_or(CollectError::NotFound("could not get block".to_string()))?;
 
         // logs
         let logs = source
             .get_transaction_receipt(tx_hash)
             .await?
-            .ok_or(CollectError::CollectError("could not get tx receipt".to_string()))?
+            .ok_or(CollectError::NotFound("could not get tx receipt".to_string()))?
             .logs;
 
         // traces
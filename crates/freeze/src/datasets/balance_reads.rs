@@ -17,7 +17,11 @@ pub struct BalanceReads {
 }
 
 #[async_trait::async_trait]
-impl Dataset for BalanceReads {}
+impl Dataset for BalanceReads {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 type BlockTxsTraces = (Option<u32>, Vec<Option<Vec<u8>>>, Vec<BTreeMap<H160, AccountState>>);
 
@@ -48,7 +48,7 @@ impl CollectByBlock for Blocks {
         let block = source
             .get_block(request.block_number()?)
             .await?
-            .ok_or(CollectError::CollectError("block not found".to_string()))?;
+            .ok_or(CollectError::NotFound("block not found".to_string()))?;
         Ok(block)
     }
 
@@ -66,11 +66,11 @@ impl CollectByTransaction for Blocks {
         let transaction = source
             .get_transaction(request.ethers_transaction_hash()?)
             .await?
-            .ok_or(CollectError::CollectError("transaction not found".to_string()))?;
+            .ok_or(CollectError::NotFound("transaction not found".to_string()))?;
         let block = source
             .get_block_by_hash(transaction.block_hash.ok_or(err("no block block_hash found"))?)
             .await?
-            .ok_or(CollectError::CollectError("block not found".to_string()))?;
+            .ok_or(CollectError::NotFound("block not found".to_string()))?;
         Ok(block)
     }
 
@@ -17,7 +17,11 @@ pub struct CodeDiffs {
 }
 
 #[async_trait::async_trait]
-impl Dataset for CodeDiffs {}
+impl Dataset for CodeDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceReplay, Capability::HistoricalState]
+    }
+}
 
 type BlockTxTraces = (Option<u32>, Vec<Option<Vec<u8>>>, Vec<ethers::types::BlockTrace>);
 
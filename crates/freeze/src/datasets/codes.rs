@@ -30,6 +30,10 @@ impl Dataset for Codes {
     fn default_blocks() -> Option<String> {
         Some("latest".to_string())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 type BlockTxAddressOutput = (u32, Option<Vec<u8>>, Vec<u8>, Vec<u8>);
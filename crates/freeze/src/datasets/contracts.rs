@@ -28,6 +28,10 @@ impl Dataset for Contracts {
     fn default_sort() -> Option<Vec<&'static str>> {
         Some(vec!["block_number", "create_index"])
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceBlock]
+    }
 }
 
 #[async_trait::async_trait]
@@ -23,6 +23,10 @@ impl Dataset for Erc20Balances {
     fn required_parameters() -> Vec<Dim> {
         vec![Dim::Contract, Dim::Address]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 #[async_trait::async_trait]
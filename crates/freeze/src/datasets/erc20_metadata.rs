@@ -31,6 +31,10 @@ impl Dataset for Erc20Metadata {
     fn arg_aliases() -> Option<std::collections::HashMap<Dim, Dim>> {
         Some([(Dim::Contract, Dim::Address)].into_iter().collect())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 pub(crate) fn remove_control_characters(s: &str) -> String {
@@ -30,6 +30,10 @@ impl Dataset for Erc20Supplies {
     fn default_blocks() -> Option<String> {
         Some("latest".to_string())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 #[async_trait::async_trait]
@@ -37,6 +37,10 @@ impl Dataset for EthCalls {
     fn required_parameters() -> Vec<Dim> {
         vec![Dim::Contract, Dim::CallData]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 type EthCallsResponse = (u32, Vec<u8>, Vec<u8>, Vec<u8>);
@@ -21,6 +21,10 @@ impl Dataset for FourByteCounts {
     fn aliases() -> Vec<&'static str> {
         vec!["4byte_counts"]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
 }
 
 type BlockTxsTraces = (Option<u32>, Vec<Option<Vec<u8>>>, Vec<BTreeMap<String, u64>>);
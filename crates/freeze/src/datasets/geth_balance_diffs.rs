@@ -17,7 +17,11 @@ pub struct GethBalanceDiffs {
 }
 
 #[async_trait::async_trait]
-impl Dataset for GethBalanceDiffs {}
+impl Dataset for GethBalanceDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethBalanceDiffs {
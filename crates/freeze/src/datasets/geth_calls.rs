@@ -24,7 +24,11 @@ pub struct GethCalls {
 }
 
 #[async_trait::async_trait]
-impl Dataset for GethCalls {}
+impl Dataset for GethCalls {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethCalls {
@@ -16,7 +16,11 @@ pub struct GethCodeDiffs {
 }
 
 #[async_trait::async_trait]
-impl Dataset for GethCodeDiffs {}
+impl Dataset for GethCodeDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethCodeDiffs {
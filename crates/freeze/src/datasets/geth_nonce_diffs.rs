@@ -17,7 +17,11 @@ pub struct GethNonceDiffs {
 }
 
 #[async_trait::async_trait]
-impl Dataset for GethNonceDiffs {}
+impl Dataset for GethNonceDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethNonceDiffs {
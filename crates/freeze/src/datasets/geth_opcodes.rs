@@ -32,6 +32,10 @@ impl Dataset for GethOpcodes {
         let f = |x: &&str| x != &"memory" && x != &"stack" && x != &"storage";
         Some(GethOpcodes::column_types().into_keys().filter(f).collect())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
 }
 
 #[async_trait::async_trait]
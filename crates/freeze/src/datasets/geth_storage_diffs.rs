@@ -16,7 +16,11 @@ pub struct GethStorageDiffs {
     pub(crate) chain_id: Vec<u64>,
 }
 
-impl Dataset for GethStorageDiffs {}
+impl Dataset for GethStorageDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
+}
 
 #[async_trait::async_trait]
 impl CollectByBlock for GethStorageDiffs {
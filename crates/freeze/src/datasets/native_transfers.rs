@@ -23,6 +23,10 @@ impl Dataset for NativeTransfers {
     fn optional_parameters() -> Vec<Dim> {
         vec![Dim::FromAddress, Dim::ToAddress]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceBlock]
+    }
 }
 
 #[async_trait::async_trait]
@@ -17,7 +17,11 @@ pub struct NonceDiffs {
 }
 
 #[async_trait::async_trait]
-impl Dataset for NonceDiffs {}
+impl Dataset for NonceDiffs {
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceReplay, Capability::HistoricalState]
+    }
+}
 
 type BlockTxsTraces = (Option<u32>, Vec<Option<Vec<u8>>>, Vec<ethers::types::BlockTrace>);
 
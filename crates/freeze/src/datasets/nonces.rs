@@ -26,6 +26,10 @@ impl Dataset for Nonces {
     fn default_blocks() -> Option<String> {
         Some("latest".to_string())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::HistoricalState]
+    }
 }
 
 type BlockTxAddressOutput = (u32, Option<Vec<u8>>, Vec<u8>, u64);
@@ -22,6 +22,10 @@ impl Dataset for StorageReads {
     fn aliases() -> Vec<&'static str> {
         vec!["slot_reads"]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::DebugTrace, Capability::HistoricalState]
+    }
 }
 
 type BlockTxsTraces = (Option<u32>, Vec<Option<Vec<u8>>>, Vec<BTreeMap<H160, AccountState>>);
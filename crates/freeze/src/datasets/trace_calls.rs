@@ -43,6 +43,10 @@ impl Dataset for TraceCalls {
     fn arg_aliases() -> Option<std::collections::HashMap<Dim, Dim>> {
         Some([(Dim::Address, Dim::Contract), (Dim::ToAddress, Dim::Contract)].into_iter().collect())
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceCall, Capability::HistoricalState]
+    }
 }
 
 #[async_trait::async_trait]
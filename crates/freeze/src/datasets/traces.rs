@@ -35,6 +35,10 @@ impl Dataset for Traces {
     fn optional_parameters() -> Vec<Dim> {
         vec![Dim::FromAddress, Dim::ToAddress]
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceBlock]
+    }
 }
 
 #[async_trait::async_trait]
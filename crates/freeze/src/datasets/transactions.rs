@@ -70,7 +70,7 @@ impl CollectByBlock for Transactions {
         let block = source
             .get_block_with_txs(request.block_number()?)
             .await?
-            .ok_or(CollectError::CollectError("block not found".to_string()))?;
+            .ok_or(CollectError::NotFound("block not found".to_string()))?;
         let schema = query.schemas.get_schema(&Datatype::Transactions)?;
 
         // 1. collect transactions and filter them if optional parameters are supplied
@@ -138,7 +138,7 @@ impl CollectByTransaction for Transactions {
         let transaction = source
             .get_transaction(tx_hash)
             .await?
-            .ok_or(CollectError::CollectError("transaction not found".to_string()))?;
+            .ok_or(CollectError::NotFound("transaction not found".to_string()))?;
         let receipt = if schema.has_column("gas_used") {
             source.get_transaction_receipt(tx_hash).await?
         } else {
@@ -152,7 +152,7 @@ impl CollectByTransaction for Transactions {
         let block = source
             .get_block(block_number.as_u64())
             .await?
-            .ok_or(CollectError::CollectError("block not found".to_string()))?;
+            .ok_or(CollectError::NotFound("block not found".to_string()))?;
 
         let timestamp = block.timestamp.as_u32();
 
@@ -35,6 +35,10 @@ impl Dataset for VmTraces {
     fn default_sort() -> Option<Vec<&'static str>> {
         Some(vec!["block_number", "transaction_index", "used"])
     }
+
+    fn required_capabilities() -> Vec<Capability> {
+        vec![Capability::TraceReplay, Capability::HistoricalState]
+    }
 }
 
 #[async_trait::async_trait]
@@ -0,0 +1,283 @@
+use crate::{
+    err, freeze, summaries, BlockChunk, CollectError, Dim, ExecutionEnv, Partition, Query, Sink,
+    Source,
+};
+use chrono::Local;
+use std::time::Duration;
+
+/// options for following the chain tip
+#[derive(Clone, Debug)]
+pub struct FollowOptions {
+    /// number of blocks in each new chunk
+    pub chunk_size: u64,
+    /// time to wait between polls of the latest block
+    pub poll_interval: Duration,
+}
+
+/// collect data and write it to sink, then keep collecting new chunks as the chain advances
+///
+/// a chunk is only collected once all of its blocks are `reorg_buffer` blocks deep, so a
+/// trailing partial chunk of the initial query is deferred until it can be collected in full.
+/// chunks that error are collected again in the next poll
+pub async fn follow<S: Sink + Clone + 'static>(
+    query: &Query,
+    source: &Source,
+    sink: &S,
+    env: &ExecutionEnv,
+    options: &FollowOptions,
+) -> Result<(), CollectError> {
+    if options.chunk_size == 0 {
+        return Err(err("chunk size must be greater than 0"))
+    }
+    let (initial, templates, mut next_block) = split_follow_partitions(query, options.chunk_size)?;
+
+    // freeze blocks that are already available
+    let mut errored = Vec::new();
+    if !initial.is_empty() {
+        let initial_query = Query { partitions: initial, ..query.clone() };
+        if let Some(summary) = freeze(&initial_query, source, sink, env).await? {
+            errored.extend(summary.errored.into_iter().filter_map(|(partition, _)| partition));
+        }
+    }
+    if env.dry {
+        return Ok(())
+    }
+
+    // poll for new blocks, collecting them together with the chunks that errored before
+    loop {
+        if !errored.is_empty() {
+            tokio::time::sleep(options.poll_interval).await;
+        }
+        let latest_block = match source.get_block_number().await {
+            Ok(latest_block) => latest_block.as_u64(),
+            Err(e) => {
+                if env.verbose >= 1 {
+                    print_status(&format!("could not get latest block number: {}", e));
+                }
+                tokio::time::sleep(options.poll_interval).await;
+                continue
+            }
+        };
+        let max_allowed = latest_block.saturating_sub(query.labels.reorg_buffer);
+
+        let chunks;
+        (chunks, next_block) = new_chunks(next_block, options.chunk_size, max_allowed);
+        if chunks.is_empty() && errored.is_empty() {
+            tokio::time::sleep(options.poll_interval).await;
+            continue
+        }
+
+        let partitions = poll_partitions(std::mem::take(&mut errored), &chunks, &templates, query);
+        let n_partitions = partitions.len() as u64;
+        let new_query = Query { partitions: partitions.clone(), ..query.clone() };
+        let new_env = follow_env(env, n_partitions)?;
+        if env.verbose >= 1 && !chunks.is_empty() {
+            print_status(&format!(
+                "following chain, collecting blocks {} to {}",
+                next_block - options.chunk_size * chunks.len() as u64,
+                next_block - 1,
+            ));
+        }
+        match freeze(&new_query, source, sink, &new_env).await {
+            Ok(Some(summary)) => {
+                if env.verbose >= 1 {
+                    print_status(&format!(
+                        "{} chunks completed, {} chunks errored, {} rows",
+                        summary.completed.len(),
+                        summary.errored.len(),
+                        summary.n_rows,
+                    ));
+                    if !summary.errored.is_empty() {
+                        summaries::print_error_summary(&summary.errored);
+                        println!();
+                    }
+                }
+                errored.extend(summary.errored.into_iter().filter_map(|(partition, _)| partition));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                if env.verbose >= 1 {
+                    print_status(&format!("could not collect chunks: {}", e));
+                }
+                errored = partitions;
+            }
+        }
+    }
+}
+
+/// print status of follow loop with current time
+fn print_status(message: &str) {
+    println!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+/// split partitions into those that can be collected immediately and templates for new chunks,
+/// also returning the first block that is not yet covered
+fn split_follow_partitions(
+    query: &Query,
+    chunk_size: u64,
+) -> Result<(Vec<Partition>, Vec<Partition>, u64), CollectError> {
+    if !query.partitioned_by.contains(&Dim::BlockNumber) {
+        return Err(err("following the chain requires partitioning by block number"))
+    }
+
+    let mut ranges = Vec::new();
+    for partition in query.partitions.iter() {
+        match partition.block_numbers.as_deref() {
+            Some([BlockChunk::Range(start, end)]) => ranges.push((*start, *end)),
+            _ => return Err(err("following the chain requires block ranges")),
+        }
+    }
+    let last_block = ranges
+        .iter()
+        .map(|(_, end)| *end)
+        .max()
+        .ok_or(err("following the chain requires at least one block range"))?;
+
+    let mut initial = Vec::new();
+    let mut templates = Vec::new();
+    let mut next_block = last_block + 1;
+    for (partition, (start, end)) in query.partitions.iter().zip(ranges) {
+        if end == last_block {
+            templates.push(partition.clone());
+            if end - start + 1 < chunk_size {
+                next_block = std::cmp::min(next_block, start);
+                continue
+            }
+        }
+        initial.push(partition.clone());
+    }
+    Ok((initial, templates, next_block))
+}
+
+/// complete chunks of `chunk_size` blocks from `next_block` up to `max_allowed`, also returning
+/// the first block that is not yet covered
+fn new_chunks(mut next_block: u64, chunk_size: u64, max_allowed: u64) -> (Vec<BlockChunk>, u64) {
+    let mut chunks = Vec::new();
+    while next_block + chunk_size - 1 <= max_allowed {
+        chunks.push(BlockChunk::Range(next_block, next_block + chunk_size - 1));
+        next_block += chunk_size;
+    }
+    (chunks, next_block)
+}
+
+/// partitions of a poll, i.e. the partitions that errored before followed by a partition of
+/// each template for each new chunk
+fn poll_partitions(
+    errored: Vec<Partition>,
+    chunks: &[BlockChunk],
+    templates: &[Partition],
+    query: &Query,
+) -> Vec<Partition> {
+    let new_partitions = chunks.iter().flat_map(|chunk| {
+        templates.iter().map(|template| with_block_chunk(template, chunk, query))
+    });
+    errored.into_iter().chain(new_partitions).collect()
+}
+
+/// replace block chunk of template partition, dropping any stored block label
+fn with_block_chunk(template: &Partition, chunk: &BlockChunk, query: &Query) -> Partition {
+    let label = template.label.as_ref().map(|labels| {
+        labels
+            .iter()
+            .zip(query.partitioned_by.iter())
+            .map(|(label, dim)| if dim == &Dim::BlockNumber { None } else { label.clone() })
+            .collect()
+    });
+    Partition { label, block_numbers: Some(vec![chunk.clone()]), ..template.clone() }
+}
+
+/// execution env for a round of new chunks, printing only a progress bar
+fn follow_env(env: &ExecutionEnv, n_partitions: u64) -> Result<ExecutionEnv, CollectError> {
+    let bar = match env.bar {
+        Some(_) => Some(crate::types::execution::new_bar(n_partitions)?),
+        None => None,
+    };
+    Ok(ExecutionEnv { verbose: 0, bar, t_end: None, ..env.clone() }.set_start_time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_partition(start: u64, end: u64) -> Partition {
+        Partition { block_numbers: Some(vec![BlockChunk::Range(start, end)]), ..Default::default() }
+    }
+
+    fn block_query(ranges: &[(u64, u64)], align: bool) -> Query {
+        Query {
+            datatypes: vec![],
+            schemas: std::collections::HashMap::new(),
+            time_dimension: crate::TimeDimension::Blocks,
+            partitions: ranges.iter().map(|(start, end)| block_partition(*start, *end)).collect(),
+            partitioned_by: vec![Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: crate::QueryLabels { align, reorg_buffer: 0 },
+        }
+    }
+
+    fn chunk_ranges(chunks: &[BlockChunk]) -> Vec<(u64, u64)> {
+        chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                BlockChunk::Range(start, end) => Some((*start, *end)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn ranges(partitions: &[Partition]) -> Vec<(u64, u64)> {
+        partitions
+            .iter()
+            .flat_map(|p| chunk_ranges(p.block_numbers.as_deref().unwrap_or(&[])))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_follow_partitions() {
+        // trailing partial chunk is deferred and followed from its first block
+        let query = block_query(&[(0, 99), (100, 149)], true);
+        let (initial, templates, next_block) = split_follow_partitions(&query, 100).unwrap();
+        assert_eq!(ranges(&initial), vec![(0, 99)]);
+        assert_eq!(ranges(&templates), vec![(100, 149)]);
+        assert_eq!(next_block, 100);
+
+        // complete chunks are collected immediately, following starts after them
+        let query = block_query(&[(5, 104), (105, 204)], false);
+        let (initial, _, next_block) = split_follow_partitions(&query, 100).unwrap();
+        assert_eq!(ranges(&initial), vec![(5, 104), (105, 204)]);
+        assert_eq!(next_block, 205);
+
+        let query = Query { partitioned_by: vec![Dim::Address], ..block_query(&[(0, 9)], false) };
+        assert!(split_follow_partitions(&query, 10).is_err());
+    }
+
+    #[test]
+    fn test_new_chunks() {
+        // chunks keep the alignment of the first uncovered block
+        let (chunks, next_block) = new_chunks(100, 100, 349);
+        assert_eq!(chunk_ranges(&chunks), vec![(100, 199), (200, 299)]);
+        assert_eq!(next_block, 300);
+
+        // chunks are only collected once all of their blocks are available
+        let (chunks, next_block) = new_chunks(300, 100, 398);
+        assert!(chunks.is_empty());
+        assert_eq!(next_block, 300);
+    }
+
+    #[test]
+    fn test_poll_partitions() {
+        let query = block_query(&[(0, 99)], false);
+        let template = Partition {
+            label: Some(vec![Some("00000000_to_00000099".to_string())]),
+            ..block_partition(0, 99)
+        };
+        let errored = vec![block_partition(100, 199)];
+        let chunks = [BlockChunk::Range(200, 299)];
+
+        // errored partitions are collected again before new chunks
+        let partitions = poll_partitions(errored, &chunks, &[template], &query);
+        assert_eq!(ranges(&partitions), vec![(100, 199), (200, 299)]);
+        assert_eq!(partitions[1].label, Some(vec![None]));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    collect_partition, dataframes, err, reports, summaries, CollectError, Datatype, ExecutionEnv,
-    FileOutput, FreezeSummary, MetaDatatype, Partition, Query, Source,
+    collect_partition, delta, err, reports, sink::outputs_exist, split::SplitIndex, summaries,
+    CollectError, Datatype, ExecutionEnv, FreezeSummary, Journal, MetaDatatype, Partition,
+    PartitionStatus, Query, Sink, Source,
 };
 use chrono::{DateTime, Local};
 use futures::{stream::FuturesUnordered, StreamExt};
@@ -11,33 +12,70 @@ use std::{
 };
 use tokio::sync::Semaphore;
 
-type PartitionPayload = (
+type PartitionPayload<S> = (
     Partition,
     MetaDatatype,
     HashMap<Datatype, PathBuf>,
     Arc<Query>,
     Arc<Source>,
-    FileOutput,
+    Arc<S>,
     ExecutionEnv,
     Option<std::sync::Arc<Semaphore>>,
+    Option<Arc<Journal>>,
 );
 
-/// collect data and output as files
-pub async fn freeze(
+/// collect data and write it to sink
+pub async fn freeze<S: Sink + Clone + 'static>(
     query: &Query,
     source: &Source,
-    sink: &FileOutput,
+    sink: &S,
     env: &ExecutionEnv,
 ) -> Result<Option<FreezeSummary>, CollectError> {
     // check validity of query
     query.is_valid()?;
+    let file_output = sink.file_output();
+
+    // check that node supports the methods of the datatypes before any work is done, the node
+    // is only probed once per source and not at all for dry runs
+    let required_capabilities = query.required_capabilities();
+    let capabilities = match required_capabilities.is_empty() || env.dry {
+        true => None,
+        false => Some(source.get_capabilities(query, &required_capabilities).await?),
+    };
+    if let Some(capabilities) = &capabilities {
+        capabilities.check(query)?;
+    }
+
+    // load checkpoint journal
+    let journal = match (env.dry, file_output) {
+        (false, Some(file_output)) => {
+            let journal = Journal::open(Journal::get_journal_path(file_output))?;
+            if env.resume {
+                let removed = journal.clean_orphaned_tmp_files()?;
+                if env.verbose >= 1 && !removed.is_empty() {
+                    println!("removed {} orphaned _tmp files", removed.len());
+                }
+            };
+            Some(Arc::new(journal))
+        }
+        _ => None,
+    };
 
     // get partitions
-    let (payloads, skipping) = get_payloads(query, source, sink, env)?;
+    let (payloads, skipping) = get_payloads(query, source, sink, env, journal).await?;
 
     // print summary
     if env.verbose >= 1 {
-        summaries::print_cryo_intro(query, source, sink, env, payloads.len() as u64)?;
+        let n_chunks_remaining = payloads.len() as u64;
+        let capabilities = capabilities.as_ref();
+        summaries::print_cryo_intro(
+            query,
+            source,
+            capabilities,
+            file_output,
+            env,
+            n_chunks_remaining,
+        )?;
     }
 
     // check dry run
@@ -55,45 +93,99 @@ pub async fn freeze(
     }
 
     // create initial report
-    if env.report {
-        reports::write_report(env, query, sink, None)?;
+    if let (true, Some(file_output)) = (env.report, file_output) {
+        reports::write_report(env, query, file_output, None)?;
     };
 
+    // record pending partitions
+    if let Some((_, _, _, _, _, _, _, _, Some(journal))) = payloads.first() {
+        let paths = payloads.iter().flat_map(|payload| payload.2.values());
+        journal.record(paths, &PartitionStatus::Pending)?;
+    }
+
     // perform collection
     let results = freeze_partitions(env, payloads, skipping).await;
+    sink.finalize().await?;
 
     // create summary
     if env.verbose >= 1 {
         summaries::print_cryo_conclusion(&results, query, env)
     }
 
+    // commit files to delta tables once every partition has completed
+    if let (true, Some(file_output)) = (results.errored.is_empty(), file_output) {
+        if file_output.delta {
+            let partitions: Vec<&Partition> =
+                results.completed.iter().chain(results.skipped.iter()).collect();
+            delta::commit_partitions(query, file_output, &partitions)?;
+        }
+    }
+
     // create final report
-    if env.report {
-        reports::write_report(env, query, sink, Some(&results))?;
+    if let (true, Some(file_output)) = (env.report, file_output) {
+        reports::write_report(env, query, file_output, Some(&results))?;
     };
 
     // return
     Ok(Some(results))
 }
 
-fn get_payloads(
+async fn get_payloads<S: Sink + Clone + 'static>(
     query: &Query,
     source: &Source,
-    sink: &FileOutput,
+    sink: &S,
     env: &ExecutionEnv,
-) -> Result<(Vec<PartitionPayload>, Vec<Partition>), CollectError> {
+    journal: Option<Arc<Journal>>,
+) -> Result<(Vec<PartitionPayload<S>>, Vec<Partition>), CollectError> {
     let semaphore = source
         .max_concurrent_chunks
         .map(|x| std::sync::Arc::new(tokio::sync::Semaphore::new(x as usize)));
     let source: Arc<Source> = Arc::new(source.clone());
     let arc_query = Arc::new(query.clone());
+    let arc_sink = Arc::new(sink.clone());
     let mut payloads = Vec::new();
     let mut skipping = Vec::new();
     let mut all_paths = HashSet::new();
+    let mut manifests = HashMap::new();
+    let mut splits = SplitIndex::default();
     for datatype in query.datatypes.clone().into_iter() {
         for partition in query.partitions.clone().into_iter() {
-            let paths = sink.get_paths(query, &partition, Some(vec![datatype.clone()]))?;
-            if !sink.overwrite && paths.values().all(|path| path.exists()) {
+            let file_output = match sink.file_output() {
+                Some(file_output) => file_output,
+                None => {
+                    if partition_exists(query, &partition, &datatype, sink).await? {
+                        skipping.push(partition);
+                    } else {
+                        let payload = (
+                            partition.clone(),
+                            datatype.clone(),
+                            HashMap::new(),
+                            arc_query.clone(),
+                            source.clone(),
+                            arc_sink.clone(),
+                            env.clone(),
+                            semaphore.clone(),
+                            None,
+                        );
+                        payloads.push(payload);
+                    }
+                    continue
+                }
+            };
+
+            let paths = file_output.get_paths(query, &partition, Some(vec![datatype.clone()]))?;
+            // outputs recorded as completed are only skipped if they still exist
+            if let (true, Some(journal)) = (env.resume, &journal) {
+                if journal.previously_completed(paths.values()) &&
+                    outputs_exist(&paths, file_output, &mut manifests, &mut splits)?
+                {
+                    skipping.push(partition);
+                    continue
+                }
+            }
+            if !file_output.overwrite &&
+                outputs_exist(&paths, file_output, &mut manifests, &mut splits)?
+            {
                 skipping.push(partition);
                 continue
             }
@@ -114,9 +206,10 @@ fn get_payloads(
                 paths,
                 arc_query.clone(),
                 source.clone(),
-                sink.clone(),
+                arc_sink.clone(),
                 env.clone(),
                 semaphore.clone(),
+                journal.clone(),
             );
             payloads.push(payload);
         }
@@ -124,77 +217,167 @@ fn get_payloads(
     Ok((payloads, skipping))
 }
 
-async fn freeze_partitions(
+/// whether sink already has output of every datatype of partition
+async fn partition_exists<S: Sink>(
+    query: &Query,
+    partition: &Partition,
+    datatype: &MetaDatatype,
+    sink: &S,
+) -> Result<bool, CollectError> {
+    for datatype in datatype.datatypes().into_iter() {
+        if !sink.exists(query, partition, datatype).await? {
+            return Ok(false)
+        }
+    }
+    Ok(true)
+}
+
+async fn freeze_partitions<S: Sink + 'static>(
     env: &ExecutionEnv,
-    payloads: Vec<PartitionPayload>,
+    payloads: Vec<PartitionPayload<S>>,
     skipped: Vec<Partition>,
 ) -> FreezeSummary {
+    let mut limits_task = None;
     if let Some(bar) = &env.bar {
         bar.set_length(payloads.len() as u64);
         if let Some(payload) = &payloads.first() {
-            let (_, _, _, _, _, _, env, _) = payload;
+            let (_, _, _, _, source, _, env, _, _) = payload;
             let dt_start: DateTime<Local> = env.t_start.into();
-            bar.set_message(format!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f")));
+            let started = format!("started at {}", dt_start.format("%Y-%m-%d %H:%M:%S%.3f"));
+            bar.set_message(started.clone());
+
+            // show current limits of adaptive limiter
+            if let Some(limiter) = source.adaptive_limiter.clone() {
+                let bar = bar.clone();
+                limits_task = Some(tokio::spawn(async move {
+                    loop {
+                        let (concurrency, rate) = limiter.effective_limits();
+                        let rate = match rate {
+                            Some(rate) => format!("{:.1} requests/s", rate),
+                            None => "unlimited requests/s".to_string(),
+                        };
+                        let limits = format!("{} concurrent requests, {}", concurrency, rate);
+                        bar.set_message(format!("{}, limits: {}", started, limits));
+                        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                    }
+                }));
+            }
         }
     }
 
-    // spawn task for each partition
+    // spawn task for each partition, keeping partition to report tasks that fail to join
     let mut futures = FuturesUnordered::new();
     for payload in payloads.into_iter() {
-        futures.push(tokio::spawn(
-            async move { (payload.0.clone(), freeze_partition(payload).await) },
-        ));
+        let partition = payload.0.clone();
+        let handle = tokio::spawn(async move { freeze_partition(payload).await });
+        futures.push(async move { (partition, handle.await) });
     }
 
     // aggregate results
     let mut completed = Vec::new();
     let mut errored = Vec::new();
+    let mut retried = Vec::new();
     let mut n_rows = 0;
-    while let Some(result) = futures.next().await {
+    while let Some((partition, result)) = futures.next().await {
         match result {
-            Ok((partition, Ok(chunk_n_rows))) => {
-                n_rows += chunk_n_rows;
-                completed.push(partition)
+            Ok((result, attempts)) => {
+                if attempts > 1 {
+                    retried.push((partition.clone(), attempts))
+                }
+                match result {
+                    Ok(chunk_n_rows) => {
+                        n_rows += chunk_n_rows;
+                        completed.push(partition)
+                    }
+                    Err(e) => errored.push((Some(partition), e)),
+                }
             }
-            Ok((partition, Err(e))) => errored.push((Some(partition), e)),
-            Err(_e) => errored.push((None, err("error joining chunks"))),
+            Err(_e) => errored.push((Some(partition), err("error joining chunks"))),
         }
     }
 
+    if let Some(task) = limits_task {
+        task.abort()
+    }
     if let Some(bar) = &env.bar {
         bar.finish_and_clear();
     }
 
-    FreezeSummary { completed, errored, skipped, n_rows }
+    FreezeSummary { completed, errored, skipped, retried, n_rows }
 }
 
-async fn freeze_partition(payload: PartitionPayload) -> Result<u64, CollectError> {
-    let (partition, datatype, paths, query, source, sink, env, semaphore) = payload;
+/// freeze partition, retrying according to retry policy, returning result and number of attempts
+async fn freeze_partition<S: Sink>(
+    payload: PartitionPayload<S>,
+) -> (Result<u64, CollectError>, u32) {
+    let (partition, datatype, paths, query, source, sink, env, semaphore, journal) = payload;
 
-    // acquire chunk semaphore
-    let _permit = match &semaphore {
-        Some(semaphore) => Some(semaphore.acquire().await),
-        None => None,
-    };
+    let mut attempt = 1;
+    loop {
+        let result = {
+            // acquire chunk semaphore, released between attempts so other chunks can proceed
+            let _permit = match &semaphore {
+                Some(semaphore) => Some(semaphore.acquire().await),
+                None => None,
+            };
 
-    // collect data
-    let dfs = collect_partition(datatype, partition, query, source).await?;
+            // collect and write data, recording progress in journal
+            let recorded = match &journal {
+                Some(journal) => journal.record(paths.values(), &PartitionStatus::InFlight),
+                None => Ok(()),
+            };
+            match recorded {
+                Ok(()) => {
+                    collect_and_write(
+                        partition.clone(),
+                        datatype.clone(),
+                        query.clone(),
+                        source.clone(),
+                        sink.as_ref(),
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            }
+        };
 
-    // write dataframes to disk
-    let mut n_rows = 0;
-    for (datatype, mut df) in dfs {
-        n_rows += df.height() as u64;
-        let path = paths.get(&datatype).ok_or_else(|| {
-            CollectError::CollectError("could not get path for datatype".to_string())
-        })?;
-        let result = dataframes::df_to_file(&mut df, path, &sink);
-        result.map_err(|_| CollectError::CollectError("error writing file".to_string()))?
-    }
+        // re-queue partition if error is retryable
+        if let Err(e) = &result {
+            if env.retry.should_retry(e, attempt) {
+                tokio::time::sleep(env.retry.backoff(attempt)).await;
+                attempt += 1;
+                continue
+            }
+        }
 
-    // update progress bar
-    if let Some(bar) = env.bar {
-        bar.inc(1);
+        // record final status in journal
+        let result = match (&journal, result) {
+            (Some(journal), Ok(n_rows)) => {
+                journal.record(paths.values(), &PartitionStatus::Completed).map(|_| n_rows)
+            }
+            (Some(journal), Err(e)) => {
+                let status = PartitionStatus::Errored(e.to_string());
+                journal.record(paths.values(), &status).and(Err(e))
+            }
+            (None, result) => result,
+        };
+
+        // update progress bar
+        if let (Some(bar), Ok(_)) = (&env.bar, &result) {
+            bar.inc(1);
+        }
+
+        return (result, attempt)
     }
+}
 
-    Ok(n_rows)
+pub(crate) async fn collect_and_write<S: Sink>(
+    partition: Partition,
+    datatype: MetaDatatype,
+    query: Arc<Query>,
+    source: Arc<Source>,
+    sink: &S,
+) -> Result<u64, CollectError> {
+    let dfs = collect_partition(datatype, partition.clone(), query.clone(), source).await?;
+    sink.write_partition(&query, &partition, dfs).await
 }
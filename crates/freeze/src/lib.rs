@@ -7,14 +7,22 @@
     attr(deny(warnings, rust_2018_idioms), allow(dead_code, unused_variables))
 ))]
 
+mod audit;
 mod collect;
+mod compact;
 mod datasets;
+mod follow;
 mod freeze;
 mod multi_datasets;
 mod types;
+mod verify;
 
-pub use collect::collect;
+pub use audit::{audit, parse_output_path, AuditReport, DatasetAudit, DatasetKey, OutputFile};
+pub use collect::{collect, collect_stream};
+pub use compact::{compact, CompactOptions, CompactSummary};
 pub use datasets::*;
+pub use follow::{follow, FollowOptions};
 pub use freeze::freeze;
 pub use multi_datasets::*;
 pub use types::*;
+pub use verify::{verify_canonical, VerifySummary};
@@ -52,7 +52,7 @@ impl CollectByTransaction for BlocksAndTransactions {
         let block = source
             .get_block(block_number)
             .await?
-            .ok_or(CollectError::CollectError("block not found".to_string()))?;
+            .ok_or(CollectError::NotFound("block not found".to_string()))?;
         Ok((block, ((tx, receipt), exclude_failed, timestamp)))
     }
 
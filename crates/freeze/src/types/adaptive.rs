@@ -0,0 +1,318 @@
+use ethers::prelude::*;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// factor by which limits are multiplied when the node signals overload
+const DECREASE_FACTOR: f64 = 0.5;
+
+/// minimum time between decreases, so that the errors of one overload count once
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// latency relative to the lowest observed latency at which the node counts as overloaded
+const LATENCY_THRESHOLD: f64 = 3.0;
+
+/// weight of each new sample in the moving average of latency
+const LATENCY_WEIGHT: f64 = 0.1;
+
+/// number of latency samples before latency is used as a signal
+const MIN_LATENCY_SAMPLES: u64 = 20;
+
+/// fraction of the rate ceiling by which rate increases per second of successful requests
+const RATE_INCREASE: f64 = 0.02;
+
+/// concurrency and rate limits that adapt to the rpc node, AIMD-style
+///
+/// limits are halved when the node signals overload, i.e. on HTTP 429s and rate limit errors, or
+/// when latency rises well above its lowest level (which only lowers concurrency). limits then
+/// rise additively with each successful request, by about one concurrent request per round trip
+/// and a small fraction of the rate ceiling per second, up to the configured maximums. without a
+/// configured rate, a rate limit is only introduced once the node throttles requests, and is
+/// lifted again once it exceeds twice the throughput observed before
+#[derive(Debug)]
+pub struct AdaptiveLimiter {
+    max_concurrent_requests: u64,
+    max_requests_per_second: Option<f64>,
+    state: Mutex<AdaptiveState>,
+    notify: Notify,
+}
+
+#[derive(Debug)]
+struct AdaptiveState {
+    concurrency: f64,
+    in_flight: u64,
+    rate: Option<f64>,
+    next_request: Instant,
+    latency: Option<f64>,
+    min_latency: Option<f64>,
+    n_samples: u64,
+    last_decrease: Option<Instant>,
+    window_start: Instant,
+    window_requests: u64,
+    peak_throughput: f64,
+}
+
+/// permit of adaptive limiter, records latency of request when dropped
+#[derive(Debug)]
+pub struct AdaptivePermit<'a> {
+    limiter: &'a AdaptiveLimiter,
+    start: Instant,
+}
+
+impl AdaptiveLimiter {
+    /// create limiter that starts at the maximum limits
+    pub fn new(
+        max_concurrent_requests: u64,
+        max_requests_per_second: Option<u64>,
+    ) -> AdaptiveLimiter {
+        let max_concurrent_requests = max_concurrent_requests.max(1);
+        let max_requests_per_second = max_requests_per_second.map(|rate| rate.max(1) as f64);
+        let now = Instant::now();
+        let state = AdaptiveState {
+            concurrency: max_concurrent_requests as f64,
+            in_flight: 0,
+            rate: max_requests_per_second,
+            next_request: now,
+            latency: None,
+            min_latency: None,
+            n_samples: 0,
+            last_decrease: None,
+            window_start: now,
+            window_requests: 0,
+            peak_throughput: 0.0,
+        };
+        AdaptiveLimiter {
+            max_concurrent_requests,
+            max_requests_per_second,
+            state: Mutex::new(state),
+            notify: Notify::new(),
+        }
+    }
+
+    /// wait until request fits within current concurrency and rate limits
+    pub async fn acquire(&self) -> AdaptivePermit<'_> {
+        loop {
+            let notified = self.notify.notified();
+            if let Some(wait) = self.try_acquire() {
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+                break
+            }
+            notified.await;
+        }
+        AdaptivePermit { limiter: self, start: Instant::now() }
+    }
+
+    /// reserve concurrency slot and return wait until rate allows request, if a slot is free
+    fn try_acquire(&self) -> Option<Duration> {
+        let Ok(mut state) = self.state.lock() else { return Some(Duration::ZERO) };
+        if state.in_flight as f64 >= state.concurrency.floor() {
+            return None
+        }
+        state.in_flight += 1;
+        let Some(rate) = state.rate else { return Some(Duration::ZERO) };
+        let now = Instant::now();
+        let slot = state.next_request.max(now);
+        state.next_request = slot + Duration::from_secs_f64(1.0 / rate);
+        Some(slot - now)
+    }
+
+    /// record completed request
+    fn release(&self, latency: Duration) {
+        if let Ok(mut state) = self.state.lock() {
+            state.in_flight = state.in_flight.saturating_sub(1);
+            self.record(&mut state, latency, Instant::now());
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// lower limits because node throttled a request
+    pub fn throttle(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            self.decrease(&mut state, Instant::now(), true);
+        }
+    }
+
+    /// current concurrency limit and rate limit, `None` if rate is unlimited
+    pub fn effective_limits(&self) -> (u64, Option<f64>) {
+        match self.state.lock() {
+            Ok(state) => (state.concurrency.floor() as u64, state.rate),
+            Err(_) => (self.max_concurrent_requests, self.max_requests_per_second),
+        }
+    }
+
+    /// update latency and throughput with completed request, then adjust limits
+    fn record(&self, state: &mut AdaptiveState, latency: Duration, now: Instant) {
+        let latency = latency.as_secs_f64();
+        let average = match state.latency {
+            Some(average) => average + LATENCY_WEIGHT * (latency - average),
+            None => latency,
+        };
+        state.latency = Some(average);
+        state.n_samples += 1;
+
+        state.window_requests += 1;
+        let elapsed = now.saturating_duration_since(state.window_start).as_secs_f64();
+        if elapsed >= 1.0 {
+            let throughput = state.window_requests as f64 / elapsed;
+            state.peak_throughput = state.peak_throughput.max(throughput);
+            state.window_start = now;
+            state.window_requests = 0;
+        }
+
+        if state.n_samples < MIN_LATENCY_SAMPLES {
+            return
+        }
+        let min_latency = state.min_latency.map_or(average, |min_latency| min_latency.min(average));
+        state.min_latency = Some(min_latency);
+        if average > LATENCY_THRESHOLD * min_latency {
+            self.decrease(state, now, false)
+        } else {
+            self.increase(state)
+        }
+    }
+
+    /// multiply limits by decrease factor, the rate only if node throttled requests
+    fn decrease(&self, state: &mut AdaptiveState, now: Instant, throttled: bool) {
+        if state.last_decrease.map_or(false, |last| now < last + DECREASE_COOLDOWN) {
+            return
+        }
+        state.last_decrease = Some(now);
+        state.concurrency = (state.concurrency * DECREASE_FACTOR).max(1.0);
+        if throttled {
+            let rate = match state.rate {
+                Some(rate) => rate,
+                None if state.peak_throughput > 0.0 => state.peak_throughput,
+                None => state.concurrency / state.latency.unwrap_or(1.0).max(0.001),
+            };
+            state.rate = Some((rate * DECREASE_FACTOR).max(1.0));
+        }
+    }
+
+    /// raise concurrency by one per round trip and rate by a fraction of its ceiling per second
+    fn increase(&self, state: &mut AdaptiveState) {
+        let max_concurrency = self.max_concurrent_requests as f64;
+        state.concurrency = (state.concurrency + 1.0 / state.concurrency).min(max_concurrency);
+        if let Some(rate) = state.rate {
+            let ceiling = match self.max_requests_per_second {
+                Some(max_rate) => max_rate,
+                None => 2.0 * state.peak_throughput.max(1.0),
+            };
+            let rate = rate + (RATE_INCREASE * ceiling).max(1.0) / rate;
+            state.rate = match (rate >= ceiling, self.max_requests_per_second) {
+                (true, None) => None,
+                (true, Some(max_rate)) => Some(max_rate),
+                (false, _) => Some(rate),
+            };
+        }
+    }
+}
+
+impl Drop for AdaptivePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.release(self.start.elapsed())
+    }
+}
+
+/// retry policy of http providers that reports rate limit errors to an adaptive limiter
+#[derive(Debug)]
+pub struct AdaptiveRetryPolicy {
+    limiter: Arc<AdaptiveLimiter>,
+}
+
+impl AdaptiveRetryPolicy {
+    /// create retry policy for limiter
+    pub fn new(limiter: Arc<AdaptiveLimiter>) -> AdaptiveRetryPolicy {
+        AdaptiveRetryPolicy { limiter }
+    }
+}
+
+impl RetryPolicy<HttpClientError> for AdaptiveRetryPolicy {
+    fn should_retry(&self, error: &HttpClientError) -> bool {
+        if is_rate_limited(error) {
+            self.limiter.throttle()
+        }
+        HttpRateLimitRetryPolicy.should_retry(error)
+    }
+
+    fn backoff_hint(&self, error: &HttpClientError) -> Option<Duration> {
+        HttpRateLimitRetryPolicy.backoff_hint(error)
+    }
+}
+
+/// whether error means that the node throttled the request, which providers report with HTTP
+/// 429s or with different json-rpc codes and messages
+fn is_rate_limited(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::ReqwestError(e) => {
+            e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+        }
+        HttpClientError::JsonRpcError(error) => is_rate_limit_response(error),
+        HttpClientError::SerdeJson { text, .. } => is_rate_limit_message(text),
+    }
+}
+
+/// whether json-rpc error response means that the node throttled the request
+pub(crate) fn is_rate_limit_response(error: &JsonRpcError) -> bool {
+    error.code == 429 || error.code == -32005 || is_rate_limit_message(&error.message)
+}
+
+fn is_rate_limit_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("rate limit") ||
+        message.contains("too many requests") ||
+        (message.contains("exceeded") && message.contains("limit"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aimd() {
+        let limiter = AdaptiveLimiter::new(8, Some(100));
+        let mut state = limiter.state.lock().unwrap();
+        let now = Instant::now();
+
+        // throttling halves both limits, once per cooldown
+        limiter.decrease(&mut state, now, true);
+        assert_eq!((state.concurrency, state.rate), (4.0, Some(50.0)));
+        limiter.decrease(&mut state, now + Duration::from_millis(10), true);
+        assert_eq!((state.concurrency, state.rate), (4.0, Some(50.0)));
+
+        // rising latency only halves concurrency
+        limiter.decrease(&mut state, now + DECREASE_COOLDOWN, false);
+        assert_eq!((state.concurrency, state.rate), (2.0, Some(50.0)));
+
+        // limits rise additively up to the maximums
+        limiter.increase(&mut state);
+        assert_eq!(state.concurrency, 2.5);
+        assert_eq!(state.rate, Some(50.0 + 2.0 / 50.0));
+        for _ in 0..10_000 {
+            limiter.increase(&mut state);
+        }
+        assert_eq!((state.concurrency, state.rate), (8.0, Some(100.0)));
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        let error = |code, message: &str| {
+            HttpClientError::JsonRpcError(JsonRpcError {
+                code,
+                message: message.to_string(),
+                data: None,
+            })
+        };
+        assert!(is_rate_limited(&error(429, "Too Many Requests")));
+        assert!(is_rate_limited(&error(-32005, "request limit reached")));
+        assert!(is_rate_limited(&error(
+            -32000,
+            "daily request count exceeded, request rate limited"
+        )));
+        assert!(!is_rate_limited(&error(-32000, "header not found")));
+        assert!(!is_rate_limited(&error(-32601, "method not found")));
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{
+    adaptive::is_rate_limit_response, err, AdaptiveLimiter, AdaptivePermit, CollectError,
+    RateLimiter,
+};
+use ethers::prelude::*;
+use governor::Quota;
+use reqwest::{StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// methods whose concurrent requests are coalesced into batches, i.e. cheap state reads that
+/// datasets issue once per block and address
+pub const BATCHED_METHODS: [&str; 5] =
+    ["eth_getBalance", "eth_getTransactionCount", "eth_getCode", "eth_getStorageAt", "eth_call"];
+
+/// time that a batch waits for further requests after its first request
+const BATCH_WINDOW: Duration = Duration::from_millis(2);
+
+/// outcome of a request within a batch, `None` if the batch itself failed
+type BatchResult = Result<Value, Option<JsonRpcError>>;
+
+/// http transport that coalesces concurrent requests of the same method into json-rpc batches
+///
+/// requests of `BATCHED_METHODS` wait briefly for other requests of the same method, and are then
+/// sent as one batch of at most `max_batch_size` requests. other methods are sent individually.
+/// if a batch fails as a whole, e.g. because the node does not accept batches, its requests are
+/// sent individually, so that errors are the same as without batching, unless the node throttled
+/// the batch, in which case each request fails with the rate limit error. the rate limit and the
+/// adaptive limits of the transport count http requests, so that a batch counts once and retries
+/// do not hold a permit during their backoff, while concurrency limits of `Source` still count
+/// each request of a batch
+#[derive(Debug, Clone)]
+pub struct BatchHttp {
+    http: Arc<Http>,
+    client: reqwest::Client,
+    url: Url,
+    max_batch_size: usize,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    adaptive_limiter: Option<Arc<AdaptiveLimiter>>,
+    queues: Arc<Mutex<HashMap<String, Vec<PendingRequest>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    id: u64,
+    params: Value,
+    sender: oneshot::Sender<BatchResult>,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    id: u64,
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+/// error response to a batch as a whole
+#[derive(Deserialize)]
+struct BatchErrorResponse {
+    error: JsonRpcError,
+}
+
+impl BatchHttp {
+    /// create transport for http url, a `max_batch_size` of 1 disables batching
+    pub fn new(
+        url: &str,
+        max_batch_size: usize,
+        max_requests_per_second: Option<u32>,
+    ) -> Result<BatchHttp, CollectError> {
+        let url = Url::parse(url).map_err(|_| err(&format!("invalid rpc url: {}", url)))?;
+        let client = reqwest::Client::new();
+        let http = Http::new_with_client(url.clone(), client.clone());
+        let rate_limiter = max_requests_per_second.and_then(NonZeroU32::new).map(|value| {
+            let quota = Quota::per_second(value).allow_burst(NonZeroU32::MIN);
+            Arc::new(RateLimiter::direct(quota))
+        });
+        Ok(BatchHttp {
+            http: Arc::new(http),
+            client,
+            url,
+            max_batch_size: max_batch_size.max(1),
+            rate_limiter,
+            adaptive_limiter: None,
+            queues: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+        })
+    }
+
+    /// add request to queue of its method, flushing the queue once it is full or after the
+    /// batch window of its first request
+    fn enqueue(&self, method: &str, params: Value) -> oneshot::Receiver<BatchResult> {
+        let (sender, receiver) = oneshot::channel();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = PendingRequest { id, params, sender };
+        let (is_first, full_batch) = match self.queues.lock() {
+            Ok(mut queues) => {
+                let queue = queues.entry(method.to_string()).or_default();
+                queue.push(request);
+                let is_first = queue.len() == 1;
+                let full_batch = match queue.len() >= self.max_batch_size {
+                    true => Some(std::mem::take(queue)),
+                    false => None,
+                };
+                (is_first, full_batch)
+            }
+            // dropping the request makes the caller send it individually
+            Err(_) => return receiver,
+        };
+
+        if let Some(batch) = full_batch {
+            let transport = self.clone();
+            let method = method.to_string();
+            tokio::spawn(async move { transport.send_batch(&method, batch).await });
+        } else if is_first {
+            let transport = self.clone();
+            let method = method.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(BATCH_WINDOW).await;
+                let batch = match transport.queues.lock() {
+                    Ok(mut queues) => queues.remove(&method).unwrap_or_default(),
+                    Err(_) => return,
+                };
+                if !batch.is_empty() {
+                    transport.send_batch(&method, batch).await
+                }
+            });
+        }
+        receiver
+    }
+
+    /// limit http requests of transport with adaptive limiter
+    pub fn with_adaptive_limiter(mut self, limiter: Arc<AdaptiveLimiter>) -> BatchHttp {
+        self.adaptive_limiter = Some(limiter);
+        self
+    }
+
+    /// wait until limits allow another http request, the permit is held until its response
+    async fn permit_request(&self) -> Option<AdaptivePermit<'_>> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.until_ready().await;
+        }
+        match &self.adaptive_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        }
+    }
+
+    /// send batch and distribute its responses to the waiting requests
+    async fn send_batch(&self, method: &str, batch: Vec<PendingRequest>) {
+        let payload: Vec<Value> = batch
+            .iter()
+            .map(|request| {
+                let params = &request.params;
+                json!({"jsonrpc": "2.0", "id": request.id, "method": method, "params": params})
+            })
+            .collect();
+        let permit = self.permit_request().await;
+        let response = match self.client.post(self.url.clone()).json(&payload).send().await {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                let error = JsonRpcError {
+                    code: 429,
+                    message: "too many requests".to_string(),
+                    data: None,
+                };
+                return reject_batch(batch, error)
+            }
+            Ok(response) => response.bytes().await.ok(),
+            Err(_) => None,
+        };
+        drop(permit);
+        if let Some(BatchErrorResponse { error }) =
+            response.as_ref().and_then(|body| serde_json::from_slice(body).ok())
+        {
+            if is_rate_limit_response(&error) {
+                return reject_batch(batch, error)
+            }
+        }
+        let mut responses: HashMap<u64, BatchResponse> = response
+            .and_then(|body| serde_json::from_slice::<Vec<BatchResponse>>(&body).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|response| (response.id, response))
+            .collect();
+        for request in batch.into_iter() {
+            let result = match responses.remove(&request.id) {
+                Some(BatchResponse { error: Some(error), .. }) => Err(Some(error)),
+                Some(BatchResponse { result, .. }) => Ok(result.unwrap_or(Value::Null)),
+                None => Err(None),
+            };
+            let _ = request.sender.send(result);
+        }
+    }
+}
+
+/// fail every request of batch with error, instead of sending them individually
+fn reject_batch(batch: Vec<PendingRequest>, error: JsonRpcError) {
+    for request in batch.into_iter() {
+        let _ = request.sender.send(Err(Some(error.clone())));
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonRpcClient for BatchHttp {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if self.max_batch_size <= 1 || !BATCHED_METHODS.contains(&method) {
+            let _permit = self.permit_request().await;
+            return JsonRpcClient::request(self.http.as_ref(), method, params).await
+        }
+
+        let value = serde_json::to_value(&params)
+            .map_err(|err| HttpClientError::SerdeJson { err, text: format!("{:?}", params) })?;
+        match self.enqueue(method, value).await {
+            Ok(Ok(result)) => serde_json::from_value(result.clone())
+                .map_err(|err| HttpClientError::SerdeJson { err, text: result.to_string() }),
+            Ok(Err(Some(error))) => Err(HttpClientError::JsonRpcError(error)),
+            _ => {
+                let _permit = self.permit_request().await;
+                JsonRpcClient::request(self.http.as_ref(), method, params).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// serve batches of `eth_getBalance` requests, answering each with its id or with HTTP 429s
+    /// if `throttled`, and record the number of http requests
+    async fn serve_batches(
+        listener: TcpListener,
+        n_http_requests: Arc<AtomicU64>,
+        throttled: bool,
+    ) {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else { return };
+            n_http_requests.fetch_add(1, Ordering::SeqCst);
+            let mut buffer = Vec::new();
+            let body = loop {
+                let mut chunk = [0; 4096];
+                let n_bytes = stream.read(&mut chunk).await.unwrap();
+                buffer.extend_from_slice(&chunk[..n_bytes]);
+                let text = String::from_utf8_lossy(&buffer).to_string();
+                if let Some((header, body)) = text.split_once("\r\n\r\n") {
+                    let length = header
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|n| n.parse::<usize>().unwrap())
+                        })
+                        .unwrap();
+                    if body.len() >= length {
+                        break body.to_string()
+                    }
+                }
+            };
+            if throttled {
+                let response = "HTTP/1.1 429 Too Many Requests\r\ncontent-length: 0\r\n\r\n";
+                stream.write_all(response.as_bytes()).await.unwrap();
+                continue
+            }
+            let requests: Vec<Value> = serde_json::from_str(&body).unwrap();
+            let responses: Vec<Value> = requests
+                .iter()
+                .map(|request| {
+                    let result = format!("{:#x}", request["id"].as_u64().unwrap());
+                    json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+                })
+                .collect();
+            let body = serde_json::to_string(&responses).unwrap();
+            let header = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close";
+            let response = format!("{}\r\ncontent-length: {}\r\n\r\n{}", header, body.len(), body);
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let n_http_requests = Arc::new(AtomicU64::new(0));
+        tokio::spawn(serve_batches(listener, n_http_requests.clone(), false));
+
+        let transport = BatchHttp::new(&url, 10, None).unwrap();
+        let mut tasks = Vec::new();
+        for _ in 0..25 {
+            let transport = transport.clone();
+            tasks.push(tokio::spawn(async move {
+                let params = (H160::zero(), "latest");
+                let balance: U256 =
+                    JsonRpcClient::request(&transport, "eth_getBalance", params).await.unwrap();
+                balance
+            }));
+        }
+        let mut balances = Vec::new();
+        for task in tasks {
+            balances.push(task.await.unwrap().as_u64());
+        }
+        balances.sort();
+        assert_eq!(balances, (1..=25).collect::<Vec<u64>>());
+        assert_eq!(n_http_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_throttled_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let n_http_requests = Arc::new(AtomicU64::new(0));
+        tokio::spawn(serve_batches(listener, n_http_requests.clone(), true));
+
+        // a throttled batch is neither re-sent as individual requests nor rate limited per request
+        let transport = BatchHttp::new(&url, 10, Some(1)).unwrap();
+        let start = std::time::Instant::now();
+        let mut tasks = Vec::new();
+        for _ in 0..10 {
+            let transport = transport.clone();
+            tasks.push(tokio::spawn(async move {
+                let params = (H160::zero(), "latest");
+                JsonRpcClient::request::<_, U256>(&transport, "eth_getBalance", params).await
+            }));
+        }
+        for task in tasks {
+            match task.await.unwrap() {
+                Err(HttpClientError::JsonRpcError(error)) => assert_eq!(error.code, 429),
+                result => panic!("expected rate limit error, got {:?}", result),
+            }
+        }
+        assert_eq!(n_http_requests.load(Ordering::SeqCst), 1);
+        assert!(start.elapsed() < Duration::from_millis(900));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::{err, CollectError, Source};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// minimum time between refreshes of the finalized block
+const FINALIZED_REFRESH: Duration = Duration::from_secs(1);
+
+/// fraction of max size that eviction shrinks the cache to, so that eviction runs in batches
+const EVICTION_TARGET: f64 = 0.9;
+
+/// number of blocks behind the chain tip that count as final without a reorg buffer, if the node
+/// does not know the finalized tag
+const MIN_REORG_DEPTH: u64 = 64;
+
+/// on-disk cache of rpc responses, content-addressed by chain id, method, and params
+///
+/// only responses for blocks that are at least `reorg_buffer` blocks behind the chain tip are
+/// cached, since responses for younger blocks can change with reorgs. without a reorg buffer,
+/// only responses for blocks up to the node's finalized block are cached, or for blocks at least
+/// `MIN_REORG_DEPTH` blocks behind the tip if the node does not know the finalized tag. cache
+/// files are read and written off the async runtime. once the cache exceeds
+/// `max_size` bytes, the least recently used responses are evicted. cache errors never fail a
+/// request, the request is sent to the node instead
+#[derive(Debug)]
+pub struct RequestCache {
+    /// directory of cache
+    pub dir: PathBuf,
+    /// max total size of cached responses, in bytes
+    pub max_size: u64,
+    /// number of blocks behind the chain tip whose responses are not cached
+    pub reorg_buffer: u64,
+    index: Mutex<CacheIndex>,
+    finalized: tokio::sync::Mutex<Option<(u64, Instant)>>,
+    n_writes: AtomicU64,
+}
+
+/// size and last use of each cached response
+#[derive(Debug, Default)]
+struct CacheIndex {
+    entries: HashMap<PathBuf, (u64, SystemTime)>,
+    total_size: u64,
+}
+
+impl RequestCache {
+    /// open cache in directory, indexing the responses cached by previous runs
+    pub fn open(
+        dir: &Path,
+        max_size: u64,
+        reorg_buffer: u64,
+    ) -> Result<RequestCache, CollectError> {
+        fs::create_dir_all(dir).map_err(|_| err("could not create cache dir"))?;
+        let mut index = CacheIndex::default();
+        let subdirs = fs::read_dir(dir).map_err(|_| err("could not read cache dir"))?;
+        for subdir in subdirs.flatten().filter(|entry| entry.path().is_dir()) {
+            let Ok(files) = fs::read_dir(subdir.path()) else { continue };
+            for file in files.flatten() {
+                let path = file.path();
+                let Ok(metadata) = file.metadata() else { continue };
+                if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                    // unfinished write of an interrupted run
+                    let _ = fs::remove_file(&path);
+                    continue
+                }
+                let last_used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                index.total_size += metadata.len();
+                index.entries.insert(path, (metadata.len(), last_used));
+            }
+        }
+        let cache = RequestCache {
+            dir: dir.to_path_buf(),
+            max_size,
+            reorg_buffer,
+            index: Mutex::new(index),
+            finalized: tokio::sync::Mutex::new(None),
+            n_writes: AtomicU64::new(0),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    /// total size of cached responses, in bytes
+    pub fn size(&self) -> u64 {
+        self.index.lock().map(|index| index.total_size).unwrap_or_default()
+    }
+
+    /// path of cached response of request
+    fn get_path<P: Serialize>(&self, chain_id: u64, method: &str, params: &P) -> Option<PathBuf> {
+        let request = serde_json::to_vec(&(chain_id, method, params)).ok()?;
+        let mut hasher = Sha256::new();
+        hasher.update(&request);
+        let key = format!("{:x}", hasher.finalize());
+        Some(self.dir.join(&key[..2]).join(key + ".json"))
+    }
+
+    /// read cached response, marking it as recently used
+    fn get<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        if !self.index.lock().ok()?.entries.contains_key(path) {
+            return None
+        }
+        let value = fs::read(path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        let Some(value) = value else {
+            // drop responses that were removed or can no longer be parsed
+            self.remove(path);
+            return None
+        };
+        let now = SystemTime::now();
+        if let Ok(file) = File::options().write(true).open(path) {
+            let _ = file.set_modified(now);
+        }
+        if let Ok(mut index) = self.index.lock() {
+            if let Some(entry) = index.entries.get_mut(path) {
+                entry.1 = now;
+            }
+        }
+        Some(value)
+    }
+
+    /// write response to cache, evicting least recently used responses if cache is full
+    #[cfg(test)]
+    fn insert<T: Serialize>(&self, path: &Path, value: &T) {
+        if let Ok(bytes) = serde_json::to_vec(value) {
+            self.write(path, bytes)
+        }
+    }
+
+    /// write serialized response to cache, evicting least recently used responses if cache is
+    /// full
+    fn write(&self, path: &Path, bytes: Vec<u8>) {
+        let size = bytes.len() as u64;
+        if size > self.max_size {
+            return
+        }
+
+        // write to temporary file first so that readers never see partial responses
+        let n_write = self.n_writes.fetch_add(1, Ordering::SeqCst);
+        let tmp_path = path.with_extension(format!("{}_{}.tmp", std::process::id(), n_write));
+        let written = path.parent().map_or(Ok(()), fs::create_dir_all).is_ok() &&
+            fs::write(&tmp_path, bytes).is_ok() &&
+            fs::rename(&tmp_path, path).is_ok();
+        if !written {
+            let _ = fs::remove_file(&tmp_path);
+            return
+        }
+
+        if let Ok(mut index) = self.index.lock() {
+            if let Some((old_size, _)) =
+                index.entries.insert(path.to_path_buf(), (size, SystemTime::now()))
+            {
+                index.total_size -= old_size;
+            }
+            index.total_size += size;
+        }
+        self.evict()
+    }
+
+    /// remove response from cache
+    fn remove(&self, path: &Path) {
+        let _ = fs::remove_file(path);
+        if let Ok(mut index) = self.index.lock() {
+            if let Some((size, _)) = index.entries.remove(path) {
+                index.total_size -= size;
+            }
+        }
+    }
+
+    /// evict least recently used responses until cache is below its eviction target
+    fn evict(&self) {
+        let Ok(mut index) = self.index.lock() else { return };
+        if index.total_size <= self.max_size {
+            return
+        }
+        let target = (self.max_size as f64 * EVICTION_TARGET) as u64;
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = index
+            .entries
+            .iter()
+            .map(|(path, (size, last_used))| (path.clone(), *size, *last_used))
+            .collect();
+        entries.sort_by_key(|(_, _, last_used)| *last_used);
+        for (path, size, _) in entries.into_iter() {
+            if index.total_size <= target {
+                break
+            }
+            let _ = fs::remove_file(&path);
+            index.entries.remove(&path);
+            index.total_size -= size;
+        }
+    }
+}
+
+impl Source {
+    /// send request through request cache, if source has one and `last_block` of request is
+    /// beyond the reorg buffer, otherwise send it to the node directly
+    pub(crate) async fn cached<T, P, F>(
+        &self,
+        method: &str,
+        params: &P,
+        last_block: Option<u64>,
+        request: F,
+    ) -> Result<T, CollectError>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        P: Serialize,
+        F: Future<Output = Result<T, CollectError>>,
+    {
+        let (Some(cache), Some(last_block)) = (&self.request_cache, last_block) else {
+            return request.await
+        };
+        // errors while checking finality only mean that the response is not cached
+        if !self.is_finalized(cache, last_block).await.unwrap_or(false) {
+            return request.await
+        }
+        let Some(path) = cache.get_path(self.chain_id, method, params) else {
+            return request.await
+        };
+        let cached = {
+            let (cache, path) = (cache.clone(), path.clone());
+            tokio::task::spawn_blocking(move || cache.get::<T>(&path)).await.ok().flatten()
+        };
+        if let Some(value) = cached {
+            return Ok(value)
+        }
+        let value = request.await?;
+        if let Ok(bytes) = serde_json::to_vec(&value) {
+            let cache = cache.clone();
+            let _ = tokio::task::spawn_blocking(move || cache.write(&path, bytes)).await;
+        }
+        Ok(value)
+    }
+
+    /// whether block is at least `reorg_buffer` blocks behind the chain tip, or finalized if there
+    /// is no reorg buffer, concurrent checks share one refresh of the chain tip
+    async fn is_finalized(&self, cache: &RequestCache, block: u64) -> Result<bool, CollectError> {
+        let mut finalized = cache.finalized.lock().await;
+        if let Some((finalized_block, refreshed_at)) = *finalized {
+            if block <= finalized_block {
+                return Ok(true)
+            } else if refreshed_at.elapsed() < FINALIZED_REFRESH {
+                return Ok(false)
+            }
+        }
+        let finalized_block = match cache.reorg_buffer {
+            0 => match self.get_finalized_block_number().await {
+                Ok(Some(finalized_block)) => finalized_block,
+                _ => self.get_block_number().await?.as_u64().saturating_sub(MIN_REORG_DEPTH),
+            },
+            reorg_buffer => self.get_block_number().await?.as_u64().saturating_sub(reorg_buffer),
+        };
+        *finalized = Some((finalized_block, Instant::now()));
+        Ok(block <= finalized_block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_request_cache() {
+        let dir = std::env::temp_dir().join(format!("cryo_cache_{}", std::process::id()));
+        let cache = RequestCache::open(&dir, 1000, 0).unwrap();
+
+        // responses are keyed by chain id, method, and params
+        let path = cache.get_path(1, "trace_block", &("0x1",)).unwrap();
+        assert_ne!(Some(&path), cache.get_path(10, "trace_block", &("0x1",)).as_ref());
+        assert_eq!(cache.get::<Vec<u64>>(&path), None);
+        cache.insert(&path, &vec![1u64, 2, 3]);
+        assert_eq!(cache.get::<Vec<u64>>(&path), Some(vec![1, 2, 3]));
+
+        // responses of previous runs are reused
+        let cache = RequestCache::open(&dir, 1000, 0).unwrap();
+        assert_eq!(cache.get::<Vec<u64>>(&path), Some(vec![1, 2, 3]));
+
+        // least recently used responses are evicted once cache is full
+        let value = vec![0u8; 200];
+        let paths: Vec<PathBuf> =
+            (0..5).map(|i| cache.get_path(1, "eth_getLogs", &(i,)).unwrap()).collect();
+        for other_path in paths.iter() {
+            cache.insert(other_path, &value);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(cache.size() <= 1000);
+        assert_eq!(cache.get::<Vec<u8>>(&paths[0]), None);
+        assert_eq!(cache.get::<Vec<u8>>(&paths[4]), Some(value));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn mocked_source(cache: RequestCache) -> (Source, MockProvider) {
+        let (provider, mock) = Provider::mocked();
+        let source = Source {
+            provider: provider.into(),
+            chain_id: 1,
+            inner_request_size: 1,
+            max_concurrent_chunks: None,
+            rpc_url: "".to_string(),
+            semaphore: Arc::new(None),
+            rate_limiter: Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: Some(Arc::new(cache)),
+            capabilities: Default::default(),
+            labels: crate::SourceLabels::default(),
+        };
+        (source, mock)
+    }
+
+    #[tokio::test]
+    async fn test_reorg_buffer() {
+        let dir = std::env::temp_dir().join(format!("cryo_cache_reorg_{}", std::process::id()));
+        let (source, mock) = mocked_source(RequestCache::open(&dir, 1000, 10).unwrap());
+        mock.push(U64::from(100)).unwrap();
+
+        // blocks within the reorg buffer of the chain tip are never cached
+        let response = source.cached("trace_block", &(95,), Some(95), async { Ok(vec![95]) });
+        assert_eq!(response.await.unwrap(), vec![95]);
+        let response = source.cached("trace_block", &(95,), Some(95), async { Ok(vec![0]) });
+        assert_eq!(response.await.unwrap(), vec![0]);
+
+        // blocks beyond the reorg buffer are served from cache
+        let response = source.cached("trace_block", &(90,), Some(90), async { Ok(vec![90]) });
+        assert_eq!(response.await.unwrap(), vec![90]);
+        let response = source.cached("trace_block", &(90,), Some(90), async { Ok(vec![0]) });
+        assert_eq!(response.await.unwrap(), vec![90]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_finalized_block() {
+        let dir = std::env::temp_dir().join(format!("cryo_cache_final_{}", std::process::id()));
+        let (source, mock) = mocked_source(RequestCache::open(&dir, 1000, 0).unwrap());
+        let finalized = Block::<TxHash> { number: Some(90.into()), ..Default::default() };
+        mock.push(finalized).unwrap();
+
+        // without reorg buffer, only blocks up to the finalized block are cached
+        let response = source.cached("trace_block", &(91,), Some(91), async { Ok(vec![91]) });
+        assert_eq!(response.await.unwrap(), vec![91]);
+        let response = source.cached("trace_block", &(91,), Some(91), async { Ok(vec![0]) });
+        assert_eq!(response.await.unwrap(), vec![0]);
+        let response = source.cached("trace_block", &(90,), Some(90), async { Ok(vec![90]) });
+        assert_eq!(response.await.unwrap(), vec![90]);
+        let response = source.cached("trace_block", &(90,), Some(90), async { Ok(vec![0]) });
+        assert_eq!(response.await.unwrap(), vec![90]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chain_tip_error() {
+        let dir = std::env::temp_dir().join(format!("cryo_cache_error_{}", std::process::id()));
+        let (source, _mock) = mocked_source(RequestCache::open(&dir, 1000, 10).unwrap());
+
+        // requests are sent to the node if the chain tip cannot be fetched
+        let response = source.cached("trace_block", &(1,), Some(1), async { Ok(vec![1]) });
+        assert_eq!(response.await.unwrap(), vec![1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
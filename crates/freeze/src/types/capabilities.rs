@@ -0,0 +1,318 @@
+use crate::{err, CollectError, Datatype, Query, Source};
+use ethers::prelude::*;
+use std::collections::BTreeMap;
+
+/// feature of rpc node that datasets depend on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Capability {
+    /// `eth_getBlockReceipts`, without it receipts are fetched per transaction
+    BlockReceipts,
+    /// `trace_block` and `trace_transaction`
+    TraceBlock,
+    /// `trace_replayBlockTransactions` and `trace_replayTransaction`
+    TraceReplay,
+    /// `trace_call`
+    TraceCall,
+    /// `debug_traceBlockByNumber` and `debug_traceTransaction`
+    DebugTrace,
+    /// state at historical blocks, as served by archive nodes
+    HistoricalState,
+}
+
+impl Capability {
+    /// name of capability, i.e. the rpc method that is probed
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::BlockReceipts => "eth_getBlockReceipts",
+            Capability::TraceBlock => "trace_block",
+            Capability::TraceReplay => "trace_replayBlockTransactions",
+            Capability::TraceCall => "trace_call",
+            Capability::DebugTrace => "debug_traceBlockByNumber",
+            Capability::HistoricalState => "historical state",
+        }
+    }
+}
+
+/// support of capability by rpc node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Support {
+    /// node supports capability
+    Supported,
+    /// node does not support capability, with the error message of the node
+    Unsupported(String),
+    /// probe failed for other reasons, e.g. a timeout
+    Unknown(String),
+}
+
+/// capabilities of rpc node, probed at a block
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// block at which capabilities were probed
+    pub block_number: u64,
+    /// support of each probed capability
+    pub support: BTreeMap<Capability, Support>,
+}
+
+impl Query {
+    /// capabilities that the datatypes of query depend on
+    ///
+    /// `eth_getBlockReceipts` is included for transactions although they fall back to
+    /// `eth_getTransactionReceipt`, so that its support is reported
+    pub fn required_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = Vec::new();
+        for datatype in self.datatypes.iter().flat_map(|dt| dt.datatypes()) {
+            capabilities.extend(datatype.required_capabilities());
+            if datatype == Datatype::Transactions {
+                capabilities.push(Capability::BlockReceipts);
+            }
+        }
+        capabilities.sort();
+        capabilities.dedup();
+        capabilities
+    }
+
+    /// first block of query, if query is partitioned by block
+    fn first_block(&self) -> Option<u64> {
+        self.partitions
+            .iter()
+            .filter_map(|partition| partition.stats().block_numbers?.min_value)
+            .min()
+    }
+}
+
+impl Source {
+    /// capabilities of node, only capabilities that were not probed by earlier queries of source
+    /// are probed
+    pub async fn get_capabilities(
+        &self,
+        query: &Query,
+        capabilities: &[Capability],
+    ) -> Result<Capabilities, CollectError> {
+        let mut probed = self.capabilities.lock().await;
+        let unprobed: Vec<Capability> = capabilities
+            .iter()
+            .filter(|capability| {
+                probed.as_ref().map_or(true, |probed| !probed.support.contains_key(capability))
+            })
+            .copied()
+            .collect();
+        if !unprobed.is_empty() {
+            let new = self.probe_capabilities(query, &unprobed).await?;
+            match probed.as_mut() {
+                Some(probed) => probed.support.extend(new.support),
+                None => *probed = Some(new),
+            }
+        }
+        let probed = probed.as_ref().ok_or_else(|| err("no capabilities were probed"))?;
+        let support = capabilities
+            .iter()
+            .filter_map(|capability| Some((*capability, probed.support.get(capability)?.clone())))
+            .collect();
+        Ok(Capabilities { block_number: probed.block_number, support })
+    }
+
+    /// probe which capabilities the node supports at the first block of query, or at the latest
+    /// block if query has no blocks or its first block is not yet mined
+    pub async fn probe_capabilities(
+        &self,
+        query: &Query,
+        capabilities: &[Capability],
+    ) -> Result<Capabilities, CollectError> {
+        let latest = self.get_block_number().await?.as_u64();
+        let block_number = query.first_block().map_or(latest, |first| first.min(latest));
+        let block = BlockNumber::from(block_number);
+        let mut support = BTreeMap::new();
+        for capability in capabilities.iter() {
+            let capability_support = match capability {
+                Capability::BlockReceipts => {
+                    get_support(self.get_block_receipts(block_number).await, capability)
+                }
+                Capability::TraceBlock => get_support(self.trace_block(block).await, capability),
+                Capability::TraceReplay => {
+                    let trace_types = vec![TraceType::Trace];
+                    let result = self.trace_replay_block_transactions(block, trace_types).await;
+                    get_support(result, capability)
+                }
+                Capability::TraceCall => {
+                    let transaction = TransactionRequest::new().to(H160::zero());
+                    let trace_types = vec![TraceType::Trace];
+                    let result = self.trace_call(transaction, trace_types, Some(block)).await;
+                    get_support(result, capability)
+                }
+                Capability::DebugTrace => {
+                    let tracer = GethDebugBuiltInTracerType::FourByteTracer;
+                    let options = GethDebugTracingOptions {
+                        tracer: Some(GethDebugTracerType::BuiltInTracer(tracer)),
+                        ..Default::default()
+                    };
+                    let result = self.geth_debug_trace_block(block_number as u32, options, false);
+                    get_support(result.await, capability)
+                }
+                Capability::HistoricalState => {
+                    get_support(self.get_balance(H160::zero(), block).await, capability)
+                }
+            };
+            support.insert(*capability, capability_support);
+        }
+        Ok(Capabilities { block_number, support })
+    }
+}
+
+impl Capabilities {
+    /// check that node supports the capabilities that the datatypes of query require
+    pub fn check(&self, query: &Query) -> Result<(), CollectError> {
+        for datatype in query.datatypes.iter().flat_map(|dt| dt.datatypes()) {
+            for capability in datatype.required_capabilities().iter() {
+                let Some(Support::Unsupported(message)) = self.support.get(capability) else {
+                    continue
+                };
+                let missing = match capability {
+                    Capability::HistoricalState => format!(
+                        "requires state at block {}, which the rpc node does not serve ({})",
+                        self.block_number, message
+                    ),
+                    _ => format!(
+                        "requires {}, which the rpc node does not support ({})",
+                        capability.name(),
+                        message
+                    ),
+                };
+                let alternative = match (capability, get_alternative(datatype)) {
+                    (Capability::HistoricalState, _) => ", use an archive node".to_string(),
+                    (_, Some(alternative)) => format!(", use {} instead", alternative.name()),
+                    (_, None) => "".to_string(),
+                };
+                return Err(CollectError::CollectError(format!(
+                    "dataset {} {}{}",
+                    datatype.name(),
+                    missing,
+                    alternative
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// dataset that collects similar data with the methods of the other tracing namespace
+fn get_alternative(datatype: Datatype) -> Option<Datatype> {
+    let alternative = match datatype {
+        Datatype::Traces => Datatype::GethCalls,
+        Datatype::GethCalls => Datatype::Traces,
+        Datatype::BalanceDiffs => Datatype::GethBalanceDiffs,
+        Datatype::GethBalanceDiffs => Datatype::BalanceDiffs,
+        Datatype::CodeDiffs => Datatype::GethCodeDiffs,
+        Datatype::GethCodeDiffs => Datatype::CodeDiffs,
+        Datatype::NonceDiffs => Datatype::GethNonceDiffs,
+        Datatype::GethNonceDiffs => Datatype::NonceDiffs,
+        Datatype::StorageDiffs => Datatype::GethStorageDiffs,
+        Datatype::GethStorageDiffs => Datatype::StorageDiffs,
+        Datatype::VmTraces => Datatype::GethOpcodes,
+        Datatype::GethOpcodes => Datatype::VmTraces,
+        _ => return None,
+    };
+    Some(alternative)
+}
+
+/// support of capability given the result of its probe
+///
+/// errors of a supported method, e.g. missing state of an old block, do not count against the
+/// method itself, only against historical state
+fn get_support<T>(result: Result<T, CollectError>, capability: &Capability) -> Support {
+    let e = match result {
+        Ok(_) => return Support::Supported,
+        Err(CollectError::ProviderError(e)) => e,
+        Err(e) => return Support::Unknown(e.to_string()),
+    };
+    let Some(response) = RpcError::as_error_response(&e) else {
+        return Support::Unknown(e.to_string())
+    };
+    if capability == &Capability::HistoricalState || is_unsupported_method(response) {
+        Support::Unsupported(response.message.clone())
+    } else {
+        Support::Supported
+    }
+}
+
+/// whether error response means that method does not exist or is disabled, which nodes and
+/// providers report with different codes and messages
+fn is_unsupported_method(response: &JsonRpcError) -> bool {
+    let message = response.message.to_lowercase();
+    response.code == -32601 ||
+        message.contains("not supported") ||
+        message.contains("unsupported") ||
+        message.contains("not available") ||
+        message.contains("does not exist") ||
+        message.contains("method not found") ||
+        message.contains("not whitelisted") ||
+        message.contains("not enabled")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_response(code: i64, message: &str) -> Result<(), CollectError> {
+        let response = JsonRpcError { code, message: message.to_string(), data: None };
+        let e = HttpClientError::JsonRpcError(response);
+        Err(CollectError::ProviderError(ProviderError::JsonRpcClientError(Box::new(e))))
+    }
+
+    #[test]
+    fn test_get_support() {
+        let trace_block = &Capability::TraceBlock;
+        assert_eq!(get_support(Ok(()), trace_block), Support::Supported);
+        let result = error_response(-32601, "the method trace_block does not exist");
+        assert!(matches!(get_support(result, trace_block), Support::Unsupported(_)));
+        let result = error_response(-32000, "Unsupported method: trace_block");
+        assert!(matches!(get_support(result, trace_block), Support::Unsupported(_)));
+
+        // missing state only counts against historical state
+        let result = error_response(-32000, "missing trie node");
+        assert_eq!(get_support(result, trace_block), Support::Supported);
+        let result = error_response(-32000, "missing trie node");
+        assert!(matches!(
+            get_support(result, &Capability::HistoricalState),
+            Support::Unsupported(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_capabilities() {
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        mock.push(U256::from(1)).unwrap();
+        mock.push(U64::from(100)).unwrap();
+        let source = Source {
+            provider: provider.into(),
+            chain_id: 1,
+            inner_request_size: 1,
+            max_concurrent_chunks: None,
+            rpc_url: "".to_string(),
+            semaphore: std::sync::Arc::new(None),
+            rate_limiter: std::sync::Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
+            labels: crate::SourceLabels::default(),
+        };
+        let query = Query {
+            datatypes: vec![],
+            schemas: std::collections::HashMap::new(),
+            time_dimension: crate::TimeDimension::Blocks,
+            partitions: vec![],
+            partitioned_by: vec![],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: crate::QueryLabels { align: false, reorg_buffer: 0 },
+        };
+
+        // later queries reuse probed capabilities without requests to the node
+        let historical_state = [Capability::HistoricalState];
+        for _ in 0..2 {
+            let capabilities = source.get_capabilities(&query, &historical_state).await.unwrap();
+            assert_eq!(capabilities.block_number, 100);
+            assert_eq!(capabilities.support[&Capability::HistoricalState], Support::Supported);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::collect_generic::{fetch_partition, join_partition_handles};
+use super::collect_generic::{abort_partition_handles, fetch_partition, join_partition_handles};
 use crate::{CollectError, Datatype, Params, Partition, Query, Source, ToDataFrames};
 use polars::prelude::*;
 use std::collections::HashMap;
@@ -22,12 +22,14 @@ pub trait CollectByTransaction: 'static + Send + Default + ToDataFrames {
         Err(CollectError::CollectError("CollectByTransaction not implemented".to_string()))
     }
 
-    /// collect data into DataFrame
+    /// collect data into DataFrame, or if `stream` is given, send a DataFrame per response to it as
+    /// soon as the response is transformed, returning no DataFrames
     async fn collect_by_transaction(
         partition: Partition,
         source: Arc<Source>,
         query: Arc<Query>,
         inner_request_size: Option<u64>,
+        stream: Option<mpsc::Sender<R<(Datatype, DataFrame)>>>,
     ) -> R<HashMap<Datatype, DataFrame>> {
         let (sender, receiver) = mpsc::channel(1);
         let chain_id = source.chain_id;
@@ -40,9 +42,43 @@ pub trait CollectByTransaction: 'static + Send + Default + ToDataFrames {
             sender,
         )
         .await?;
-        let columns = Self::transform_channel(receiver, &query).await?;
+        let result = match stream {
+            Some(stream) => {
+                Self::stream_channel(receiver, &query, chain_id, stream).await.map(|_| None)
+            }
+            None => Self::transform_channel(receiver, &query).await.map(Some),
+        };
+        let columns = match result {
+            Ok(columns) => columns,
+            Err(e) => {
+                abort_partition_handles(&handles);
+                return Err(e)
+            }
+        };
         join_partition_handles(handles).await?;
-        columns.create_dfs(&query.schemas, chain_id)
+        match columns {
+            Some(columns) => columns.create_dfs(&query.schemas, chain_id),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    /// convert each transaction-derived response to dataframes and send them
+    async fn stream_channel(
+        mut receiver: mpsc::Receiver<R<Self::Response>>,
+        query: &Arc<Query>,
+        chain_id: u64,
+        stream: mpsc::Sender<R<(Datatype, DataFrame)>>,
+    ) -> R<()> {
+        while let Some(message) = receiver.recv().await {
+            let mut columns = Self::default();
+            Self::transform(message?, &mut columns, query)?;
+            for output in columns.create_dfs(&query.schemas, chain_id)?.into_iter() {
+                stream.send(Ok(output)).await.map_err(|_| {
+                    CollectError::CollectError("tokio mpsc send failure".to_string())
+                })?;
+            }
+        }
+        Ok(())
     }
 
     /// convert transaction-derived data to dataframe
@@ -12,13 +12,33 @@ pub async fn collect_partition(
     source: Arc<Source>,
 ) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
     match query.time_dimension {
-        TimeDimension::Blocks => collect_by_block(datatype, partition, source, query).await,
+        TimeDimension::Blocks => collect_by_block(datatype, partition, source, query, None).await,
         TimeDimension::Transactions => {
-            collect_by_transaction(datatype, partition, source, query).await
+            collect_by_transaction(datatype, partition, source, query, None).await
         }
     }
 }
 
+/// collect single partition, sending a DataFrame per response
+pub async fn collect_partition_stream(
+    datatype: MetaDatatype,
+    partition: Partition,
+    query: Arc<Query>,
+    source: Arc<Source>,
+    sender: mpsc::Sender<Result<(Datatype, DataFrame), CollectError>>,
+) -> Result<(), CollectError> {
+    let sender = Some(sender);
+    let _ = match query.time_dimension {
+        TimeDimension::Blocks => {
+            collect_by_block(datatype, partition, source, query, sender).await?
+        }
+        TimeDimension::Transactions => {
+            collect_by_transaction(datatype, partition, source, query, sender).await?
+        }
+    };
+    Ok(())
+}
+
 /// fetch data for a given partition
 pub async fn fetch_partition<F, Fut, T>(
     f_request: F,
@@ -55,6 +75,15 @@ where
     Ok(handles)
 }
 
+/// abort fetch tasks of partition whose collection failed
+pub(crate) fn abort_partition_handles(
+    handles: &[tokio::task::JoinHandle<Result<(), CollectError>>],
+) {
+    for handle in handles.iter() {
+        handle.abort()
+    }
+}
+
 pub(crate) async fn join_partition_handles(
     handles: Vec<tokio::task::JoinHandle<Result<(), CollectError>>>,
 ) -> Result<(), CollectError> {
@@ -1,4 +1,4 @@
-use crate::{CollectError, ColumnType, Datatype, Dim, Table};
+use crate::{Capability, CollectError, ColumnType, Datatype, Dim, Table};
 use polars::prelude::*;
 use std::collections::HashMap;
 
@@ -110,4 +110,9 @@ pub trait Dataset: Sync + Send {
     fn arg_aliases() -> Option<HashMap<Dim, Dim>> {
         None
     }
+
+    /// node capabilities required to collect dataset
+    fn required_capabilities() -> Vec<Capability> {
+        vec![]
+    }
 }
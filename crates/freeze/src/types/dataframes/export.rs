@@ -1,26 +1,48 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
-use polars::prelude::*;
+use polars::{
+    export::arrow::io::ipc::write::{FileWriter, WriteOptions},
+    prelude::*,
+};
 
-use crate::types::{FileError, FileOutput};
+use crate::types::{provenance, FileError, FileOutput};
 
 /// write polars dataframe to file
 pub(crate) fn df_to_file(
     df: &mut DataFrame,
     filename: &Path,
     file_output: &FileOutput,
+    metadata: &BTreeMap<String, String>,
 ) -> Result<(), FileError> {
-    let tmp_filename = filename.with_extension("_tmp");
+    let tmp_filename = get_tmp_path(filename);
     let result = match filename.extension().and_then(|ex| ex.to_str()) {
-        Some("parquet") => df_to_parquet(df, &tmp_filename, file_output),
+        Some("parquet") => df_to_parquet(df, &tmp_filename, file_output, metadata),
         Some("csv") => df_to_csv(df, &tmp_filename),
         Some("json") => df_to_json(df, &tmp_filename),
+        Some("ndjson") => df_to_ndjson(df, &tmp_filename),
+        Some("arrow") => df_to_arrow(df, &tmp_filename, file_output, metadata),
         _ => return Err(FileError::FileWriteError),
     };
     match result {
         Ok(()) => std::fs::rename(tmp_filename, filename).map_err(|_e| FileError::FileWriteError),
         Err(_e) => Err(FileError::FileWriteError),
+    }?;
+    if provenance::uses_sidecar(filename) {
+        write_sidecar(filename, metadata)?;
     }
+    Ok(())
+}
+
+/// write metadata of output file to a json sidecar file next to it
+fn write_sidecar(filename: &Path, metadata: &BTreeMap<String, String>) -> Result<(), FileError> {
+    let content = serde_json::to_string_pretty(metadata).map_err(|_e| FileError::FileWriteError)?;
+    std::fs::write(provenance::get_sidecar_path(filename), content)
+        .map_err(|_e| FileError::FileWriteError)
+}
+
+/// path of temporary file used while writing output file
+pub(crate) fn get_tmp_path(filename: &Path) -> std::path::PathBuf {
+    filename.with_extension("_tmp")
 }
 
 /// write polars dataframe to parquet file
@@ -28,14 +50,9 @@ fn df_to_parquet(
     df: &mut DataFrame,
     filename: &Path,
     file_output: &FileOutput,
+    metadata: &BTreeMap<String, String>,
 ) -> Result<(), FileError> {
-    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
-    let result = ParquetWriter::new(file)
-        .with_statistics(file_output.parquet_statistics)
-        .with_compression(file_output.parquet_compression)
-        .with_row_group_size(file_output.row_group_size)
-        .finish(df);
-    match result {
+    match super::parquet::write_parquet(df, filename, file_output, metadata) {
         Err(_e) => Err(FileError::FileWriteError),
         _ => Ok(()),
     }
@@ -51,6 +68,26 @@ fn df_to_csv(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
     }
 }
 
+/// write polars dataframe to arrow ipc file, with metadata in the schema
+fn df_to_arrow(
+    df: &mut DataFrame,
+    filename: &Path,
+    file_output: &FileOutput,
+    metadata: &BTreeMap<String, String>,
+) -> Result<(), FileError> {
+    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
+    let mut schema = df.schema().to_arrow();
+    schema.metadata = metadata.clone();
+    let options = WriteOptions { compression: file_output.ipc_compression.map(|c| c.into()) };
+    let mut writer = FileWriter::try_new(file, std::sync::Arc::new(schema), None, options)
+        .map_err(|_e| FileError::FileWriteError)?;
+    df.align_chunks();
+    for chunk in df.iter_chunks() {
+        writer.write(&chunk, None).map_err(|_e| FileError::FileWriteError)?;
+    }
+    writer.finish().map_err(|_e| FileError::FileWriteError)
+}
+
 /// write polars dataframe to json file
 fn df_to_json(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
     let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
@@ -60,3 +97,32 @@ fn df_to_json(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
         _ => Ok(()),
     }
 }
+
+/// write polars dataframe to newline-delimited json file, one object per row
+fn df_to_ndjson(df: &mut DataFrame, filename: &Path) -> Result<(), FileError> {
+    let mut df = binary_columns_to_hex(df)?;
+    let file = std::fs::File::create(filename).map_err(|_e| FileError::FileWriteError)?;
+    let result = JsonWriter::new(file).with_json_format(JsonFormat::JsonLines).finish(&mut df);
+    match result {
+        Err(_e) => Err(FileError::FileWriteError),
+        _ => Ok(()),
+    }
+}
+
+/// encode binary columns as 0x-prefixed hex strings
+fn binary_columns_to_hex(df: &DataFrame) -> Result<DataFrame, FileError> {
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|column| match column.dtype() {
+            DataType::Binary => {
+                let values = column.binary().map_err(|_e| FileError::FileWriteError)?;
+                let encoded: Vec<Option<String>> =
+                    values.into_iter().map(|value| value.map(prefix_hex::encode)).collect();
+                Ok(Series::new(column.name(), encoded))
+            }
+            _ => Ok(column.clone()),
+        })
+        .collect::<Result<Vec<_>, FileError>>()?;
+    DataFrame::new(columns).map_err(|_e| FileError::FileWriteError)
+}
@@ -1,4 +1,5 @@
 mod export;
+mod parquet;
 mod read;
 mod sort;
 mod u256s;
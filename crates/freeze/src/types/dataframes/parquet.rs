@@ -0,0 +1,321 @@
+use crate::types::FileOutput;
+use parquet_format_safe::{
+    thrift::protocol::TCompactOutputProtocol, BloomFilterAlgorithm, BloomFilterCompression,
+    BloomFilterHash, BloomFilterHeader, SplitBlockAlgorithm, Uncompressed, XxHash,
+};
+use polars::{
+    export::arrow::{
+        chunk::Chunk,
+        compute::cast::{cast, CastOptions},
+        datatypes::{ArrowDataType, ArrowSchema, Field, IntegerType},
+    },
+    prelude::*,
+};
+use polars_parquet::{
+    parquet::bloom_filter,
+    write::{
+        transverse, Encoding, FileWriter, KeyValue, RowGroupIterator, ThriftFileMetaData, Version,
+        WriteOptions,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// number of rows per row group if neither row group size nor number of row groups is given
+const DEFAULT_ROW_GROUP_SIZE: usize = 512 * 512;
+
+/// false positive probability of bloom filters
+const BLOOM_FILTER_FPP: f64 = 0.01;
+
+/// hashes of the values of a column in a row group
+type ColumnHashes = (String, HashSet<u64>);
+
+/// write dataframe to parquet file
+///
+/// columns selected by file output are dictionary encoded or get a bloom filter per row group,
+/// and metadata is embedded as key-value metadata in the footer
+pub(crate) fn write_parquet(
+    df: &DataFrame,
+    filename: &Path,
+    file_output: &FileOutput,
+    metadata: &BTreeMap<String, String>,
+) -> PolarsResult<()> {
+    let names = df.get_column_names();
+    let dictionary: Vec<bool> = names
+        .iter()
+        .map(|name| matches_any(&file_output.parquet_dictionary_columns, name))
+        .collect();
+    let bloom_filter: Vec<bool> = names
+        .iter()
+        .map(|name| matches_any(&file_output.parquet_bloom_filter_columns, name))
+        .collect();
+
+    // row groups are encoded with dictionary types, while the file schema keeps the plain
+    // types so that dictionary encoded columns are read back with their original types
+    let schema = df.schema().to_arrow();
+    let fields: Vec<Field> = schema
+        .fields
+        .iter()
+        .zip(dictionary.iter())
+        .map(|(field, dictionary)| match dictionary {
+            true => Field::new(
+                field.name.clone(),
+                ArrowDataType::Dictionary(
+                    IntegerType::UInt32,
+                    Box::new(field.data_type.clone()),
+                    false,
+                ),
+                field.is_nullable,
+            ),
+            false => field.clone(),
+        })
+        .collect();
+    let encodings = fields
+        .iter()
+        .map(|field| {
+            transverse(&field.data_type, |data_type| match data_type {
+                ArrowDataType::Dictionary(..) => Encoding::RleDictionary,
+                _ => Encoding::Plain,
+            })
+        })
+        .collect();
+    let encoded_schema = ArrowSchema::from(fields.clone());
+
+    // split dataframe into row groups
+    let row_group_size = row_group_size(df.height(), file_output);
+    let mut chunks = Vec::new();
+    let mut hashes: Vec<Vec<ColumnHashes>> = Vec::new();
+    for offset in (0..df.height()).step_by(row_group_size) {
+        let mut group = df.slice(offset as i64, row_group_size);
+        group.as_single_chunk();
+        for chunk in group.iter_chunks() {
+            let mut arrays = Vec::new();
+            for (array, field) in chunk.into_arrays().into_iter().zip(fields.iter()) {
+                match field.data_type {
+                    ArrowDataType::Dictionary(..) => {
+                        arrays.push(cast(array.as_ref(), &field.data_type, CastOptions::default())?)
+                    }
+                    _ => arrays.push(array),
+                }
+            }
+            chunks.push(Chunk::new(arrays));
+        }
+        let mut group_hashes = Vec::new();
+        for (series, bloom_filter) in group.get_columns().iter().zip(bloom_filter.iter()) {
+            if let (true, Some(column_hashes)) = (bloom_filter, column_hashes(series)?) {
+                group_hashes.push((series.name().to_string(), column_hashes));
+            }
+        }
+        hashes.push(group_hashes);
+    }
+
+    // write row groups
+    let options = WriteOptions {
+        write_statistics: file_output.parquet_statistics,
+        compression: file_output.parquet_compression.into(),
+        version: Version::V2,
+        data_pagesize_limit: file_output.parquet_page_size,
+    };
+    let tail = Rc::new(RefCell::new(None));
+    let file = TailWriter { inner: BufWriter::new(File::create(filename)?), tail: tail.clone() };
+    let mut writer = FileWriter::try_new(file, schema, options)?;
+    let row_groups =
+        RowGroupIterator::try_new(chunks.into_iter().map(Ok), &encoded_schema, options, encodings)?;
+    for row_group in row_groups {
+        writer.write(row_group?)?;
+    }
+    let key_value_metadata = metadata
+        .iter()
+        .map(|(key, value)| KeyValue { key: key.clone(), value: Some(value.clone()) })
+        .collect();
+
+    // hold back page indexes and footer, so that bloom filters can be written before the footer
+    *tail.borrow_mut() = Some(Vec::new());
+    let file_size = writer.end(Some(key_value_metadata))?;
+    let (file, mut file_metadata) = writer.into_inner_and_metadata();
+    let tail = tail.borrow_mut().take().unwrap_or_default();
+    let mut file = file.inner;
+    let metadata_size = tail
+        .len()
+        .checked_sub(8)
+        .and_then(|start| tail[start..start + 4].try_into().ok())
+        .map(|bytes| i32::from_le_bytes(bytes) as usize);
+    let footer_size = match metadata_size {
+        Some(metadata_size) if metadata_size + 8 <= tail.len() => metadata_size + 8,
+        _ => return Err(PolarsError::ComputeError("could not write parquet footer".into())),
+    };
+    let (indexes, footer) = tail.split_at(tail.len() - footer_size);
+    file.write_all(indexes)?;
+    if hashes.iter().all(|group_hashes| group_hashes.is_empty()) {
+        file.write_all(footer)?;
+    } else {
+        let offset = file_size - footer_size as u64;
+        write_bloom_filters(&mut file, offset, &mut file_metadata, &hashes)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// writer that holds back everything written once `tail` is set
+struct TailWriter<W: Write> {
+    inner: W,
+    tail: Rc<RefCell<Option<Vec<u8>>>>,
+}
+
+impl<W: Write> Write for TailWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.tail.borrow_mut().as_mut() {
+            Some(tail) => {
+                tail.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            None => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// number of rows per row group, so that a file has `n_row_groups` row groups if given
+fn row_group_size(n_rows: usize, file_output: &FileOutput) -> usize {
+    let size = match (file_output.row_group_size, file_output.n_row_groups) {
+        (Some(row_group_size), _) => row_group_size,
+        (None, Some(n_row_groups)) => (n_rows + n_row_groups - 1) / n_row_groups.max(1),
+        (None, None) => DEFAULT_ROW_GROUP_SIZE,
+    };
+    size.max(1)
+}
+
+/// whether column name matches any of the given names, `*` at the start of a name matches
+/// any prefix, e.g. `*address` matches `from_address`
+pub(crate) fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| match pattern.strip_prefix('*') {
+        Some(suffix) => name.ends_with(suffix),
+        None => pattern == name,
+    })
+}
+
+/// hashes of the distinct values of a binary or string column
+fn column_hashes(series: &Series) -> PolarsResult<Option<HashSet<u64>>> {
+    let hashes = match series.dtype() {
+        DataType::Binary => {
+            series.binary()?.into_iter().flatten().map(bloom_filter::hash_byte).collect()
+        }
+        DataType::Utf8 => {
+            series.utf8()?.into_iter().flatten().map(bloom_filter::hash_byte).collect()
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(hashes))
+}
+
+/// number of bytes of bloom filter for number of distinct values, as recommended by the
+/// parquet format specification
+fn bloom_filter_size(n_distinct: usize) -> usize {
+    let n_bits = -8.0 * (n_distinct as f64) / (1.0 - BLOOM_FILTER_FPP.powf(1.0 / 8.0)).ln();
+    ((n_bits / 8.0).ceil() as usize).next_power_of_two().clamp(32, 128 * 1024 * 1024)
+}
+
+/// write bloom filters starting at `offset` of file, followed by a footer that references them
+fn write_bloom_filters<W: Write>(
+    writer: &mut W,
+    mut offset: u64,
+    metadata: &mut ThriftFileMetaData,
+    hashes: &[Vec<ColumnHashes>],
+) -> PolarsResult<()> {
+    for (row_group, group_hashes) in metadata.row_groups.iter_mut().zip(hashes.iter()) {
+        for (name, column_hashes) in group_hashes.iter() {
+            let column_metadata = row_group
+                .columns
+                .iter_mut()
+                .filter_map(|column| column.meta_data.as_mut())
+                .find(|column_metadata| column_metadata.path_in_schema == [name.clone()]);
+            let Some(column_metadata) = column_metadata else { continue };
+
+            let mut bitset = vec![0; bloom_filter_size(column_hashes.len())];
+            for hash in column_hashes.iter() {
+                bloom_filter::insert(&mut bitset, *hash);
+            }
+            let header = BloomFilterHeader::new(
+                bitset.len() as i32,
+                BloomFilterAlgorithm::BLOCK(SplitBlockAlgorithm {}),
+                BloomFilterHash::XXHASH(XxHash {}),
+                BloomFilterCompression::UNCOMPRESSED(Uncompressed {}),
+            );
+            let mut protocol = TCompactOutputProtocol::new(&mut *writer);
+            let header_size = header.write_to_out_protocol(&mut protocol).map_err(thrift_err)?;
+            writer.write_all(&bitset)?;
+            column_metadata.bloom_filter_offset = Some(offset as i64);
+            offset += (header_size + bitset.len()) as u64;
+        }
+    }
+
+    let footer = serialize_metadata(metadata)?;
+    writer.write_all(&footer)?;
+    writer.write_all(&(footer.len() as i32).to_le_bytes())?;
+    writer.write_all(b"PAR1")?;
+    Ok(())
+}
+
+fn serialize_metadata(metadata: &ThriftFileMetaData) -> PolarsResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut protocol = TCompactOutputProtocol::new(&mut bytes);
+    metadata.write_to_out_protocol(&mut protocol).map_err(thrift_err)?;
+    Ok(bytes)
+}
+
+fn thrift_err(e: parquet_format_safe::thrift::Error) -> PolarsError {
+    PolarsError::ComputeError(format!("could not write parquet metadata: {}", e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileFormat;
+    use polars_parquet::parquet::read::read_metadata;
+
+    #[test]
+    fn test_write_parquet_with_bloom_filters() {
+        let path =
+            std::env::temp_dir().join(format!("cryo_parquet_{}.parquet", std::process::id()));
+        let file_output = FileOutput {
+            n_row_groups: Some(2),
+            parquet_dictionary_columns: vec!["from_address".to_string()],
+            parquet_bloom_filter_columns: vec!["*address".to_string()],
+            metadata: [("chain_id".to_string(), "1".to_string())].into_iter().collect(),
+            ..FileOutput::new_test(std::env::temp_dir(), FileFormat::Parquet)
+        };
+        let df = df!(
+            "block_number" => &[0u32, 1, 2, 3],
+            "from_address" => &[vec![1u8; 20], vec![1u8; 20], vec![2u8; 20], vec![3u8; 20]],
+        )
+        .unwrap();
+        write_parquet(&df, &path, &file_output, &file_output.metadata).unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let metadata = read_metadata(&mut file).unwrap();
+        assert_eq!(metadata.row_groups.len(), 2);
+        let key_value = metadata.key_value_metadata.unwrap_or_default();
+        assert!(key_value
+            .iter()
+            .any(|kv| kv.key == "chain_id" && kv.value.as_deref() == Some("1")));
+        let column = &metadata.row_groups[1].columns()[1];
+        assert!(column.column_encoding().contains(&parquet_format_safe::Encoding::RLE_DICTIONARY));
+        let mut bitset = Vec::new();
+        bloom_filter::read(column, &mut file, &mut bitset).unwrap();
+        assert!(bloom_filter::is_in_set(&bitset, bloom_filter::hash_byte([3u8; 20])));
+        assert!(!bloom_filter::is_in_set(&bitset, bloom_filter::hash_byte([4u8; 20])));
+
+        let read = ParquetReader::new(File::open(&path).unwrap()).finish().unwrap();
+        assert!(read.frame_equal(&df));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -29,3 +29,102 @@ pub fn read_binary_column(path: &str, column: &str) -> Result<Vec<Vec<u8>>, Pars
         })
         .collect()
 }
+
+/// distinct (block number, block hash, parent hash) entries stored in an output file
+pub type StoredBlockHashes = std::collections::BTreeSet<(u64, Vec<u8>, Option<Vec<u8>>)>;
+
+/// read block hashes of parquet file, along with parent hashes if present
+///
+/// returns None if the file has no block_hash column
+pub fn read_block_hashes(path: &str) -> Result<Option<StoredBlockHashes>, ParseError> {
+    let file = std::fs::File::open(path)
+        .map_err(|_e| ParseError::ParseError("could not open file path".to_string()))?;
+    let mut reader = ParquetReader::new(file);
+    let schema = reader
+        .schema()
+        .map_err(|_e| ParseError::ParseError("could not read schema of file".to_string()))?;
+    let has_column = |name: &str| schema.fields.iter().any(|field| field.name == name);
+    if !has_column("block_number") || !has_column("block_hash") {
+        return Ok(None)
+    }
+    let mut columns = vec!["block_number".to_string(), "block_hash".to_string()];
+    if has_column("parent_hash") {
+        columns.push("parent_hash".to_string());
+    }
+
+    let df = reader
+        .with_columns(Some(columns))
+        .finish()
+        .map_err(|_e| ParseError::ParseError("could not read data from column".to_string()))?;
+    let block_numbers = df
+        .column("block_number")
+        .and_then(|series| series.cast(&DataType::UInt64))
+        .map_err(|_e| ParseError::ParseError("could not get column".to_string()))?;
+    let block_numbers = block_numbers
+        .u64()
+        .map_err(|_e| ParseError::ParseError("could not convert to integer column".to_string()))?;
+    let block_hashes = read_hashes(&df, "block_hash")?;
+    let parent_hashes = match df.column("parent_hash") {
+        Ok(_) => Some(read_hashes(&df, "parent_hash")?),
+        Err(_) => None,
+    };
+
+    let mut hashes = StoredBlockHashes::new();
+    for (i, (block_number, block_hash)) in block_numbers.into_iter().zip(block_hashes).enumerate() {
+        let (Some(block_number), Some(block_hash)) = (block_number, block_hash) else { continue };
+        let parent_hash = parent_hashes.as_ref().and_then(|hashes| hashes[i].clone());
+        hashes.insert((block_number, block_hash, parent_hash));
+    }
+    Ok(Some(hashes))
+}
+
+/// read hashes of a binary column, or of a utf8 column of hex strings as written with --hex
+fn read_hashes(df: &DataFrame, column: &str) -> Result<Vec<Option<Vec<u8>>>, ParseError> {
+    let series = df
+        .column(column)
+        .map_err(|_e| ParseError::ParseError("could not get column".to_string()))?;
+    match series.dtype() {
+        DataType::Binary => Ok(series
+            .binary()
+            .map_err(|_e| ParseError::ParseError("could not convert to binary column".to_string()))?
+            .into_iter()
+            .map(|hash| hash.map(|hash| hash.to_vec()))
+            .collect()),
+        DataType::Utf8 => series
+            .utf8()
+            .map_err(|_e| ParseError::ParseError("could not convert to utf8 column".to_string()))?
+            .into_iter()
+            .map(|hash| {
+                hash.map(prefix_hex::decode::<Vec<u8>>).transpose().map_err(|_e| {
+                    ParseError::ParseError(format!("could not decode hex in {} column", column))
+                })
+            })
+            .collect(),
+        _ => Err(ParseError::ParseError("could not convert to binary column".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_hex_block_hashes() {
+        let path =
+            std::env::temp_dir().join(format!("cryo_read_hex_{}.parquet", std::process::id()));
+        let mut df = df!(
+            "block_number" => [1u32, 2u32],
+            "block_hash" => ["0x0a0b", "0x0c0d"],
+            "parent_hash" => [Some("0x0102"), None],
+        )
+        .unwrap();
+        let file = std::fs::File::create(&path).unwrap();
+        ParquetWriter::new(file).finish(&mut df).unwrap();
+
+        let hashes = read_block_hashes(path.to_str().unwrap()).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected: StoredBlockHashes =
+            [(1, vec![10, 11], Some(vec![1, 2])), (2, vec![12, 13], None)].into_iter().collect();
+        assert_eq!(hashes, expected);
+    }
+}
@@ -90,6 +90,13 @@ macro_rules! define_datatypes {
                 }
             }
 
+            /// node capabilities required to collect datatype
+            pub fn required_capabilities(&self) -> Vec<Capability> {
+                match *self {
+                    $(Datatype::$datatype => $datatype::required_capabilities(),)*
+                }
+            }
+
             /// whether datatype can be collected by block
             pub fn can_collect_by_block(&self) -> bool {
                 match *self {
@@ -105,12 +112,13 @@ macro_rules! define_datatypes {
             }
         }
 
-        /// collect by block
+        /// collect by block, sending a DataFrame per response to `stream` if given
         pub async fn collect_by_block(
             datatype: MetaDatatype,
             partition: Partition,
             source: Arc<Source>,
-            query: Arc<Query>
+            query: Arc<Query>,
+            stream: Option<tokio::sync::mpsc::Sender<Result<(Datatype, DataFrame), CollectError>>>,
         ) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
             let task = match datatype {
                 MetaDatatype::Scalar(datatype) => {
@@ -121,37 +129,38 @@ macro_rules! define_datatypes {
                     };
                     match datatype {
                     $(
-                        Datatype::$datatype => $datatype::collect_by_block(partition, source, query, inner_request_size),
+                        Datatype::$datatype => $datatype::collect_by_block(partition, source, query, inner_request_size, stream),
                     )*
                     }
                 },
                 MetaDatatype::Multi(datatype) => match datatype {
                     MultiDatatype::BlocksAndTransactions => {
-                        BlocksAndTransactions::collect_by_block(partition, source, query, None)
+                        BlocksAndTransactions::collect_by_block(partition, source, query, None, stream)
                     }
                     MultiDatatype::CallTraceDerivatives => {
-                        CallTraceDerivatives::collect_by_block(partition, source, query, None)
+                        CallTraceDerivatives::collect_by_block(partition, source, query, None, stream)
                     }
                     MultiDatatype::GethStateDiffs => {
-                        GethStateDiffs::collect_by_block(partition, source, query, None)
+                        GethStateDiffs::collect_by_block(partition, source, query, None, stream)
                     },
                     MultiDatatype::StateDiffs => {
-                        StateDiffs::collect_by_block(partition, source, query, None)
+                        StateDiffs::collect_by_block(partition, source, query, None, stream)
                     },
                     MultiDatatype::StateReads => {
-                        StateReads::collect_by_block(partition, source, query, None)
+                        StateReads::collect_by_block(partition, source, query, None, stream)
                     },
                 },
             };
             task.await
         }
 
-        /// collect by transaction
+        /// collect by transaction, sending a DataFrame per response to `stream` if given
         pub async fn collect_by_transaction(
             datatype: MetaDatatype,
             partition: Partition,
             source: Arc<Source>,
             query: Arc<Query>,
+            stream: Option<tokio::sync::mpsc::Sender<Result<(Datatype, DataFrame), CollectError>>>,
         ) -> Result<HashMap<Datatype, DataFrame>, CollectError> {
             let task = match datatype {
                 MetaDatatype::Scalar(datatype) => {
@@ -162,7 +171,7 @@ macro_rules! define_datatypes {
                     };
                     match datatype {
                     $(
-                        Datatype::$datatype => $datatype::collect_by_transaction(partition, source, query, inner_request_size),
+                        Datatype::$datatype => $datatype::collect_by_transaction(partition, source, query, inner_request_size, stream),
                     )*
                     }
                 },
@@ -170,19 +179,19 @@ macro_rules! define_datatypes {
                     let inner_request_size = None;
                     match datatype {
                         MultiDatatype::BlocksAndTransactions => {
-                            BlocksAndTransactions::collect_by_transaction(partition, source, query, inner_request_size)
+                            BlocksAndTransactions::collect_by_transaction(partition, source, query, inner_request_size, stream)
                         }
                         MultiDatatype::CallTraceDerivatives => {
-                            CallTraceDerivatives::collect_by_transaction(partition, source, query, None)
+                            CallTraceDerivatives::collect_by_transaction(partition, source, query, None, stream)
                         }
                         MultiDatatype::GethStateDiffs => {
-                            GethStateDiffs::collect_by_transaction(partition, source, query, None)
+                            GethStateDiffs::collect_by_transaction(partition, source, query, None, stream)
                         },
                         MultiDatatype::StateDiffs => {
-                            StateDiffs::collect_by_transaction(partition, source, query, inner_request_size)
+                            StateDiffs::collect_by_transaction(partition, source, query, inner_request_size, stream)
                         },
                         MultiDatatype::StateReads => {
-                            StateReads::collect_by_transaction(partition, source, query, inner_request_size)
+                            StateReads::collect_by_transaction(partition, source, query, inner_request_size, stream)
                         },
                     }
                 },
@@ -0,0 +1,350 @@
+use crate::{err, split::SplitIndex, CollectError, FileOutput, Partition, Query, CRYO_VERSION};
+use polars::{
+    export::arrow::datatypes::{ArrowDataType, Field},
+    prelude::*,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// name of directory holding the commits of a delta table, inside the dataset directory
+pub(crate) const DELTA_LOG_DIRNAME: &str = "_delta_log";
+
+/// number of times a commit is retried when another writer commits the same version
+const MAX_COMMIT_ATTEMPTS: usize = 10;
+
+/// size and modification time of each file that is part of the current version of the table
+type ActiveFiles = HashMap<String, (u64, u64)>;
+
+/// commit the output files of partitions to the delta table of each dataset directory
+///
+/// called once every partition of a run has completed, so that readers of the table see either
+/// none or all of the files of the run. files of skipped partitions are included, because they
+/// may have been written by an earlier run that did not complete
+pub(crate) fn commit_partitions(
+    query: &Query,
+    sink: &FileOutput,
+    partitions: &[&Partition],
+) -> Result<(), CollectError> {
+    let mut tables: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    let mut splits = SplitIndex::default();
+    for partition in partitions.iter() {
+        for (datatype, path) in sink.get_paths(query, partition, None)?.into_iter() {
+            let files = splits.output_files(&path, sink)?.unwrap_or_default();
+            tables.entry(sink.get_dataset_dir(datatype)).or_default().extend(files);
+        }
+    }
+    for (table_dir, files) in tables.iter() {
+        commit(table_dir, files, &[])?;
+    }
+    Ok(())
+}
+
+/// commit new version of delta table that adds files that are new or were rewritten, and removes
+/// `removed` files and files that no longer exist, returning the committed version if there were
+/// any changes
+///
+/// files that are replaced should be passed as `removed` and only deleted once the commit lands,
+/// so that the current version of the table never references missing files
+///
+/// commits are written to a temporary file and then hard linked to their final name, which fails
+/// if another writer already committed that version, in which case the commit is retried
+pub(crate) fn commit(
+    table_dir: &Path,
+    files: &[PathBuf],
+    removed: &[PathBuf],
+) -> Result<Option<u64>, CollectError> {
+    let log_dir = table_dir.join(DELTA_LOG_DIRNAME);
+    std::fs::create_dir_all(&log_dir).map_err(|_| err("could not create delta log directory"))?;
+    for _ in 0..MAX_COMMIT_ATTEMPTS {
+        let (version, active) = read_log(&log_dir)?;
+        let mut changes = Vec::new();
+        for file in files.iter() {
+            let relative = relative_path(table_dir, file)?;
+            let (size, modification_time) = file_stats(file)?;
+            if active.get(&relative) != Some(&(size, modification_time)) {
+                changes.push(json!({"add": {
+                    "path": relative,
+                    "partitionValues": {},
+                    "size": size,
+                    "modificationTime": modification_time,
+                    "dataChange": true,
+                }}));
+            }
+        }
+        let removed: Vec<String> =
+            removed.iter().map(|file| relative_path(table_dir, file)).collect::<Result<_, _>>()?;
+        let now = timestamp_millis(SystemTime::now());
+        for relative in active.keys() {
+            if removed.contains(relative) || !table_dir.join(relative).exists() {
+                changes.push(json!({"remove": {
+                    "path": relative,
+                    "deletionTimestamp": now,
+                    "dataChange": true,
+                }}));
+            }
+        }
+        if changes.is_empty() {
+            return Ok(None)
+        }
+
+        let mut actions = Vec::new();
+        if version == 0 {
+            let first = files.first().ok_or_else(|| err("no files to create delta table"))?;
+            actions.push(json!({"protocol": {"minReaderVersion": 1, "minWriterVersion": 2}}));
+            actions.push(json!({"metaData": {
+                "id": table_id(table_dir, now),
+                "format": {"provider": "parquet", "options": {}},
+                "schemaString": schema_string(first)?,
+                "partitionColumns": [],
+                "configuration": {},
+                "createdTime": now,
+            }}));
+        }
+        actions.extend(changes);
+        actions.push(json!({"commitInfo": {
+            "timestamp": now,
+            "operation": "WRITE",
+            "operationParameters": {"mode": "Append"},
+            "engineInfo": format!("cryo/{}", CRYO_VERSION),
+        }}));
+
+        if write_commit(&log_dir, version, &actions)? {
+            return Ok(Some(version))
+        }
+    }
+    Err(err("could not commit to delta table, other writers committed concurrently"))
+}
+
+/// write commit of version, returning false if the version was already committed
+fn write_commit(log_dir: &Path, version: u64, actions: &[Value]) -> Result<bool, CollectError> {
+    let path = log_dir.join(format!("{:020}.json", version));
+    let tmp_path = log_dir.join(format!(".{:020}.json.{}.tmp", version, std::process::id()));
+    let mut file =
+        std::fs::File::create(&tmp_path).map_err(|_| err("could not write delta commit"))?;
+    for action in actions.iter() {
+        writeln!(file, "{}", action).map_err(|_| err("could not write delta commit"))?;
+    }
+    file.sync_all().map_err(|_| err("could not write delta commit"))?;
+    let linked = std::fs::hard_link(&tmp_path, path);
+    std::fs::remove_file(&tmp_path).map_err(|_| err("could not remove delta commit tmp file"))?;
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(err(&format!("could not write delta commit: {}", e))),
+    }
+}
+
+/// read commits of delta log, returning the next version and the currently active files
+fn read_log(log_dir: &Path) -> Result<(u64, ActiveFiles), CollectError> {
+    let mut versions: Vec<u64> = Vec::new();
+    let entries = std::fs::read_dir(log_dir).map_err(|_| err("could not read delta log"))?;
+    for entry in entries {
+        let name = entry.map_err(|_| err("could not read delta log"))?.file_name();
+        let name = name.to_string_lossy();
+        if let Some(version) = name.strip_suffix(".json").and_then(|stem| stem.parse().ok()) {
+            versions.push(version);
+        }
+    }
+    versions.sort();
+
+    let mut active = HashMap::new();
+    for (expected, version) in versions.iter().enumerate() {
+        if *version != expected as u64 {
+            return Err(err(&format!("delta log is missing version {}", expected)))
+        }
+        let path = log_dir.join(format!("{:020}.json", version));
+        let content = std::fs::read_to_string(path).map_err(|_| err("could not read delta log"))?;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let action: Value =
+                serde_json::from_str(line).map_err(|_| err("could not parse delta log"))?;
+            if let Some(add) = action.get("add") {
+                let path = add["path"].as_str().unwrap_or_default().to_string();
+                let size = add["size"].as_u64().unwrap_or_default();
+                let modification_time = add["modificationTime"].as_u64().unwrap_or_default();
+                active.insert(path, (size, modification_time));
+            } else if let Some(remove) = action.get("remove") {
+                active.remove(remove["path"].as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok((versions.len() as u64, active))
+}
+
+/// path of file relative to table directory, with `/` separators
+fn relative_path(table_dir: &Path, file: &Path) -> Result<String, CollectError> {
+    let relative =
+        file.strip_prefix(table_dir).map_err(|_| err("file is outside of delta table"))?;
+    let components: Vec<String> =
+        relative.iter().map(|component| component.to_string_lossy().to_string()).collect();
+    Ok(components.join("/"))
+}
+
+fn file_stats(file: &Path) -> Result<(u64, u64), CollectError> {
+    let metadata = std::fs::metadata(file).map_err(|_| err("could not read file metadata"))?;
+    let modified = metadata.modified().map_err(|_| err("could not read file metadata"))?;
+    Ok((metadata.len(), timestamp_millis(modified)))
+}
+
+fn timestamp_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// uuid formatted identifier of table, derived from its path and creation time
+fn table_id(table_dir: &Path, created_time: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(table_dir.to_string_lossy().as_bytes());
+    hasher.update(created_time.to_le_bytes());
+    let hex = format!("{:x}", hasher.finalize());
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+/// check that dataframe can be committed to a delta table, before it is written
+///
+/// every column needs a delta type, and u64 columns, which delta tables store as long, must not
+/// hold values above `i64::MAX`
+pub(crate) fn check_dataframe(df: &DataFrame) -> Result<(), CollectError> {
+    for field in df.schema().to_arrow().fields.iter() {
+        delta_type(&field.data_type)?;
+    }
+    for column in df.get_columns() {
+        if exceeds_long(column)? {
+            return Err(err(&format!(
+                "column {} has u64 values above {}, which delta tables cannot store, write it \
+                 without --delta",
+                column.name(),
+                i64::MAX
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// whether series holds u64 values above `i64::MAX`, including values within lists and structs
+fn exceeds_long(series: &Series) -> Result<bool, CollectError> {
+    match series.dtype() {
+        DataType::UInt64 => Ok(series.u64()?.max().map_or(false, |max| max > i64::MAX as u64)),
+        DataType::List(_) => exceeds_long(&series.explode()?),
+        DataType::Struct(_) => {
+            for field in series.struct_()?.fields().iter() {
+                if exceeds_long(field)? {
+                    return Ok(true)
+                }
+            }
+            Ok(false)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// delta schema of parquet file, as serialized json
+fn schema_string(file: &Path) -> Result<String, CollectError> {
+    let file = std::fs::File::open(file).map_err(|_| err("could not open file"))?;
+    let schema = ParquetReader::new(file).schema()?;
+    Ok(struct_type(&schema.fields)?.to_string())
+}
+
+fn struct_type(fields: &[Field]) -> Result<Value, CollectError> {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            Ok(json!({
+                "name": field.name,
+                "type": delta_type(&field.data_type)?,
+                "nullable": field.is_nullable,
+                "metadata": {},
+            }))
+        })
+        .collect::<Result<Vec<Value>, CollectError>>()?;
+    Ok(json!({"type": "struct", "fields": fields}))
+}
+
+/// delta type of arrow type, unsigned integers use the next larger signed type where possible
+///
+/// delta has no unsigned 64 bit type, so u64 columns are declared as `long`, which is lossless
+/// because `check_dataframe` refuses u64 values above `i64::MAX` before files are written
+fn delta_type(data_type: &ArrowDataType) -> Result<Value, CollectError> {
+    let name = match data_type {
+        ArrowDataType::Boolean => "boolean",
+        ArrowDataType::Int8 => "byte",
+        ArrowDataType::Int16 | ArrowDataType::UInt8 => "short",
+        ArrowDataType::Int32 | ArrowDataType::UInt16 => "integer",
+        ArrowDataType::Int64 | ArrowDataType::UInt32 | ArrowDataType::UInt64 => "long",
+        ArrowDataType::Float32 => "float",
+        ArrowDataType::Float64 => "double",
+        ArrowDataType::Utf8 | ArrowDataType::LargeUtf8 => "string",
+        ArrowDataType::Binary | ArrowDataType::LargeBinary => "binary",
+        ArrowDataType::Decimal(precision, scale) => {
+            return Ok(Value::from(format!("decimal({},{})", precision, scale)))
+        }
+        ArrowDataType::List(field) | ArrowDataType::LargeList(field) => {
+            return Ok(json!({
+                "type": "array",
+                "elementType": delta_type(&field.data_type)?,
+                "containsNull": field.is_nullable,
+            }))
+        }
+        ArrowDataType::Struct(fields) => return struct_type(fields),
+        data_type => {
+            return Err(err(&format!("delta tables have no type for {:?} columns", data_type)))
+        }
+    };
+    Ok(Value::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit() {
+        let table_dir = std::env::temp_dir().join(format!("cryo_delta_{}", std::process::id()));
+        std::fs::create_dir_all(&table_dir).unwrap();
+        let mut paths = Vec::new();
+        for i in 0..2u32 {
+            let path = table_dir.join(format!("ethereum__blocks__{}_to_{}.parquet", i, i));
+            let mut df = df!("block_number" => &[i]).unwrap();
+            ParquetWriter::new(std::fs::File::create(&path).unwrap()).finish(&mut df).unwrap();
+            paths.push(path);
+        }
+
+        assert_eq!(commit(&table_dir, &paths, &[]).unwrap(), Some(0));
+        assert_eq!(commit(&table_dir, &paths, &[]).unwrap(), None);
+        std::fs::remove_file(&paths[1]).unwrap();
+        assert_eq!(commit(&table_dir, &paths[..1], &[]).unwrap(), Some(1));
+
+        // removed files are removed from the table before they are deleted
+        assert_eq!(commit(&table_dir, &[], &paths[..1]).unwrap(), Some(2));
+        let log_dir = table_dir.join(DELTA_LOG_DIRNAME);
+        assert!(read_log(&log_dir).unwrap().1.is_empty());
+        assert_eq!(commit(&table_dir, &paths[..1], &[]).unwrap(), Some(3));
+
+        let (version, active) = read_log(&log_dir).unwrap();
+        assert_eq!(version, 4);
+        assert_eq!(active.keys().collect::<Vec<_>>(), vec!["ethereum__blocks__0_to_0.parquet"]);
+        let first = std::fs::read_to_string(log_dir.join(format!("{:020}.json", 0))).unwrap();
+        assert!(first.lines().any(|line| line.contains("\"schemaString\"")));
+        std::fs::remove_dir_all(table_dir).unwrap();
+    }
+
+    #[test]
+    fn test_delta_type() {
+        let delta_type = |data_type: ArrowDataType| delta_type(&data_type).map(|t| t.to_string());
+        assert_eq!(delta_type(ArrowDataType::UInt32).unwrap(), "\"long\"");
+        assert_eq!(delta_type(ArrowDataType::LargeUtf8).unwrap(), "\"string\"");
+        assert_eq!(delta_type(ArrowDataType::Decimal(38, 4)).unwrap(), "\"decimal(38,4)\"");
+        let time = ArrowDataType::Time64(polars::export::arrow::datatypes::TimeUnit::Nanosecond);
+        assert!(delta_type(time).is_err());
+
+        // u64 columns are only committed if their values fit in a long
+        let df = df!("gas_used" => &[1u64, i64::MAX as u64]).unwrap();
+        assert!(check_dataframe(&df).is_ok());
+        let df = df!("gas_used" => &[1u64, i64::MAX as u64 + 1]).unwrap();
+        assert!(check_dataframe(&df).unwrap_err().to_string().contains("gas_used"));
+    }
+}
@@ -85,6 +85,42 @@ pub enum CollectError {
     /// Generic RPC Error
     #[error("RPC call error")]
     RPCError(String),
+
+    /// Block or transaction missing from rpc response, e.g. because node has not yet indexed it
+    #[error("Collect failed: {0}")]
+    NotFound(String),
+}
+
+/// kinds of CollectError, as returned by `CollectError::kind()`
+pub const ERROR_KINDS: [&str; 10] = [
+    "collect",
+    "parse",
+    "provider",
+    "task_failed",
+    "polars",
+    "invalid_number_of_topics",
+    "bad_schema",
+    "too_many_requests",
+    "rpc",
+    "not_found",
+];
+
+impl CollectError {
+    /// name of error variant, used for selecting which errors to retry
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CollectError::CollectError(_) => "collect",
+            CollectError::ParseError(_) => "parse",
+            CollectError::ProviderError(_) => "provider",
+            CollectError::TaskFailed(_) => "task_failed",
+            CollectError::PolarsError(_) => "polars",
+            CollectError::InvalidNumberOfTopics => "invalid_number_of_topics",
+            CollectError::BadSchemaError => "bad_schema",
+            CollectError::TooManyRequestsError => "too_many_requests",
+            CollectError::RPCError(_) => "rpc",
+            CollectError::NotFound(_) => "not_found",
+        }
+    }
 }
 
 /// Error related to parsing
@@ -1,6 +1,10 @@
 use crate::CollectError;
 use indicatif::ProgressBar;
-use std::{path::PathBuf, sync::Arc, time::SystemTime};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 /// configuration of execution environment
 #[derive(Clone)]
@@ -25,6 +29,10 @@ pub struct ExecutionEnv {
     pub t_end: Option<SystemTime>,
     /// report directory
     pub report_dir: Option<PathBuf>,
+    /// resume from checkpoint journal
+    pub resume: bool,
+    /// retry policy for errored partitions
+    pub retry: PartitionRetryPolicy,
 }
 
 impl ExecutionEnv {
@@ -45,7 +53,48 @@ impl Default for ExecutionEnv {
     }
 }
 
-fn new_bar(n: u64) -> Result<Arc<ProgressBar>, CollectError> {
+/// policy for retrying partitions that fail to collect
+#[derive(Clone, Debug)]
+pub struct PartitionRetryPolicy {
+    /// max number of attempts per partition, including the first attempt
+    pub max_attempts: u32,
+    /// backoff before the first retry, doubled for each subsequent retry
+    pub initial_backoff: Duration,
+    /// upper bound on backoff between retries
+    pub max_backoff: Duration,
+    /// kinds of CollectError that should be retried, see `CollectError::kind()`
+    pub retryable: Vec<String>,
+}
+
+impl Default for PartitionRetryPolicy {
+    fn default() -> Self {
+        PartitionRetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(1000),
+            max_backoff: Duration::from_secs(60),
+            retryable: DEFAULT_RETRYABLE_ERRORS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// kinds of CollectError that are retried by default
+pub const DEFAULT_RETRYABLE_ERRORS: [&str; 5] =
+    ["provider", "task_failed", "rpc", "too_many_requests", "not_found"];
+
+impl PartitionRetryPolicy {
+    /// whether an error on given attempt (starting at 1) should be retried
+    pub fn should_retry(&self, error: &CollectError, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retryable.iter().any(|kind| kind == error.kind())
+    }
+
+    /// backoff to wait after given attempt (starting at 1) before retrying
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        std::cmp::min(self.initial_backoff.saturating_mul(factor), self.max_backoff)
+    }
+}
+
+pub(crate) fn new_bar(n: u64) -> Result<Arc<ProgressBar>, CollectError> {
     let bar = Arc::new(ProgressBar::new(n));
     bar.set_style(
         indicatif::ProgressStyle::default_bar()
@@ -67,6 +116,8 @@ pub struct ExecutionEnvBuilder {
     t_start: SystemTime,
     t_end: Option<SystemTime>,
     report_dir: Option<PathBuf>,
+    resume: bool,
+    retry: PartitionRetryPolicy,
 }
 
 impl Default for ExecutionEnvBuilder {
@@ -82,6 +133,8 @@ impl Default for ExecutionEnvBuilder {
             t_start: SystemTime::now(),
             t_end: None,
             report_dir: None,
+            resume: false,
+            retry: PartitionRetryPolicy::default(),
         }
     }
 }
@@ -116,6 +169,18 @@ impl ExecutionEnvBuilder {
         self
     }
 
+    /// resume from checkpoint journal
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// retry policy for errored partitions
+    pub fn retry(mut self, retry: PartitionRetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     /// progress bar size
     pub fn bar(mut self, n: u64) -> Result<Self, CollectError> {
         self.bar = Some(new_bar(n)?);
@@ -147,6 +212,41 @@ impl ExecutionEnvBuilder {
             t_start: self.t_start,
             t_end: self.t_end,
             report_dir: self.report_dir,
+            resume: self.resume,
+            retry: self.retry,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_retry() {
+        let policy = PartitionRetryPolicy { max_attempts: 3, ..Default::default() };
+        let not_found = CollectError::NotFound("block not found".to_string());
+        assert!(policy.should_retry(&not_found, 1));
+        assert!(policy.should_retry(&not_found, 2));
+        assert!(!policy.should_retry(&not_found, 3));
+
+        // errors outside of retryable kinds are never retried
+        let collect = CollectError::CollectError("could not write file".to_string());
+        assert!(!policy.should_retry(&collect, 1));
+        let policy = PartitionRetryPolicy { retryable: vec!["collect".to_string()], ..policy };
+        assert!(policy.should_retry(&collect, 1));
+        assert!(!policy.should_retry(&not_found, 1));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = PartitionRetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let backoffs: Vec<u64> = (1..=5).map(|attempt| policy.backoff(attempt).as_secs()).collect();
+        assert_eq!(backoffs, vec![1, 2, 4, 5, 5]);
+        assert_eq!(policy.backoff(100), Duration::from_secs(5));
+    }
+}
@@ -1,5 +1,12 @@
-use crate::{CollectError, Datatype, MetaDatatype, ParseError, Partition, Query};
-use std::{collections::HashMap, path::PathBuf};
+use crate::{
+    manifest::MANIFEST_FILENAME, ChunkData, CollectError, Datatype, Dim, MetaDatatype, ParseError,
+    Partition, Query, CRYO_VERSION,
+};
+use polars::prelude::DataFrame;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
 
 /// Options for file output
 #[derive(Clone, Debug)]
@@ -12,16 +19,38 @@ pub struct FileOutput {
     pub suffix: Option<String>,
     /// subdirectories to use
     pub subdirs: Vec<SubDir>,
+    /// layout of output files
+    pub layout: OutputLayout,
+    /// Whether to maintain a manifest of written files
+    pub manifest: bool,
+    /// Whether to commit the files of each run to a delta table log in the dataset directory
+    pub delta: bool,
     /// Whether to overwrite existing files or skip them
     pub overwrite: bool,
+    /// Maximum number of rows per file, larger partitions are split into multiple files
+    pub max_rows_per_file: Option<usize>,
+    /// Maximum bytes per file, estimated from an encoded sample, larger partitions are split
+    pub max_file_size: Option<u64>,
     /// File format to used for output files
     pub format: FileFormat,
     /// Number of rows per parquet row group
     pub row_group_size: Option<usize>,
+    /// Number of row groups per parquet file, used if row_group_size is not given
+    pub n_row_groups: Option<usize>,
     /// Parquet statistics recording flag
     pub parquet_statistics: bool,
     /// Parquet compression options
     pub parquet_compression: polars::prelude::ParquetCompression,
+    /// Target size of parquet data pages in bytes
+    pub parquet_page_size: Option<usize>,
+    /// Columns to dictionary encode in parquet files
+    pub parquet_dictionary_columns: Vec<String>,
+    /// Columns to write parquet bloom filters for
+    pub parquet_bloom_filter_columns: Vec<String>,
+    /// Arrow IPC buffer compression, None for uncompressed buffers that can be memory-mapped
+    pub ipc_compression: Option<polars::prelude::IpcCompression>,
+    /// Key-value metadata embedded in output files, such as chain id
+    pub metadata: BTreeMap<String, String>,
 }
 
 /// Possible item to use as subdirectory
@@ -35,6 +64,47 @@ pub enum SubDir {
     Custom(String),
 }
 
+/// Layout of output files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// files named by network, datatype, and partition label, placed in subdirs
+    Flat,
+    /// hive-style `key=value` directories, block ranges are grouped into buckets of given size
+    Hive {
+        /// number of blocks per block_bucket directory
+        block_bucket_size: u64,
+    },
+}
+
+#[cfg(test)]
+impl FileOutput {
+    /// flat file output of format to output_dir with default options, for tests
+    pub(crate) fn new_test(output_dir: PathBuf, format: FileFormat) -> FileOutput {
+        FileOutput {
+            output_dir,
+            prefix: "ethereum".to_string(),
+            suffix: None,
+            subdirs: vec![],
+            layout: OutputLayout::Flat,
+            manifest: false,
+            delta: false,
+            overwrite: false,
+            max_rows_per_file: None,
+            max_file_size: None,
+            format,
+            row_group_size: None,
+            n_row_groups: None,
+            parquet_statistics: true,
+            parquet_compression: polars::prelude::ParquetCompression::Uncompressed,
+            parquet_page_size: None,
+            parquet_dictionary_columns: vec![],
+            parquet_bloom_filter_columns: vec![],
+            ipc_compression: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
 impl FileOutput {
     /// get output file paths
     pub fn get_paths(
@@ -64,6 +134,25 @@ impl FileOutput {
         partition: &Partition,
         datatype: Datatype,
     ) -> Result<PathBuf, CollectError> {
+        let (output_dir, filename) = match self.layout {
+            OutputLayout::Flat => self.get_flat_path(query, partition, datatype)?,
+            OutputLayout::Hive { block_bucket_size } => {
+                self.get_hive_path(query, partition, datatype, block_bucket_size)?
+            }
+        };
+
+        std::fs::create_dir_all(output_dir.clone())
+            .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+
+        Ok(output_dir.join(filename))
+    }
+
+    fn get_flat_path(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+    ) -> Result<(PathBuf, String), CollectError> {
         let filename = if let Some(suffix) = self.suffix.clone() {
             format!(
                 "{}__{}__{}__{}.{}",
@@ -82,7 +171,10 @@ impl FileOutput {
                 self.format.as_str(),
             )
         };
-        let filename = std::path::Path::new(&filename).to_path_buf();
+        Ok((self.get_flat_dir(datatype), filename))
+    }
+
+    fn get_flat_dir(&self, datatype: Datatype) -> PathBuf {
         let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
         for subdir in self.subdirs.iter() {
             let subdir_str: String = match subdir {
@@ -95,11 +187,93 @@ impl FileOutput {
             };
             output_dir = output_dir.join(std::path::Path::new(&subdir_str));
         }
+        output_dir
+    }
 
-        std::fs::create_dir_all(output_dir.clone())
-            .map_err(|_| ParseError::ParseError("could not create dir".to_string()))?;
+    /// e.g. network=ethereum/datatype=logs/block_bucket=00010000/part-00010000_to_00010999.parquet
+    fn get_hive_path(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+        block_bucket_size: u64,
+    ) -> Result<(PathBuf, String), CollectError> {
+        let mut output_dir = self.get_hive_dir(datatype);
 
-        Ok(output_dir.join(filename))
+        // block ranges go in buckets, other partition dimensions get their own directories
+        let pieces = partition.label_pieces(&query.partitioned_by)?;
+        let mut block_piece = None;
+        for (dim, piece) in query.partitioned_by.iter().zip(pieces.iter()) {
+            if dim == &Dim::BlockNumber {
+                let min_block = partition
+                    .block_numbers
+                    .as_ref()
+                    .and_then(|chunks| chunks.iter().filter_map(|c| c.min_value()).min())
+                    .ok_or(CollectError::CollectError("missing block numbers".to_string()))?;
+                let bucket = (min_block / block_bucket_size) * block_bucket_size;
+                output_dir = output_dir.join(format!("block_bucket={:0>8}", bucket));
+                block_piece = Some(piece.clone());
+            }
+        }
+        for (dim, piece) in query.partitioned_by.iter().zip(pieces.iter()) {
+            if dim != &Dim::BlockNumber {
+                output_dir = output_dir.join(format!("{}={}", dim, piece));
+            }
+        }
+
+        let label = block_piece.unwrap_or_else(|| pieces.join("__"));
+        let filename = format!("part-{}.{}", label, self.format.as_str());
+        Ok((output_dir, filename))
+    }
+
+    fn get_hive_dir(&self, datatype: Datatype) -> PathBuf {
+        let mut output_dir = std::path::Path::new(&self.output_dir).to_path_buf();
+        output_dir = output_dir.join(format!("network={}", self.prefix));
+        output_dir = output_dir.join(format!("datatype={}", datatype.name()));
+        if let Some(suffix) = &self.suffix {
+            output_dir = output_dir.join(format!("label={}", suffix));
+        }
+        output_dir
+    }
+
+    /// get directory that holds the files of datatype, excluding hive partition directories
+    pub fn get_dataset_dir(&self, datatype: Datatype) -> PathBuf {
+        match self.layout {
+            OutputLayout::Flat => self.get_flat_dir(datatype),
+            OutputLayout::Hive { .. } => self.get_hive_dir(datatype),
+        }
+    }
+
+    /// get path of manifest for the dataset directory of datatype
+    pub fn get_manifest_path(&self, datatype: Datatype) -> PathBuf {
+        self.get_dataset_dir(datatype).join(MANIFEST_FILENAME)
+    }
+
+    /// key-value metadata embedded in output file of partition, including cryo version, block
+    /// range, and schema
+    pub fn file_metadata(&self, partition: &Partition, df: &DataFrame) -> BTreeMap<String, String> {
+        let mut metadata = self.metadata.clone();
+        metadata.insert("cryo_version".to_string(), CRYO_VERSION.to_string());
+        if let Some(block_chunks) = &partition.block_numbers {
+            if let Some(min_block) = block_chunks.min_value() {
+                metadata.insert("min_block".to_string(), min_block.to_string());
+            }
+            if let Some(max_block) = block_chunks.max_value() {
+                metadata.insert("max_block".to_string(), max_block.to_string());
+            }
+        }
+        let schema: Vec<serde_json::Value> = df
+            .schema()
+            .iter()
+            .map(|(name, dtype)| serde_json::json!({"name": name.as_str(), "type": dtype.to_string()}))
+            .collect();
+        metadata.insert("schema".to_string(), serde_json::Value::from(schema).to_string());
+        metadata
+    }
+
+    /// whether partitions may be split into multiple files
+    pub fn splits_files(&self) -> bool {
+        self.max_rows_per_file.is_some() || self.max_file_size.is_some()
     }
 }
 
@@ -112,6 +286,10 @@ pub enum FileFormat {
     Csv,
     /// Json file format
     Json,
+    /// Newline-delimited json file format, one object per row
+    Ndjson,
+    /// Arrow IPC (feather v2) file format
+    Arrow,
 }
 
 impl FileFormat {
@@ -121,6 +299,8 @@ impl FileFormat {
             FileFormat::Parquet => "parquet",
             FileFormat::Csv => "csv",
             FileFormat::Json => "json",
+            FileFormat::Ndjson => "ndjson",
+            FileFormat::Arrow => "arrow",
         }
     }
 }
@@ -143,3 +323,71 @@ impl ColumnEncoding {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{audit::parse_output_path, BlockChunk};
+
+    fn hive_path(output: &FileOutput, query: &Query, partition: &Partition) -> PathBuf {
+        let (dir, filename) =
+            output.get_hive_path(query, partition, Datatype::Logs, 10_000).unwrap();
+        dir.join(filename)
+    }
+
+    #[test]
+    fn test_hive_path_round_trip() {
+        let output = FileOutput {
+            layout: OutputLayout::Hive { block_bucket_size: 10_000 },
+            suffix: Some("v2".to_string()),
+            ..FileOutput::new_test(PathBuf::from("/data"), FileFormat::Parquet)
+        };
+        let mut query = Query {
+            datatypes: vec![],
+            schemas: HashMap::new(),
+            time_dimension: crate::TimeDimension::Blocks,
+            partitions: vec![],
+            partitioned_by: vec![Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: crate::QueryLabels { align: false, reorg_buffer: 0 },
+        };
+
+        // buckets are chosen by the first block of a partition
+        for (start, end, bucket) in
+            [(0, 9_999, 0), (9_999, 10_998, 0), (10_000, 10_999, 10_000), (19_000, 20_999, 10_000)]
+        {
+            let partition = Partition {
+                block_numbers: Some(vec![BlockChunk::Range(start, end)]),
+                ..Default::default()
+            };
+            let path = hive_path(&output, &query, &partition);
+            let expected = format!(
+                "/data/network=ethereum/datatype=logs/label=v2/block_bucket={:0>8}/part-{:0>8}_to_{:0>8}.parquet",
+                bucket, start, end
+            );
+            assert_eq!(path, PathBuf::from(expected));
+
+            let file = parse_output_path(&path).unwrap();
+            assert_eq!((file.network.as_str(), file.datatype.as_str()), ("ethereum", "logs"));
+            assert_eq!(file.label.as_deref(), Some("label=v2"));
+            assert_eq!((file.start_block, file.end_block), (start, end));
+            assert_eq!(file.format, "parquet");
+        }
+
+        // other partition dimensions get their own directories below the bucket
+        query.partitioned_by = vec![Dim::BlockNumber, Dim::Contract];
+        let partition = Partition {
+            label: Some(vec![None, Some("0xab".to_string())]),
+            block_numbers: Some(vec![BlockChunk::Range(10_000, 10_999)]),
+            ..Default::default()
+        };
+        let path = hive_path(&output, &query, &partition);
+        let file = parse_output_path(&path).unwrap();
+        assert!(
+            path.ends_with("block_bucket=00010000/contract=0xab/part-00010000_to_00010999.parquet")
+        );
+        assert_eq!(file.label.as_deref(), Some("label=v2__contract=0xab"));
+        assert_eq!((file.start_block, file.end_block), (10_000, 10_999));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{dataframes, err, CollectError, FileOutput};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const JOURNAL_FILENAME: &str = "journal.jsonl";
+
+/// status of a partition output, as recorded in the checkpoint journal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PartitionStatus {
+    /// partition is queued for collection
+    Pending,
+    /// partition is currently being collected
+    InFlight,
+    /// partition was collected and written to disk
+    Completed,
+    /// partition failed with the given error message
+    Errored(String),
+}
+
+impl PartitionStatus {
+    /// convert PartitionStatus to str
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartitionStatus::Pending => "pending",
+            PartitionStatus::InFlight => "in_flight",
+            PartitionStatus::Completed => "completed",
+            PartitionStatus::Errored(_) => "errored",
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct JournalEntry {
+    path: PathBuf,
+    status: String,
+    error: Option<String>,
+}
+
+impl JournalEntry {
+    fn new(path: &Path, status: &PartitionStatus) -> JournalEntry {
+        let error = match status {
+            PartitionStatus::Errored(e) => Some(e.clone()),
+            _ => None,
+        };
+        JournalEntry { path: path.to_path_buf(), status: status.as_str().to_string(), error }
+    }
+
+    fn status(&self) -> Result<PartitionStatus, CollectError> {
+        match (self.status.as_str(), &self.error) {
+            ("pending", _) => Ok(PartitionStatus::Pending),
+            ("in_flight", _) => Ok(PartitionStatus::InFlight),
+            ("completed", _) => Ok(PartitionStatus::Completed),
+            ("errored", error) => Ok(PartitionStatus::Errored(error.clone().unwrap_or_default())),
+            _ => Err(err("invalid status in journal")),
+        }
+    }
+}
+
+/// append-only checkpoint journal recording the status of each output file
+///
+/// each line of the journal is a json entry, later entries take precedence over earlier ones
+#[derive(Debug)]
+pub struct Journal {
+    /// path of journal file
+    pub path: PathBuf,
+    /// statuses that were recorded before this journal was opened
+    pub previous: HashMap<PathBuf, PartitionStatus>,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// get path of journal file for output
+    pub fn get_journal_path(sink: &FileOutput) -> PathBuf {
+        Path::new(&sink.output_dir).join(".cryo").join(JOURNAL_FILENAME)
+    }
+
+    /// open journal, loading and compacting any previously recorded statuses
+    pub fn open(path: PathBuf) -> Result<Journal, CollectError> {
+        let previous = if path.exists() { load_statuses(&path)? } else { HashMap::new() };
+
+        // compact journal so that it holds one entry per path
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|_| err("could not create journal dir"))?;
+        }
+        let tmp_path = path.with_extension("_tmp");
+        {
+            let mut file =
+                File::create(&tmp_path).map_err(|_| err("could not create journal file"))?;
+            for (entry_path, status) in previous.iter() {
+                write_entry(&mut file, entry_path, status)?;
+            }
+            file.sync_all().map_err(|_| err("could not sync journal file"))?;
+        }
+        std::fs::rename(&tmp_path, &path).map_err(|_| err("could not compact journal file"))?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|_| err("could not open journal file"))?;
+        Ok(Journal { path, previous, file: Mutex::new(file) })
+    }
+
+    /// status of path from before this journal was opened
+    pub fn previous_status(&self, path: &Path) -> Option<&PartitionStatus> {
+        self.previous.get(path)
+    }
+
+    /// whether every path was previously recorded as completed
+    pub fn previously_completed<'a, I: IntoIterator<Item = &'a PathBuf>>(&self, paths: I) -> bool {
+        paths
+            .into_iter()
+            .all(|path| self.previous_status(path) == Some(&PartitionStatus::Completed))
+    }
+
+    /// record status of paths, flushing to disk before returning
+    pub fn record<'a, I: IntoIterator<Item = &'a PathBuf>>(
+        &self,
+        paths: I,
+        status: &PartitionStatus,
+    ) -> Result<(), CollectError> {
+        let mut file = self.file.lock().map_err(|_| err("could not lock journal file"))?;
+        for path in paths.into_iter() {
+            write_entry(&mut *file, path, status)?;
+        }
+        file.sync_data().map_err(|_| err("could not sync journal file"))
+    }
+
+    /// delete `_tmp` files left behind by paths that never completed
+    pub fn clean_orphaned_tmp_files(&self) -> Result<Vec<PathBuf>, CollectError> {
+        let mut removed = Vec::new();
+        for (path, status) in self.previous.iter() {
+            if status == &PartitionStatus::Completed {
+                continue
+            }
+            let tmp_path = dataframes::get_tmp_path(path);
+            if tmp_path.exists() {
+                std::fs::remove_file(&tmp_path)
+                    .map_err(|_| err("could not remove orphaned tmp file"))?;
+                removed.push(tmp_path);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+fn load_statuses(path: &Path) -> Result<HashMap<PathBuf, PartitionStatus>, CollectError> {
+    let file = File::open(path).map_err(|_| err("could not open journal file"))?;
+    let mut statuses = HashMap::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|_| err("could not read journal file"))?;
+        if line.trim().is_empty() {
+            continue
+        }
+        // a crash can leave a truncated final line, which is safe to ignore
+        let entry: JournalEntry = match serde_json::from_str(&line) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let status = entry.status()?;
+        statuses.insert(entry.path, status);
+    }
+    Ok(statuses)
+}
+
+fn write_entry<W: Write>(
+    writer: &mut W,
+    path: &Path,
+    status: &PartitionStatus,
+) -> Result<(), CollectError> {
+    let entry = JournalEntry::new(path, status);
+    let mut line =
+        serde_json::to_string(&entry).map_err(|_| err("could not serialize journal entry"))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).map_err(|_| err("could not write journal entry"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cryo_journal_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let statuses = vec![
+            PartitionStatus::Pending,
+            PartitionStatus::InFlight,
+            PartitionStatus::Completed,
+            PartitionStatus::Errored("block not found".to_string()),
+        ];
+        for status in statuses.into_iter() {
+            let entry = JournalEntry::new(Path::new("blocks.parquet"), &status);
+            let line = serde_json::to_string(&entry).unwrap();
+            let entry: JournalEntry = serde_json::from_str(&line).unwrap();
+            assert_eq!(entry.status().unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn test_load_statuses_truncated() {
+        let dir = temp_dir("truncated");
+        let path = dir.join(JOURNAL_FILENAME);
+        let mut file = File::create(&path).unwrap();
+        write_entry(&mut file, Path::new("a.parquet"), &PartitionStatus::InFlight).unwrap();
+        write_entry(&mut file, Path::new("a.parquet"), &PartitionStatus::Completed).unwrap();
+        write_entry(&mut file, Path::new("b.parquet"), &PartitionStatus::Pending).unwrap();
+        file.write_all(b"{\"path\":\"b.parquet\",\"sta").unwrap();
+        drop(file);
+
+        // later entries take precedence and the truncated final line is ignored
+        let statuses = load_statuses(&path).unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[Path::new("a.parquet")], PartitionStatus::Completed);
+        assert_eq!(statuses[Path::new("b.parquet")], PartitionStatus::Pending);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_orphaned_tmp_files() {
+        let dir = temp_dir("orphaned");
+        let completed = dir.join("completed.parquet");
+        let in_flight = dir.join("in_flight.parquet");
+        let journal = Journal::open(dir.join(JOURNAL_FILENAME)).unwrap();
+        journal.record([&completed], &PartitionStatus::Completed).unwrap();
+        journal.record([&in_flight], &PartitionStatus::InFlight).unwrap();
+        for path in [&completed, &in_flight] {
+            File::create(dataframes::get_tmp_path(path)).unwrap();
+        }
+
+        // only tmp files of paths that never completed are removed
+        let journal = Journal::open(dir.join(JOURNAL_FILENAME)).unwrap();
+        let removed = journal.clean_orphaned_tmp_files().unwrap();
+        assert_eq!(removed, vec![dataframes::get_tmp_path(&in_flight)]);
+        assert!(dataframes::get_tmp_path(&completed).exists());
+        assert!(!dataframes::get_tmp_path(&in_flight).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{err, ChunkData, CollectError, Datatype, Partition, CRYO_VERSION};
+use polars::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// name of manifest file within each dataset directory
+pub const MANIFEST_FILENAME: &str = "_cryo_manifest.jsonl";
+
+/// number of records appended to a manifest between compactions
+const COMPACTION_INTERVAL: u64 = 10_000;
+
+lazy_static::lazy_static! {
+    /// serializes appends and compactions of manifests across concurrent chunks, counting the
+    /// records appended to each manifest since its last compaction
+    static ref MANIFEST_APPENDS: Mutex<HashMap<PathBuf, u64>> = Mutex::new(HashMap::new());
+}
+
+/// description of a single output file
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+    /// name of datatype
+    pub datatype: String,
+    /// first block of chunk
+    pub min_block: Option<u64>,
+    /// last block of chunk
+    pub max_block: Option<u64>,
+    /// number of rows in file
+    pub n_rows: u64,
+    /// size of file in bytes
+    pub n_bytes: u64,
+    /// sha256 of column names and dtypes
+    pub schema_fingerprint: String,
+    /// version of cryo that wrote file
+    pub cryo_version: String,
+    /// sha256 of file contents
+    pub checksum: String,
+}
+
+/// manifest listing every file written to a dataset directory
+///
+/// manifests are stored as append-only logs with a json record per line, so that recording a file
+/// does not rewrite the whole manifest, and are compacted every `COMPACTION_INTERVAL` records
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// entries keyed by file path relative to the manifest directory
+    pub files: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+/// line of manifest log, recording a written file or, without entry, the removal of a file
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ManifestRecord {
+    path: PathBuf,
+    entry: Option<ManifestEntry>,
+}
+
+impl Manifest {
+    /// read manifest from file, returning an empty manifest if file does not exist
+    pub fn read(manifest_path: &Path) -> Result<Manifest, CollectError> {
+        if !manifest_path.exists() {
+            return Ok(Manifest::default())
+        }
+        let file = File::open(manifest_path).map_err(|_| err("could not open manifest"))?;
+        let mut manifest = Manifest::default();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|_| err("could not read manifest"))?;
+            if line.trim().is_empty() {
+                continue
+            }
+            // a crash can leave a truncated final line, which is safe to ignore
+            let record: ManifestRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            match record.entry {
+                Some(entry) => manifest.files.insert(record.path, entry),
+                None => manifest.files.remove(&record.path),
+            };
+        }
+        Ok(manifest)
+    }
+
+    /// write manifest to file with a record per file, replacing previous manifest atomically
+    pub fn write(&self, manifest_path: &Path) -> Result<(), CollectError> {
+        let mut contents = String::new();
+        for (path, entry) in self.files.iter() {
+            let record = ManifestRecord { path: path.clone(), entry: Some(entry.clone()) };
+            contents.push_str(&serialize_record(&record)?);
+        }
+        let tmp_path = manifest_path.with_extension("_tmp");
+        std::fs::write(&tmp_path, contents).map_err(|_| err("could not write manifest"))?;
+        std::fs::rename(&tmp_path, manifest_path).map_err(|_| err("could not write manifest"))
+    }
+
+    /// entry of output file, if recorded
+    pub fn get_entry(&self, manifest_path: &Path, file_path: &Path) -> Option<&ManifestEntry> {
+        self.files.get(&relative_path(manifest_path, file_path))
+    }
+
+    /// whether output file exists and matches its recorded size
+    ///
+    /// only the size is compared, so that checking every file of a large dataset stays cheap,
+    /// which detects truncated and partially written files but not same-size corruption, use
+    /// `matches_checksum` to compare file contents. files written before manifests were
+    /// introduced are assumed to be intact
+    pub fn is_intact(&self, manifest_path: &Path, file_path: &Path) -> bool {
+        match (std::fs::metadata(file_path), self.get_entry(manifest_path, file_path)) {
+            (Ok(metadata), Some(entry)) => metadata.len() == entry.n_bytes,
+            (Ok(_), None) => true,
+            (Err(_), _) => false,
+        }
+    }
+
+    /// whether output file exists and its contents match its recorded checksum
+    pub fn matches_checksum(&self, manifest_path: &Path, file_path: &Path) -> bool {
+        match self.get_entry(manifest_path, file_path) {
+            Some(entry) => {
+                file_checksum(file_path).map_or(false, |checksum| checksum == entry.checksum)
+            }
+            None => false,
+        }
+    }
+}
+
+/// record a newly written output file in its manifest
+pub(crate) fn record_file(
+    manifest_path: &Path,
+    file_path: &Path,
+    datatype: Datatype,
+    partition: &Partition,
+    df: &DataFrame,
+) -> Result<(), CollectError> {
+    let block_chunks = partition.block_numbers.as_deref().unwrap_or_default();
+    let entry = ManifestEntry {
+        datatype: datatype.name(),
+        min_block: block_chunks.iter().filter_map(|chunk| chunk.min_value()).min(),
+        max_block: block_chunks.iter().filter_map(|chunk| chunk.max_value()).max(),
+        n_rows: df.height() as u64,
+        n_bytes: std::fs::metadata(file_path).map_err(|_| err("could not stat file"))?.len(),
+        schema_fingerprint: schema_fingerprint(df),
+        cryo_version: CRYO_VERSION.to_string(),
+        checksum: file_checksum(file_path)?,
+    };
+
+    let path = relative_path(manifest_path, file_path);
+    append_records(manifest_path, vec![ManifestRecord { path, entry: Some(entry) }])
+}
+
+/// remove files from their manifest, e.g. after they are merged into a larger file
+pub(crate) fn remove_files(
+    manifest_path: &Path,
+    file_paths: &[PathBuf],
+) -> Result<(), CollectError> {
+    let records = file_paths
+        .iter()
+        .map(|file_path| ManifestRecord {
+            path: relative_path(manifest_path, file_path),
+            entry: None,
+        })
+        .collect();
+    append_records(manifest_path, records)
+}
+
+/// append records to manifest log, compacting the log once enough records have been appended
+fn append_records(manifest_path: &Path, records: Vec<ManifestRecord>) -> Result<(), CollectError> {
+    let mut contents = String::new();
+    for record in records.iter() {
+        contents.push_str(&serialize_record(record)?);
+    }
+
+    let mut appends = MANIFEST_APPENDS.lock().map_err(|_| err("could not lock manifest"))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(manifest_path)
+        .map_err(|_| err("could not open manifest"))?;
+    file.write_all(contents.as_bytes()).map_err(|_| err("could not write manifest"))?;
+
+    let n_appends = appends.entry(manifest_path.to_path_buf()).or_default();
+    *n_appends += records.len() as u64;
+    if *n_appends >= COMPACTION_INTERVAL {
+        *n_appends = 0;
+        Manifest::read(manifest_path)?.write(manifest_path)?;
+    }
+    Ok(())
+}
+
+fn serialize_record(record: &ManifestRecord) -> Result<String, CollectError> {
+    let mut line =
+        serde_json::to_string(record).map_err(|_| err("could not serialize manifest record"))?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn relative_path(manifest_path: &Path, file_path: &Path) -> PathBuf {
+    match manifest_path.parent() {
+        Some(dir) => file_path.strip_prefix(dir).unwrap_or(file_path).to_path_buf(),
+        None => file_path.to_path_buf(),
+    }
+}
+
+fn schema_fingerprint(df: &DataFrame) -> String {
+    let mut hasher = Sha256::new();
+    for (name, dtype) in df.schema().iter() {
+        hasher.update(format!("{}:{};", name, dtype).as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn file_checksum(path: &Path) -> Result<String, CollectError> {
+    let mut file = std::fs::File::open(path).map_err(|_| err("could not open file"))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|_| err("could not read file"))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockChunk;
+
+    #[test]
+    fn test_manifest_log() {
+        let dir = std::env::temp_dir().join(format!("cryo_manifest_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest_path = dir.join(MANIFEST_FILENAME);
+        let df = df!("block_number" => [1u64, 2, 3]).unwrap();
+        let partition =
+            Partition { block_numbers: Some(vec![BlockChunk::Range(1, 3)]), ..Default::default() };
+        let file_paths: Vec<PathBuf> = (0..3).map(|i| dir.join(format!("{}.parquet", i))).collect();
+        for file_path in file_paths.iter() {
+            std::fs::write(file_path, b"contents").unwrap();
+            record_file(&manifest_path, file_path, Datatype::Blocks, &partition, &df).unwrap();
+        }
+
+        // records are appended, with removals overriding earlier records
+        remove_files(&manifest_path, &file_paths[..1]).unwrap();
+        let contents = std::fs::read_to_string(&manifest_path).unwrap();
+        assert_eq!(contents.lines().count(), 4);
+        let manifest = Manifest::read(&manifest_path).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        assert!(manifest.get_entry(&manifest_path, &file_paths[0]).is_none());
+        let entry = manifest.get_entry(&manifest_path, &file_paths[1]).unwrap();
+        assert_eq!((entry.min_block, entry.max_block, entry.n_rows), (Some(1), Some(3), 3));
+
+        // a truncated final record is ignored
+        let truncated = contents.clone() + "{\"path\":\"3.parq";
+        std::fs::write(&manifest_path, truncated).unwrap();
+        assert_eq!(Manifest::read(&manifest_path).unwrap().files, manifest.files);
+
+        // compaction keeps a single record per file
+        manifest.write(&manifest_path).unwrap();
+        let contents = std::fs::read_to_string(&manifest_path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert_eq!(Manifest::read(&manifest_path).unwrap().files, manifest.files);
+
+        // size checks detect truncation, checksums also detect same-size changes
+        assert!(manifest.is_intact(&manifest_path, &file_paths[1]));
+        assert!(manifest.matches_checksum(&manifest_path, &file_paths[1]));
+        std::fs::write(&file_paths[1], b"CONTENTS").unwrap();
+        assert!(manifest.is_intact(&manifest_path, &file_paths[1]));
+        assert!(!manifest.matches_checksum(&manifest_path, &file_paths[1]));
+        std::fs::write(&file_paths[1], b"cont").unwrap();
+        assert!(!manifest.is_intact(&manifest_path, &file_paths[1]));
+        std::fs::remove_file(&file_paths[2]).unwrap();
+        assert!(!manifest.is_intact(&manifest_path, &file_paths[2]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
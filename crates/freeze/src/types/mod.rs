@@ -1,3 +1,14 @@
+/// adaptive concurrency and rate limits
+pub mod adaptive;
+pub use adaptive::{AdaptiveLimiter, AdaptivePermit, AdaptiveRetryPolicy};
+/// batching of json-rpc requests
+pub mod batch;
+pub use batch::BatchHttp;
+/// on-disk cache of rpc responses
+pub mod cache;
+pub use cache::RequestCache;
+/// capabilities of rpc nodes
+pub mod capabilities;
 /// type specifications for cryo_freeze crate
 
 /// type specifications for chunk types
@@ -6,8 +17,12 @@ pub mod chunks;
 pub mod conversions;
 /// type specifications for collectable types
 pub mod datatypes;
+pub use capabilities::{Capabilities, Capability, Support};
+/// pools of rpc endpoints
+pub mod pool;
 /// type specifications for data sources
 pub mod sources;
+pub use pool::{EndpointHealth, PoolEndpoint, RpcPool};
 
 /// column data specification
 pub mod columns;
@@ -27,6 +42,40 @@ pub mod collection;
 /// execution environment
 pub mod execution;
 
+/// checkpoint journal
+pub mod journal;
+pub use journal::{Journal, PartitionStatus};
+
+/// dataset manifests
+pub mod manifest;
+pub use manifest::{Manifest, ManifestEntry};
+
+/// delta table commits
+pub mod delta;
+
+/// sqlite database output
+pub mod sqlite;
+pub use sqlite::SqliteSink;
+
+/// postgres database output
+pub mod postgres;
+pub use postgres::PostgresSink;
+
+/// provenance metadata of output files
+pub mod provenance;
+pub use provenance::{get_provenance, read_provenance};
+
+/// object store output
+pub mod store;
+pub use store::ObjectStoreSink;
+
+/// splitting of partitions into multiple files
+pub mod split;
+
+/// output sinks
+pub mod sink;
+pub use sink::Sink;
+
 /// report generation
 pub mod reports;
 pub use reports::CRYO_VERSION;
@@ -56,7 +105,7 @@ pub use chunks::{
 pub use conversions::{bytes_to_u32, ToVecHex, ToVecU8};
 pub use dataframes::*;
 pub use datatypes::*;
-pub use files::{ColumnEncoding, FileFormat, FileOutput, SubDir};
+pub use files::{ColumnEncoding, FileFormat, FileOutput, OutputLayout, SubDir};
 pub use queries::{Query, QueryLabels, TimeDimension};
 pub use schemas::{ColumnType, SchemaFunctions, Schemas, Table, U256Type};
 pub use sources::{Fetcher, RateLimiter, Source, SourceLabels};
@@ -64,10 +113,12 @@ pub use sources::{Fetcher, RateLimiter, Source, SourceLabels};
 // pub use summaries::{FreezeChunkSummary, FreezeSummary};
 pub use summaries::{print_all_datasets, print_dataset_info, FreezeSummary};
 
-pub use errors::{err, ChunkError, CollectError, FileError, FreezeError, ParseError, R};
+pub use errors::{
+    err, ChunkError, CollectError, FileError, FreezeError, ParseError, ERROR_KINDS, R,
+};
 
 pub use collection::*;
-pub use execution::{ExecutionEnv, ExecutionEnvBuilder};
+pub use execution::{ExecutionEnv, ExecutionEnvBuilder, PartitionRetryPolicy};
 
 pub use signatures::*;
 
@@ -0,0 +1,342 @@
+use crate::{adaptive::is_rate_limit_response, err, BatchHttp, CollectError};
+use ethers::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Debug,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+
+/// weight of newest latency sample in moving average of endpoint latency
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// cooldown of an endpoint after its first consecutive failure, doubled for each further failure
+const INITIAL_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// maximum cooldown of a failing endpoint
+const MAX_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// maximum retries of a request at one endpoint, further attempts fail over to other endpoints
+const MAX_ENDPOINT_RETRIES: u32 = 1;
+
+/// json-rpc client that dispatches requests over a pool of http endpoints
+///
+/// endpoints are chosen by smooth weighted round-robin, weighted by the inverse of their recent
+/// latency. requests that fail with transport errors or rate limit errors fail over to the next
+/// endpoint, and the failing endpoint is skipped for a cooldown that grows with its consecutive
+/// failures. once every endpoint has failed, requests are retried across the endpoints again
+/// after a growing backoff, until `max_retries` retries are used. other error responses of the
+/// node itself, e.g. reverted calls, are returned without failover
+#[derive(Debug)]
+pub struct RpcPool {
+    endpoints: Vec<PoolEndpoint>,
+    health: Mutex<Vec<EndpointHealth>>,
+    max_retries: u32,
+    initial_backoff: Duration,
+}
+
+/// endpoint of rpc pool, with its own concurrency and rate limits
+#[derive(Debug)]
+pub struct PoolEndpoint {
+    /// url of endpoint
+    pub url: String,
+    client: RetryClient<BatchHttp>,
+    semaphore: Option<Semaphore>,
+}
+
+/// observed health of endpoint
+#[derive(Clone, Debug, Default)]
+pub struct EndpointHealth {
+    /// moving average of request latency in milliseconds
+    pub latency: Option<f64>,
+    /// number of requests sent to endpoint
+    pub n_requests: u64,
+    /// number of requests that failed with transport errors or rate limit errors
+    pub n_failures: u64,
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+    current_weight: f64,
+}
+
+impl PoolEndpoint {
+    /// create endpoint for http url, limits apply to this endpoint only
+    ///
+    /// retries are capped at `MAX_ENDPOINT_RETRIES`, so that failing endpoints are failed over
+    /// quickly, the pool spends the remaining retries across endpoints
+    pub fn new(
+        url: &str,
+        max_retries: u32,
+        initial_backoff: u64,
+        max_concurrent_requests: Option<u64>,
+        max_requests_per_second: Option<u32>,
+        max_batch_size: usize,
+        retry_policy: Box<dyn RetryPolicy<HttpClientError>>,
+    ) -> Result<PoolEndpoint, CollectError> {
+        let client = RetryClient::new(
+            BatchHttp::new(url, max_batch_size, max_requests_per_second)?,
+            retry_policy,
+            max_retries.min(MAX_ENDPOINT_RETRIES),
+            initial_backoff,
+        );
+        let semaphore = max_concurrent_requests.map(|n| Semaphore::new(n as usize));
+        Ok(PoolEndpoint { url: url.to_string(), client, semaphore })
+    }
+}
+
+impl RpcPool {
+    /// create pool of endpoints, retrying failed requests up to `max_retries` times in total
+    /// across endpoints, with backoff starting at `initial_backoff` milliseconds between rounds
+    pub fn new(
+        endpoints: Vec<PoolEndpoint>,
+        max_retries: u32,
+        initial_backoff: u64,
+    ) -> Result<RpcPool, CollectError> {
+        if endpoints.is_empty() {
+            return Err(err("rpc pool needs at least one endpoint"))
+        }
+        let health = Mutex::new(vec![EndpointHealth::default(); endpoints.len()]);
+        let initial_backoff = Duration::from_millis(initial_backoff);
+        Ok(RpcPool { endpoints, health, max_retries, initial_backoff })
+    }
+
+    /// urls of endpoints
+    pub fn urls(&self) -> Vec<String> {
+        self.endpoints.iter().map(|endpoint| endpoint.url.clone()).collect()
+    }
+
+    /// observed health of each endpoint
+    pub fn health(&self) -> Vec<(String, EndpointHealth)> {
+        let health = self.health.lock().map(|health| health.clone()).unwrap_or_default();
+        self.urls().into_iter().zip(health).collect()
+    }
+
+    /// choose next endpoint that is not excluded, preferring endpoints outside of cooldown
+    fn select(&self, excluded: &[usize]) -> Option<usize> {
+        let mut health = self.health.lock().ok()?;
+        let now = Instant::now();
+        let mut candidates: Vec<usize> = (0..health.len())
+            .filter(|index| !excluded.contains(index))
+            .filter(|index| health[*index].cooldown_until.map_or(true, |until| until <= now))
+            .collect();
+        if candidates.is_empty() {
+            // every remaining endpoint is cooling down, use the one that recovers first
+            let index = (0..health.len())
+                .filter(|index| !excluded.contains(index))
+                .min_by_key(|index| health[*index].cooldown_until)?;
+            candidates.push(index);
+        }
+
+        // smooth weighted round-robin, endpoints without latency samples get the top weight
+        let fastest = candidates
+            .iter()
+            .filter_map(|index| health[*index].latency)
+            .fold(f64::INFINITY, f64::min);
+        let mut total = 0.0;
+        let mut best: Option<usize> = None;
+        for index in candidates.into_iter() {
+            let weight = match (health[index].latency, fastest.is_finite()) {
+                (Some(latency), true) => (fastest + 1.0) / (latency + 1.0),
+                _ => 1.0,
+            };
+            health[index].current_weight += weight;
+            total += weight;
+            if best.map_or(true, |best| health[index].current_weight > health[best].current_weight)
+            {
+                best = Some(index);
+            }
+        }
+        let best = best?;
+        health[best].current_weight -= total;
+        health[best].n_requests += 1;
+        Some(best)
+    }
+
+    fn record_success(&self, index: usize, latency: Duration) {
+        if let Ok(mut health) = self.health.lock() {
+            let health = &mut health[index];
+            let sample = latency.as_secs_f64() * 1000.0;
+            health.latency = Some(match health.latency {
+                Some(average) => average + LATENCY_SMOOTHING * (sample - average),
+                None => sample,
+            });
+            health.consecutive_failures = 0;
+            health.cooldown_until = None;
+        }
+    }
+
+    /// backoff before a round of retries, after every endpoint failed in the previous round
+    fn backoff(&self, round: u32) -> Duration {
+        (self.initial_backoff * 2u32.pow(round.saturating_sub(1).min(16))).min(MAX_COOLDOWN)
+    }
+
+    fn record_failure(&self, index: usize) {
+        if let Ok(mut health) = self.health.lock() {
+            let health = &mut health[index];
+            health.n_failures += 1;
+            health.consecutive_failures += 1;
+            let exponent = health.consecutive_failures.saturating_sub(1).min(16);
+            let cooldown = (INITIAL_COOLDOWN * 2u32.pow(exponent)).min(MAX_COOLDOWN);
+            health.cooldown_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
+
+/// whether error is a failure of the endpoint, i.e. a transport error or a rate limit error,
+/// rather than an error response to the request itself
+fn is_endpoint_failure(error: &RetryClientError) -> bool {
+    match error.as_error_response() {
+        Some(response) => is_rate_limit_response(response),
+        None => error.as_serde_error().is_none(),
+    }
+}
+
+/// params of a request, zero-sized params must be passed on as is to be omitted from requests
+enum PoolParams {
+    Value(serde_json::Value),
+    Zst,
+}
+
+#[async_trait::async_trait]
+impl JsonRpcClient for RpcPool {
+    type Error = RetryClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = if std::mem::size_of::<T>() == 0 {
+            PoolParams::Zst
+        } else {
+            PoolParams::Value(serde_json::to_value(params).map_err(RetryClientError::SerdeJson)?)
+        };
+
+        // endpoints that failed in the current round, and failed attempts of all rounds
+        let mut failed = Vec::new();
+        let (mut n_failures, mut round) = (0, 0);
+        loop {
+            let index = self.select(&failed).ok_or(RetryClientError::TimeoutError)?;
+            let endpoint = &self.endpoints[index];
+            let _permit = match &endpoint.semaphore {
+                Some(semaphore) => Some(semaphore.acquire().await),
+                None => None,
+            };
+
+            let start = Instant::now();
+            let client = &endpoint.client;
+            let result = match &params {
+                PoolParams::Value(params) => JsonRpcClient::request(client, method, params).await,
+                PoolParams::Zst => JsonRpcClient::request(client, method, ()).await,
+            };
+            match result {
+                Ok(response) => {
+                    self.record_success(index, start.elapsed());
+                    return Ok(response)
+                }
+                Err(e) if !is_endpoint_failure(&e) => {
+                    self.record_success(index, start.elapsed());
+                    return Err(e)
+                }
+                Err(e) => {
+                    self.record_failure(index);
+                    failed.push(index);
+                    n_failures += 1;
+                    if failed.len() == self.endpoints.len() {
+                        if n_failures > self.max_retries {
+                            return Err(e)
+                        }
+                        failed.clear();
+                        round += 1;
+                        tokio::time::sleep(self.backoff(round)).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_pool(n: usize) -> RpcPool {
+        let urls: Vec<String> = (0..n).map(|i| format!("http://localhost:{}", 8545 + i)).collect();
+        pool_of(&urls, 0)
+    }
+
+    fn pool_of(urls: &[String], max_retries: u32) -> RpcPool {
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                let retry_policy = Box::new(HttpRateLimitRetryPolicy);
+                PoolEndpoint::new(url, max_retries, 0, None, None, 1, retry_policy)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        RpcPool::new(endpoints, max_retries, 0).unwrap()
+    }
+
+    #[test]
+    fn test_select() {
+        let pool = new_pool(3);
+        let selected: Vec<usize> = (0..6).filter_map(|_| pool.select(&[])).collect();
+        assert_eq!(selected, vec![0, 1, 2, 0, 1, 2]);
+
+        // failed endpoints are skipped while cooling down
+        pool.record_failure(1);
+        let selected: Vec<usize> = (0..4).filter_map(|_| pool.select(&[])).collect();
+        assert!(!selected.contains(&1));
+        assert_eq!(pool.select(&[0, 2]), Some(1));
+
+        // faster endpoints get more requests
+        let pool = new_pool(2);
+        pool.record_success(0, Duration::from_millis(10));
+        pool.record_success(1, Duration::from_millis(100));
+        let selected: Vec<usize> = (0..22).filter_map(|_| pool.select(&[])).collect();
+        assert!(selected.iter().filter(|index| **index == 0).count() >= 18);
+    }
+
+    #[tokio::test]
+    async fn test_all_endpoints_failing() {
+        // ports that refuse connections
+        let urls: Vec<String> = (0..2)
+            .map(|_| {
+                let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                format!("http://{}", listener.local_addr().unwrap())
+            })
+            .collect();
+        let n_failures = |pool: &RpcPool| -> u64 {
+            pool.health().iter().map(|(_, health)| health.n_failures).sum()
+        };
+
+        // requests keep cycling through failing endpoints until retries are used up
+        let pool = pool_of(&urls, 5);
+        let result: Result<U64, _> = JsonRpcClient::request(&pool, "eth_blockNumber", ()).await;
+        assert!(result.is_err());
+        assert_eq!(n_failures(&pool), 6);
+        assert!(pool.health().iter().all(|(_, health)| health.n_failures == 3));
+
+        // without retries, every endpoint is still tried once
+        let pool = pool_of(&urls, 0);
+        let result: Result<U64, _> = JsonRpcClient::request(&pool, "eth_blockNumber", ()).await;
+        assert!(result.is_err());
+        assert_eq!(n_failures(&pool), 2);
+    }
+
+    #[test]
+    fn test_is_endpoint_failure() {
+        let response = |code, message: &str| {
+            let error = HttpClientError::JsonRpcError(JsonRpcError {
+                code,
+                message: message.to_string(),
+                data: None,
+            });
+            RetryClientError::ProviderError(ProviderError::JsonRpcClientError(Box::new(error)))
+        };
+        assert!(is_endpoint_failure(&response(-32005, "rate limit exceeded")));
+        assert!(is_endpoint_failure(&response(-32000, "too many requests")));
+        assert!(!is_endpoint_failure(&response(3, "execution reverted")));
+        assert!(is_endpoint_failure(&RetryClientError::TimeoutError));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{err, CollectError, ExecutionEnv, FileOutput, FreezeSummary, Query};
 use chrono::{DateTime, Local};
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::Write,
     path::{Path, PathBuf},
@@ -20,6 +21,7 @@ struct SerializedFreezeSummary {
     completed_paths: Vec<PathBuf>,
     errored_paths: Vec<PathBuf>,
     n_skipped: u64,
+    n_attempts: BTreeMap<PathBuf, u32>,
 }
 
 pub(crate) fn get_report_path(
@@ -119,10 +121,18 @@ fn serialize_summary(
         .flatten()
         .collect();
 
+    let mut n_attempts = BTreeMap::new();
+    for (partition, attempts) in summary.retried.iter() {
+        for path in sink.get_paths(query, partition, None)?.into_values() {
+            n_attempts.insert(path, *attempts);
+        }
+    }
+
     Ok(SerializedFreezeSummary {
         completed_paths,
         errored_paths,
         n_skipped: summary.skipped.len() as u64,
+        n_attempts,
     })
 }
 
@@ -0,0 +1,326 @@
+use crate::{
+    dataframes, delta, err, manifest, provenance,
+    split::{self, SplitIndex},
+    CollectError, Datatype, FileOutput, Manifest, Partition, Query,
+};
+use polars::prelude::*;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// name of table recording which chunks have been loaded into a database
+pub const CHUNKS_TABLE: &str = "_cryo_chunks";
+
+/// name of column identifying the chunk that each row of a database table belongs to
+pub const CHUNK_COLUMN: &str = "_cryo_chunk";
+
+/// destination that collected partitions are written to
+///
+/// `FileOutput` is the default sink. the checkpoint journal, `--resume`, reports, delta commits,
+/// and path collision checks work on output files, so they only apply to sinks that expose a
+/// `FileOutput` through `file_output`. other sinks decide what to skip through `exists` alone,
+/// and freezes note in their summary that these features are disabled
+#[async_trait::async_trait]
+pub trait Sink: Send + Sync {
+    /// whether output of datatype for partition already exists, so that partition can be skipped
+    async fn exists(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+    ) -> Result<bool, CollectError>;
+
+    /// write output of datatype for partition
+    async fn write(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+        df: DataFrame,
+    ) -> Result<(), CollectError>;
+
+    /// write outputs of every datatype of partition, returning number of rows written
+    ///
+    /// sinks can override this to write all datatypes of a partition atomically
+    async fn write_partition(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        dfs: HashMap<Datatype, DataFrame>,
+    ) -> Result<u64, CollectError> {
+        let mut n_rows = 0;
+        for (datatype, df) in dfs.into_iter() {
+            n_rows += df.height() as u64;
+            self.write(query, partition, datatype, df).await?;
+        }
+        Ok(n_rows)
+    }
+
+    /// called once after all partitions of a freeze have been written
+    async fn finalize(&self) -> Result<(), CollectError> {
+        Ok(())
+    }
+
+    /// file output of sink, if sink writes files, which enables the file-based features of freeze
+    fn file_output(&self) -> Option<&FileOutput> {
+        None
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for FileOutput {
+    async fn exists(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+    ) -> Result<bool, CollectError> {
+        let paths: HashMap<_, _> =
+            [(datatype, self.get_path(query, partition, datatype)?)].into_iter().collect();
+        outputs_exist(&paths, self, &mut HashMap::new(), &mut SplitIndex::default())
+    }
+
+    async fn write(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+        df: DataFrame,
+    ) -> Result<(), CollectError> {
+        let dfs = [(datatype, df)].into_iter().collect();
+        self.write_partition(query, partition, dfs).await.map(|_| ())
+    }
+
+    async fn write_partition(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        dfs: HashMap<Datatype, DataFrame>,
+    ) -> Result<u64, CollectError> {
+        let n_rows = dfs.values().map(|df| df.height() as u64).sum();
+        let mut paths = HashMap::new();
+        for datatype in dfs.keys() {
+            paths.insert(*datatype, self.get_path(query, partition, *datatype)?);
+        }
+
+        // write dataframes to disk, splitting them into multiple files if they are too large
+        for (datatype, df) in dfs.into_iter() {
+            let path = paths.get(&datatype).ok_or_else(|| {
+                CollectError::CollectError("could not get path for datatype".to_string())
+            })?;
+            if self.delta {
+                delta::check_dataframe(&df)?;
+            }
+            let pieces = match self.splits_files() {
+                true => split::split_partition(df, path, partition, self)?,
+                false => vec![(path.clone(), partition.clone(), df)],
+            };
+            let stale = match self.splits_files() {
+                true => {
+                    let piece_paths: Vec<PathBuf> =
+                        pieces.iter().map(|(path, _, _)| path.clone()).collect();
+                    find_stale_files(path, &piece_paths)?
+                }
+                false => vec![],
+            };
+            for (path, partition, mut df) in pieces.into_iter() {
+                let metadata = self.file_metadata(&partition, &df);
+                let result = dataframes::df_to_file(&mut df, &path, self, &metadata);
+                result.map_err(|_| CollectError::CollectError("error writing file".to_string()))?;
+                // hash and record file off the async runtime, since both block on file io
+                if self.manifest {
+                    let manifest_path = self.get_manifest_path(datatype);
+                    tokio::task::spawn_blocking(move || {
+                        manifest::record_file(&manifest_path, &path, datatype, &partition, &df)
+                    })
+                    .await
+                    .map_err(CollectError::TaskFailed)??;
+                }
+            }
+
+            // stale files are only removed once their replacements are in place
+            remove_stale_files(&stale, datatype, self)?;
+        }
+        Ok(n_rows)
+    }
+
+    fn file_output(&self) -> Option<&FileOutput> {
+        Some(self)
+    }
+}
+
+/// key identifying the rows of a partition in the tables of a database, made of the network
+/// prefix and label that also name output files, so that chunks of different networks or labels
+/// loaded into the same database do not collide
+pub(crate) fn chunk_key(
+    prefix: &str,
+    suffix: Option<&str>,
+    query: &Query,
+    partition: &Partition,
+) -> Result<String, CollectError> {
+    let label = partition.label(&query.partitioned_by)?;
+    Ok(match suffix {
+        Some(suffix) => format!("{}__{}__{}", prefix, suffix, label),
+        None => format!("{}__{}", prefix, label),
+    })
+}
+
+/// whether all output files exist, and are intact according to their manifests
+pub(crate) fn outputs_exist(
+    paths: &HashMap<Datatype, PathBuf>,
+    sink: &FileOutput,
+    manifests: &mut HashMap<PathBuf, Manifest>,
+    splits: &mut SplitIndex,
+) -> Result<bool, CollectError> {
+    for (datatype, path) in paths.iter() {
+        let Some(files) = splits.output_files(path, sink)? else { return Ok(false) };
+        if sink.manifest {
+            let manifest_path = sink.get_manifest_path(*datatype);
+            let manifest = match manifests.entry(manifest_path.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(Manifest::read(&manifest_path)?),
+            };
+            if !files.iter().all(|file| manifest.is_intact(&manifest_path, file)) {
+                return Ok(false)
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// files previously written for partition that are not replaced by the new files, e.g. pieces
+/// of an earlier run that split the partition differently
+fn find_stale_files(path: &Path, new_paths: &[PathBuf]) -> Result<Vec<PathBuf>, CollectError> {
+    let mut old_paths = SplitIndex::default().find_split_files(path)?.unwrap_or_default();
+    if path.exists() {
+        old_paths.push(path.to_path_buf());
+    }
+    Ok(old_paths.into_iter().filter(|old_path| !new_paths.contains(old_path)).collect())
+}
+
+/// remove stale files of partition, along with their sidecars and manifest entries
+fn remove_stale_files(
+    stale: &[PathBuf],
+    datatype: Datatype,
+    sink: &FileOutput,
+) -> Result<(), CollectError> {
+    if stale.is_empty() {
+        return Ok(())
+    }
+    for stale_path in stale.iter() {
+        std::fs::remove_file(stale_path).map_err(|_| err("could not remove stale file"))?;
+        let sidecar_path = provenance::get_sidecar_path(stale_path);
+        if sidecar_path.exists() {
+            std::fs::remove_file(sidecar_path).map_err(|_| err("could not remove stale file"))?;
+        }
+    }
+    if sink.manifest {
+        manifest::remove_files(&sink.get_manifest_path(datatype), stale)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BlockChunk, ColumnEncoding, Dim, ExecutionEnvBuilder, MetaDatatype, QueryLabels, Source,
+        SourceLabels, TimeDimension,
+    };
+    use ethers::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    /// sink that keeps written dataframes in memory, by partition label
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        written: Arc<Mutex<Vec<(String, Datatype, DataFrame)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for MemorySink {
+        async fn exists(
+            &self,
+            query: &Query,
+            partition: &Partition,
+            datatype: Datatype,
+        ) -> Result<bool, CollectError> {
+            let label = partition.label(&query.partitioned_by)?;
+            let written = self.written.lock().map_err(|_| err("could not lock sink"))?;
+            Ok(written.iter().any(|(l, d, _)| *l == label && *d == datatype))
+        }
+
+        async fn write(
+            &self,
+            query: &Query,
+            partition: &Partition,
+            datatype: Datatype,
+            df: DataFrame,
+        ) -> Result<(), CollectError> {
+            let label = partition.label(&query.partitioned_by)?;
+            let mut written = self.written.lock().map_err(|_| err("could not lock sink"))?;
+            written.push((label, datatype, df));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_freeze_to_custom_sink() {
+        let (provider, mock) = Provider::mocked();
+        let block = Block::<TxHash> {
+            number: Some(1.into()),
+            hash: Some(H256::repeat_byte(1)),
+            ..Default::default()
+        };
+        mock.push(block).unwrap();
+        let source = Source {
+            provider: provider.into(),
+            chain_id: 1,
+            inner_request_size: 1,
+            max_concurrent_chunks: None,
+            rpc_url: "".to_string(),
+            semaphore: Arc::new(None),
+            rate_limiter: Arc::new(None),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
+            labels: SourceLabels::default(),
+        };
+
+        let datatype = Datatype::Blocks;
+        let columns = Some(vec!["block_number".to_string(), "block_hash".to_string()]);
+        let table = datatype
+            .table_schema(&[], &ColumnEncoding::Binary, &None, &None, &columns, None, None)
+            .unwrap();
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Numbers(vec![1])]),
+            ..Default::default()
+        };
+        let query = Query {
+            datatypes: vec![MetaDatatype::Scalar(datatype)],
+            schemas: [(datatype, table)].into_iter().collect(),
+            time_dimension: TimeDimension::Blocks,
+            partitions: vec![partition.clone()],
+            partitioned_by: vec![Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: QueryLabels { align: false, reorg_buffer: 0 },
+        };
+        let env = ExecutionEnvBuilder::new().verbose(0).build();
+
+        // collected partitions are written to sink
+        let sink = MemorySink::default();
+        let summary = crate::freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!((summary.completed.len(), summary.n_rows), (1, 1));
+        {
+            let written = sink.written.lock().unwrap();
+            assert_eq!(written.len(), 1);
+            let block_numbers = written[0].2.column("block_number").unwrap();
+            assert_eq!(block_numbers.u32().unwrap().get(0), Some(1));
+        }
+
+        // partitions that the sink already has are skipped
+        let summary = crate::freeze(&query, &source, &sink, &env).await.unwrap().unwrap();
+        assert_eq!((summary.completed.len(), summary.skipped.len()), (0, 1));
+    }
+}
@@ -11,7 +11,7 @@ use tokio::{
     task,
 };
 
-use crate::CollectError;
+use crate::{AdaptiveLimiter, BatchHttp, Capabilities, CollectError, RequestCache, RpcPool};
 
 /// RateLimiter based on governor crate
 pub type RateLimiter = governor::RateLimiter<NotKeyed, InMemoryState, DefaultClock, NoOpMiddleware>;
@@ -33,6 +33,13 @@ pub struct Source {
     pub semaphore: Arc<Option<Semaphore>>,
     /// rate limiter for controlling request rate
     pub rate_limiter: Arc<Option<RateLimiter>>,
+    /// adaptive limits, used instead of semaphore and rate limiter and applied by the http
+    /// transport to each attempt of a request
+    pub adaptive_limiter: Option<Arc<AdaptiveLimiter>>,
+    /// on-disk cache of responses for finalized blocks
+    pub request_cache: Option<Arc<RequestCache>>,
+    /// capabilities of node, probed once and reused by later queries
+    pub capabilities: Arc<tokio::sync::Mutex<Option<Capabilities>>>,
     /// Labels (these are non-functional)
     pub labels: SourceLabels,
 }
@@ -44,10 +51,14 @@ pub enum ProviderWrapper {
     MockProvider(Arc<Provider<MockProvider>>),
     /// http client
     RetryClientHttp(Arc<Provider<RetryClient<Http>>>),
+    /// http client that batches requests
+    RetryClientBatchHttp(Arc<Provider<RetryClient<BatchHttp>>>),
     /// websocket client
     WsClient(Arc<Provider<Ws>>),
     /// ipc client
     IpcClient(Arc<Provider<Ipc>>),
+    /// pool of http clients
+    RpcPool(Arc<Provider<RpcPool>>),
 }
 
 impl From<Provider<MockProvider>> for ProviderWrapper {
@@ -62,6 +73,12 @@ impl From<Provider<RetryClient<Http>>> for ProviderWrapper {
     }
 }
 
+impl From<Provider<RetryClient<BatchHttp>>> for ProviderWrapper {
+    fn from(value: Provider<RetryClient<BatchHttp>>) -> ProviderWrapper {
+        ProviderWrapper::RetryClientBatchHttp(Arc::new(value))
+    }
+}
+
 impl From<Provider<Ws>> for ProviderWrapper {
     fn from(value: Provider<Ws>) -> ProviderWrapper {
         ProviderWrapper::WsClient(Arc::new(value))
@@ -74,6 +91,12 @@ impl From<Provider<Ipc>> for ProviderWrapper {
     }
 }
 
+impl From<Provider<RpcPool>> for ProviderWrapper {
+    fn from(value: Provider<RpcPool>) -> ProviderWrapper {
+        ProviderWrapper::RpcPool(Arc::new(value))
+    }
+}
+
 /// extract the provider from a source and run specified method
 #[macro_export]
 macro_rules! source_provider {
@@ -81,8 +104,10 @@ macro_rules! source_provider {
         match &$source.provider {
             ProviderWrapper::MockProvider(provider) => provider.$method($($arg),*),
             ProviderWrapper::RetryClientHttp(provider) => provider.$method($($arg),*),
+            ProviderWrapper::RetryClientBatchHttp(provider) => provider.$method($($arg),*),
             ProviderWrapper::WsClient(provider) => provider.$method($($arg),*),
             ProviderWrapper::IpcClient(provider) => provider.$method($($arg),*),
+            ProviderWrapper::RpcPool(provider) => provider.$method($($arg),*),
         }
     };
 }
@@ -178,9 +203,13 @@ impl Source {
                 max_requests_per_second: Some(0),
                 max_retries: Some(DEFAULT_MAX_RETRIES),
                 initial_backoff: Some(DEFAULT_INTIAL_BACKOFF),
+                max_batch_size: None,
             },
             rate_limiter: rate_limiter.into(),
             semaphore: semaphore.into(),
+            adaptive_limiter: None,
+            request_cache: None,
+            capabilities: Default::default(),
         };
 
         Ok(source)
@@ -245,6 +274,8 @@ pub struct SourceLabels {
     pub max_retries: Option<u32>,
     /// Initial backoff
     pub initial_backoff: Option<u64>,
+    /// Maximum requests per batch
+    pub max_batch_size: Option<u64>,
 }
 
 /// Wrapper over `Provider<P>` that adds concurrency and rate limiting controls
@@ -264,8 +295,18 @@ type Result<T> = ::core::result::Result<T, CollectError>;
 impl Source {
     /// Returns an array (possibly empty) of logs that match the filter
     pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, get_logs(filter)).await)
+        let last_block = match filter.block_option {
+            FilterBlockOption::Range {
+                from_block: Some(BlockNumber::Number(_)),
+                to_block: Some(BlockNumber::Number(to_block)),
+            } => Some(to_block.as_u64()),
+            _ => None,
+        };
+        self.cached("eth_getLogs", filter, last_block, async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, get_logs(filter)).await)
+        })
+        .await
     }
 
     /// Replays all transactions in a block returning the requested traces for each transaction
@@ -403,8 +444,12 @@ impl Source {
 
     /// Returns traces created at given block
     pub async fn trace_block(&self, block_num: BlockNumber) -> Result<Vec<Trace>> {
-        let _permit = self.permit_request().await;
-        Self::map_err(source_provider!(self, trace_block(block_num)).await)
+        let last_block = block_num.as_number().map(|block| block.as_u64());
+        self.cached("trace_block", &block_num, last_block, async {
+            let _permit = self.permit_request().await;
+            Self::map_err(source_provider!(self, trace_block(block_num)).await)
+        })
+        .await
     }
 
     /// Returns all traces of a given transaction
@@ -487,12 +532,20 @@ impl Source {
         Self::map_err(source_provider!(self, get_block_number()).await)
     }
 
+    /// Returns the number of the latest finalized block, `None` if node does not know the
+    /// finalized tag
+    pub async fn get_finalized_block_number(&self) -> Result<Option<u64>> {
+        let _permit = self.permit_request().await;
+        let block = source_provider!(self, get_block(BlockNumber::Finalized)).await;
+        Ok(Self::map_err(block)?.and_then(|block| block.number).map(|number| number.as_u64()))
+    }
+
     // extra helpers below
 
     /// block number of transaction
     pub async fn get_transaction_block_number(&self, transaction_hash: Vec<u8>) -> Result<u32> {
         let block = self.get_transaction(H256::from_slice(&transaction_hash)).await?;
-        let block = block.ok_or(CollectError::CollectError("could not get block".to_string()))?;
+        let block = block.ok_or(CollectError::NotFound("could not get block".to_string()))?;
         Ok(block
             .block_number
             .ok_or(CollectError::CollectError("could not get block number".to_string()))?
@@ -504,7 +557,7 @@ impl Source {
         Ok(self
             .get_transaction_receipt(H256::from_slice(&transaction_hash))
             .await?
-            .ok_or(CollectError::CollectError("transaction receipt not found".to_string()))?
+            .ok_or(CollectError::NotFound("transaction receipt not found".to_string()))?
             .logs)
     }
 
@@ -553,12 +606,16 @@ impl Source {
         options: GethDebugTracingOptions,
         include_transaction_hashes: bool,
     ) -> Result<(Option<u32>, Vec<Option<Vec<u8>>>, Vec<GethTrace>)> {
-        let traces = {
+        let params = (block_number, &options);
+        let request = async {
             let _permit = self.permit_request().await;
-            source_provider!(self, debug_trace_block_by_number(Some(block_number.into()), options))
+            let block = Some(block_number.into());
+            source_provider!(self, debug_trace_block_by_number(block, options.clone()))
                 .await
-                .map_err(CollectError::ProviderError)?
+                .map_err(CollectError::ProviderError)
         };
+        let last_block = Some(block_number as u64);
+        let traces = self.cached("debug_traceBlockByNumber", &params, last_block, request).await?;
 
         let txs = if include_transaction_hashes {
             match self.get_block(block_number as u64).await? {
@@ -566,9 +623,7 @@ impl Source {
                     block.transactions.iter().map(|x| Some(x.as_bytes().to_vec())).collect()
                 }
                 None => {
-                    return Err(CollectError::CollectError(
-                        "could not get block for txs".to_string(),
-                    ))
+                    return Err(CollectError::NotFound("could not get block for txs".to_string()))
                 }
             }
         } else {
@@ -753,9 +808,7 @@ impl Source {
             match self.get_transaction(ethers_tx).await? {
                 Some(tx) => tx.block_number.map(|x| x.as_u32()),
                 None => {
-                    return Err(CollectError::CollectError(
-                        "could not get block for txs".to_string(),
-                    ))
+                    return Err(CollectError::NotFound("could not get block for txs".to_string()))
                 }
             }
         } else {
@@ -0,0 +1,319 @@
+use crate::{
+    audit::{self, OutputFile},
+    dataframes, err, provenance, BlockChunk, ChunkData, CollectError, FileOutput, Partition,
+};
+use polars::prelude::*;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// number of rows written to estimate the encoded size of rows
+const SAMPLE_ROWS: usize = 10_000;
+
+/// number of samples written by this process, to give each sample a unique path
+static N_SAMPLES: AtomicU64 = AtomicU64::new(0);
+
+/// split dataframe of partition into pieces of at most `max_rows_per_file` rows and about
+/// `max_file_size` bytes, returning the path, partition, and dataframe of each piece
+///
+/// pieces are split between blocks and labeled with the block sub-range that they cover, so
+/// that the pieces of a partition cover its block range contiguously. dataframes without a
+/// `block_number` column are written as a single file
+pub(crate) fn split_partition(
+    df: DataFrame,
+    path: &Path,
+    partition: &Partition,
+    sink: &FileOutput,
+) -> Result<Vec<(PathBuf, Partition, DataFrame)>, CollectError> {
+    let unsplit = || Ok(vec![(path.to_path_buf(), partition.clone(), df.clone())]);
+    let max_rows = match max_rows(&df, path, sink)? {
+        Some(max_rows) if df.height() > max_rows => max_rows,
+        _ => return unsplit(),
+    };
+    let block_chunks = match &partition.block_numbers {
+        Some(block_chunks) => block_chunks,
+        None => return unsplit(),
+    };
+    let (Some(min_block), Some(max_block)) = (block_chunks.min_value(), block_chunks.max_value())
+    else {
+        return unsplit()
+    };
+    let stub = block_chunks.stub().map_err(|_| err("could not determine name of chunk"))?;
+    if !path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.contains(&stub)) {
+        return unsplit()
+    }
+
+    let df = match block_numbers(&df)? {
+        Some(blocks) if blocks.windows(2).all(|pair| pair[0] <= pair[1]) => df,
+        Some(_) => df.sort(["block_number"], false, true)?,
+        None => return unsplit(),
+    };
+    let blocks = block_numbers(&df)?.ok_or(err("could not read block numbers"))?;
+
+    let offsets = split_offsets(&blocks, max_rows);
+    let mut pieces = Vec::new();
+    for (i, start) in std::iter::once(0).chain(offsets.iter().copied()).enumerate() {
+        let end = offsets.get(i).copied().unwrap_or(blocks.len());
+        let start_block = if i == 0 { min_block } else { blocks[start] };
+        let end_block = match offsets.get(i) {
+            Some(next) => blocks[*next] - 1,
+            None => max_block,
+        };
+        let sub_chunk = BlockChunk::Range(start_block, end_block);
+        let sub_stub = sub_chunk.stub().map_err(|_| err("could not determine name of chunk"))?;
+        let sub_partition = Partition { block_numbers: Some(vec![sub_chunk]), ..partition.clone() };
+        let piece = df.slice(start as i64, end - start);
+        pieces.push((replace_stub(path, &stub, &sub_stub), sub_partition, piece));
+    }
+    Ok(pieces)
+}
+
+/// output files of a dataset directory by network, datatype, and label, sorted by block range
+type DatasetFiles = HashMap<(String, String, Option<String>), Vec<OutputFile>>;
+
+/// index of the output files of dataset directories, so that finding the pieces of many
+/// partitions reads each directory only once
+///
+/// directories are read the first time they are looked up, so an index should only be used
+/// while the files that it covers are not being written
+#[derive(Default)]
+pub(crate) struct SplitIndex {
+    dirs: HashMap<PathBuf, DatasetFiles>,
+}
+
+impl SplitIndex {
+    /// files holding the output at path, i.e. the file itself or, if sink splits files, the
+    /// pieces that it was split into, None if neither exist
+    pub(crate) fn output_files(
+        &mut self,
+        path: &Path,
+        sink: &FileOutput,
+    ) -> Result<Option<Vec<PathBuf>>, CollectError> {
+        if path.exists() {
+            Ok(Some(vec![path.to_path_buf()]))
+        } else if sink.splits_files() {
+            self.find_split_files(path)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// find files that were split from the output at path, if they cover its whole block range
+    pub(crate) fn find_split_files(
+        &mut self,
+        path: &Path,
+    ) -> Result<Option<Vec<PathBuf>>, CollectError> {
+        let Some(output) = audit::parse_output_path(path) else { return Ok(None) };
+        let Some(dir) = path.parent().filter(|dir| dir.is_dir()) else { return Ok(None) };
+        let dataset = match self.dirs.entry(dir.to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(read_dataset_files(dir)?),
+        };
+        let key = (output.network, output.datatype, output.label);
+        let files = dataset.get(&key).map(|files| files.as_slice()).unwrap_or_default();
+
+        // pieces are the files within the block range of output, which must cover it contiguously
+        let first = files.partition_point(|file| file.start_block < output.start_block);
+        let pieces: Vec<&OutputFile> = files[first..]
+            .iter()
+            .take_while(|file| file.start_block <= output.end_block)
+            .filter(|file| {
+                file.end_block <= output.end_block &&
+                    file.format == output.format &&
+                    file.path != path
+            })
+            .collect();
+        let mut next_block = output.start_block;
+        for piece in pieces.iter() {
+            if piece.start_block != next_block {
+                return Ok(None)
+            }
+            next_block = piece.end_block + 1;
+        }
+        if pieces.is_empty() || next_block != output.end_block + 1 {
+            return Ok(None)
+        }
+        Ok(Some(pieces.into_iter().map(|piece| piece.path.clone()).collect()))
+    }
+}
+
+fn read_dataset_files(dir: &Path) -> Result<DatasetFiles, CollectError> {
+    let mut dataset = DatasetFiles::new();
+    let entries = std::fs::read_dir(dir).map_err(|_| err("could not read directory"))?;
+    for entry in entries {
+        let path = entry.map_err(|_| err("could not read directory entry"))?.path();
+        if let Some(file) = audit::parse_output_path(&path) {
+            let key = (file.network.clone(), file.datatype.clone(), file.label.clone());
+            dataset.entry(key).or_default().push(file);
+        }
+    }
+    for files in dataset.values_mut() {
+        files.sort_by_key(|file| (file.start_block, file.end_block));
+    }
+    Ok(dataset)
+}
+
+/// maximum number of rows per file, estimating bytes per row by encoding a sample of rows
+fn max_rows(df: &DataFrame, path: &Path, sink: &FileOutput) -> Result<Option<usize>, CollectError> {
+    let max_rows_by_size = match sink.max_file_size {
+        Some(max_file_size) if df.height() > 0 => {
+            let row_size = (encoded_size(df, path, sink)? / df.height().min(SAMPLE_ROWS)).max(1);
+            Some(((max_file_size as usize) / row_size).max(1))
+        }
+        _ => None,
+    };
+    let max_rows = match (sink.max_rows_per_file, max_rows_by_size) {
+        (Some(max_rows), Some(max_rows_by_size)) => Some(max_rows.min(max_rows_by_size)),
+        (max_rows, max_rows_by_size) => max_rows.or(max_rows_by_size),
+    };
+    Ok(max_rows)
+}
+
+/// size of the first `SAMPLE_ROWS` rows of dataframe when written in the format of path, so that
+/// file sizes account for encoding and compression
+fn encoded_size(df: &DataFrame, path: &Path, sink: &FileOutput) -> Result<usize, CollectError> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let sample_path = std::env::temp_dir().join(format!(
+        "cryo_sample_{}_{}.{}",
+        std::process::id(),
+        N_SAMPLES.fetch_add(1, Ordering::SeqCst),
+        extension
+    ));
+    let mut sample = df.slice(0, SAMPLE_ROWS);
+    let result = dataframes::df_to_file(&mut sample, &sample_path, sink, &sink.metadata)
+        .map_err(|_| err("could not write sample to estimate file size"))
+        .and_then(|_| {
+            std::fs::metadata(&sample_path).map_err(|_| err("could not read size of sample"))
+        });
+    let _ = std::fs::remove_file(&sample_path);
+    let _ = std::fs::remove_file(provenance::get_sidecar_path(&sample_path));
+    Ok(result?.len() as usize)
+}
+
+fn block_numbers(df: &DataFrame) -> Result<Option<Vec<u64>>, CollectError> {
+    let Ok(column) = df.column("block_number") else { return Ok(None) };
+    let column = column.cast(&DataType::UInt64)?;
+    Ok(column.u64()?.into_iter().collect())
+}
+
+/// row offsets at which new files start, so that each file has at most max_rows rows
+///
+/// files are only split between blocks, a block with more than max_rows rows gets its own file
+fn split_offsets(blocks: &[u64], max_rows: usize) -> Vec<usize> {
+    let is_boundary = |i: &usize| blocks[*i] != blocks[*i - 1];
+    let mut offsets = Vec::new();
+    let mut start = 0;
+    while blocks.len() - start > max_rows {
+        let limit = start + max_rows;
+        let offset = (start + 1..=limit)
+            .rev()
+            .find(is_boundary)
+            .or_else(|| (limit + 1..blocks.len()).find(is_boundary));
+        match offset {
+            Some(offset) => {
+                offsets.push(offset);
+                start = offset;
+            }
+            None => break,
+        }
+    }
+    offsets
+}
+
+fn replace_stub(path: &Path, stub: &str, sub_stub: &str) -> PathBuf {
+    let filename = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    path.with_file_name(filename.replacen(stub, sub_stub, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_offsets() {
+        let blocks = [0, 0, 1, 1, 1, 2, 3, 3, 3, 3, 3, 4];
+        assert_eq!(split_offsets(&blocks, 20), Vec::<usize>::new());
+        assert_eq!(split_offsets(&blocks, 4), vec![2, 6, 11]);
+        assert_eq!(split_offsets(&blocks, 6), vec![6]);
+        assert_eq!(split_offsets(&[7, 7, 7], 1), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_find_split_files() {
+        let dir = std::env::temp_dir().join(format!("cryo_split_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(format!("{}.parquet", name));
+        for name in [
+            "ethereum__logs__00000000_to_00000499",
+            "ethereum__logs__00000500_to_00000999",
+            "ethereum__logs__00001000_to_00001499",
+            "ethereum__logs__v2__00002000_to_00002999",
+            "base__logs__00002000_to_00002999",
+        ] {
+            std::fs::write(path(name), b"").unwrap();
+        }
+
+        let mut splits = SplitIndex::default();
+        let pieces = splits.find_split_files(&path("ethereum__logs__00000000_to_00000999"));
+        let expected = vec![
+            path("ethereum__logs__00000000_to_00000499"),
+            path("ethereum__logs__00000500_to_00000999"),
+        ];
+        assert_eq!(pieces.unwrap(), Some(expected));
+
+        // pieces must cover the whole block range, and belong to the same network and label
+        let pieces = splits.find_split_files(&path("ethereum__logs__00001000_to_00001999"));
+        assert_eq!(pieces.unwrap(), None);
+        let pieces = splits.find_split_files(&path("ethereum__logs__00002000_to_00002999"));
+        assert_eq!(pieces.unwrap(), None);
+        let pieces = splits.find_split_files(&path("ethereum__logs__v2__00002000_to_00002999"));
+        assert_eq!(pieces.unwrap(), None);
+
+        // files that are not split are found without looking for pieces
+        let sink = FileOutput {
+            max_rows_per_file: Some(10),
+            ..FileOutput::new_test(dir.clone(), crate::FileFormat::Parquet)
+        };
+        let whole = path("base__logs__00002000_to_00002999");
+        assert_eq!(splits.output_files(&whole, &sink).unwrap(), Some(vec![whole.clone()]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_rows_by_encoded_size() {
+        let dir = std::env::temp_dir().join(format!("cryo_split_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ethereum__logs__00000000_to_00099999.parquet");
+        let sink = FileOutput {
+            max_file_size: Some(50_000),
+            parquet_compression: ParquetCompression::Lz4Raw,
+            ..FileOutput::new_test(dir.clone(), crate::FileFormat::Parquet)
+        };
+        let n_rows = 100_000u64;
+        let df = df!(
+            "block_number" => (0..n_rows).collect::<Vec<u64>>(),
+            "address" => (0..n_rows).map(|i| vec![(i % 4) as u8; 20]).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        // encoded rows are much smaller than in memory, so files hold more rows
+        let max_rows = max_rows(&df, &path, &sink).unwrap().unwrap();
+        let in_memory_max_rows = 50_000 / (df.estimated_size() / df.height());
+        assert!(max_rows > 2 * in_memory_max_rows);
+
+        let partition = Partition {
+            block_numbers: Some(vec![BlockChunk::Range(0, n_rows - 1)]),
+            ..Default::default()
+        };
+        let pieces = split_partition(df, &path, &partition, &sink).unwrap();
+        assert!(pieces.len() > 1);
+        for (piece_path, _, mut piece) in pieces.into_iter() {
+            dataframes::df_to_file(&mut piece, &piece_path, &sink, &sink.metadata).unwrap();
+            let size = std::fs::metadata(&piece_path).unwrap().len();
+            assert!(size <= 75_000, "{} bytes", size);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use crate::{
+    err,
+    sink::{chunk_key, CHUNKS_TABLE, CHUNK_COLUMN},
+    CollectError, ColumnType, Datatype, FileOutput, Partition, Query, Schemas, Sink, Table,
+    U256Type,
+};
+use polars::prelude::*;
+use rusqlite::{params, types::Value, Connection, Transaction};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// max number of parameters per statement, the default limit of sqlite before 3.32
+const MAX_PARAMETERS: usize = 999;
+
+lazy_static::lazy_static! {
+    /// serializes writes of concurrent chunks to the database
+    static ref SQLITE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// sink that inserts each partition into a sqlite database with a table per datatype
+///
+/// loaded chunks are recorded in a bookkeeping table, so that re-runs skip them
+#[derive(Clone, Debug)]
+pub struct SqliteSink {
+    /// path of database file
+    pub db_path: PathBuf,
+    /// network name, identifies chunks along with label
+    pub prefix: String,
+    /// label of output, identifies chunks along with network name
+    pub suffix: Option<String>,
+    /// whether to replace chunks that were already loaded
+    pub overwrite: bool,
+}
+
+impl SqliteSink {
+    /// sink for a database named after the network in the output dir of file output
+    pub fn new(output: &FileOutput) -> SqliteSink {
+        SqliteSink {
+            db_path: output.output_dir.join(format!("{}.sqlite", output.prefix)),
+            prefix: output.prefix.clone(),
+            suffix: output.suffix.clone(),
+            overwrite: output.overwrite,
+        }
+    }
+
+    fn chunk(&self, query: &Query, partition: &Partition) -> Result<String, CollectError> {
+        chunk_key(&self.prefix, self.suffix.as_deref(), query, partition)
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for SqliteSink {
+    async fn exists(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+    ) -> Result<bool, CollectError> {
+        if self.overwrite || !self.db_path.exists() {
+            return Ok(false)
+        }
+        let (db_path, chunk) = (self.db_path.clone(), self.chunk(query, partition)?);
+        tokio::task::spawn_blocking(move || chunk_exists(&db_path, &datatype.name(), &chunk))
+            .await
+            .map_err(CollectError::TaskFailed)?
+    }
+
+    async fn write(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+        df: DataFrame,
+    ) -> Result<(), CollectError> {
+        let dfs = [(datatype, df)].into_iter().collect();
+        self.write_partition(query, partition, dfs).await.map(|_| ())
+    }
+
+    /// insert every datatype of partition within a single transaction, off the async runtime
+    /// since sqlite io is blocking
+    async fn write_partition(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        dfs: HashMap<Datatype, DataFrame>,
+    ) -> Result<u64, CollectError> {
+        let n_rows = dfs.values().map(|df| df.height() as u64).sum();
+        let (db_path, chunk) = (self.db_path.clone(), self.chunk(query, partition)?);
+        let schemas = query.schemas.clone();
+        tokio::task::spawn_blocking(move || write_chunk(&db_path, &chunk, &schemas, &dfs))
+            .await
+            .map_err(CollectError::TaskFailed)??;
+        Ok(n_rows)
+    }
+}
+
+/// whether chunk has already been inserted into table of database
+fn chunk_exists(db_path: &Path, table: &str, chunk: &str) -> Result<bool, CollectError> {
+    let conn = open(db_path)?;
+    conn.query_row(
+        &format!(
+            "SELECT EXISTS(SELECT 1 FROM {} WHERE table_name = ?1 AND chunk = ?2)",
+            CHUNKS_TABLE
+        ),
+        params![table, chunk],
+        |row| row.get(0),
+    )
+    .map_err(sqlite_err)
+}
+
+/// insert dataframes of chunk into database within a single transaction
+///
+/// rows previously inserted for the same chunk are replaced, so re-running a chunk is idempotent
+fn write_chunk(
+    db_path: &Path,
+    chunk: &str,
+    schemas: &Schemas,
+    dfs: &HashMap<Datatype, DataFrame>,
+) -> Result<(), CollectError> {
+    let _guard = SQLITE_LOCK.lock().map_err(|_| err("could not lock database"))?;
+    let mut conn = open(db_path)?;
+    let tx = conn.transaction().map_err(sqlite_err)?;
+    for (datatype, df) in dfs.iter() {
+        let table = datatype.name();
+        create_table(&tx, &table, schemas.get(datatype), df)?;
+        tx.execute(&format!("DELETE FROM \"{}\" WHERE {} = ?1", table, CHUNK_COLUMN), [chunk])
+            .map_err(sqlite_err)?;
+        insert_rows(&tx, &table, chunk, df)?;
+        tx.execute(
+            &format!("INSERT OR REPLACE INTO {} VALUES (?1, ?2, ?3)", CHUNKS_TABLE),
+            params![table, chunk, df.height() as i64],
+        )
+        .map_err(sqlite_err)?;
+    }
+    tx.commit().map_err(sqlite_err)
+}
+
+fn open(db_path: &Path) -> Result<Connection, CollectError> {
+    let conn = Connection::open(db_path).map_err(sqlite_err)?;
+    conn.busy_timeout(std::time::Duration::from_secs(60)).map_err(sqlite_err)?;
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (table_name TEXT NOT NULL, chunk TEXT NOT NULL, \
+             n_rows INTEGER NOT NULL, PRIMARY KEY (table_name, chunk))",
+            CHUNKS_TABLE
+        ),
+        [],
+    )
+    .map_err(sqlite_err)?;
+    Ok(conn)
+}
+
+/// create table with columns of schema, plus any extra columns of dataframe such as decoded logs
+///
+/// existing tables must already have every column of dataframe, e.g. tables created by runs with
+/// different columns or event signatures are refused rather than altered
+fn create_table(
+    tx: &Transaction<'_>,
+    table: &str,
+    schema: Option<&Table>,
+    df: &DataFrame,
+) -> Result<(), CollectError> {
+    let mut columns: Vec<(String, &str)> = match schema {
+        Some(schema) => schema_columns(schema),
+        None => Vec::new(),
+    };
+    for (name, dtype) in df.schema().iter() {
+        if !columns.iter().any(|(column, _)| column == name.as_str()) {
+            columns.push((name.to_string(), dtype_to_sql(dtype)));
+        }
+    }
+    let mut definitions: Vec<String> =
+        columns.iter().map(|(name, sql_type)| format!("\"{}\" {}", name, sql_type)).collect();
+    definitions.push(format!("{} TEXT NOT NULL", CHUNK_COLUMN));
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS \"{table}\" ({}); \
+         CREATE INDEX IF NOT EXISTS \"{table}_{chunk}\" ON \"{table}\" ({chunk});",
+        definitions.join(", "),
+        table = table,
+        chunk = CHUNK_COLUMN,
+    );
+    tx.execute_batch(&sql).map_err(sqlite_err)?;
+
+    let mut statement =
+        tx.prepare(&format!("PRAGMA table_info(\"{}\")", table)).map_err(sqlite_err)?;
+    let existing = statement
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(sqlite_err)?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(sqlite_err)?;
+    let missing: Vec<String> = df
+        .get_column_names()
+        .into_iter()
+        .chain([CHUNK_COLUMN])
+        .filter(|name| !existing.iter().any(|column| column == name))
+        .map(|name| name.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(err(&format!(
+            "sqlite table {} already exists with a different schema, it is missing columns: {}. \
+             write to a new database or drop the table",
+            table,
+            missing.join(", ")
+        )))
+    }
+    Ok(())
+}
+
+/// sql columns of schema, expanding u256 columns into one column per u256 representation
+fn schema_columns(schema: &Table) -> Vec<(String, &'static str)> {
+    let mut columns = Vec::new();
+    for name in schema.columns() {
+        match schema.column_type(name) {
+            Some(ColumnType::UInt256) => {
+                for u256_type in schema.u256_types.iter() {
+                    let column = name.to_string() + u256_type.suffix().as_str();
+                    let sql_type = match u256_type {
+                        U256Type::Binary => match schema.binary_type {
+                            crate::ColumnEncoding::Binary => "BLOB",
+                            crate::ColumnEncoding::Hex => "TEXT",
+                        },
+                        u256_type => column_type_to_sql(u256_type.to_columntype()),
+                    };
+                    columns.push((column, sql_type));
+                }
+            }
+            Some(column_type) => columns.push((name.to_string(), column_type_to_sql(column_type))),
+            None => {}
+        }
+    }
+    columns
+}
+
+fn column_type_to_sql(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Boolean |
+        ColumnType::UInt32 |
+        ColumnType::UInt64 |
+        ColumnType::Int32 |
+        ColumnType::Int64 => "INTEGER",
+        ColumnType::Float32 | ColumnType::Float64 => "REAL",
+        ColumnType::Binary => "BLOB",
+        ColumnType::UInt256 | ColumnType::Decimal128 | ColumnType::String | ColumnType::Hex => {
+            "TEXT"
+        }
+    }
+}
+
+fn dtype_to_sql(dtype: &DataType) -> &'static str {
+    match dtype {
+        DataType::Boolean |
+        DataType::UInt8 |
+        DataType::UInt16 |
+        DataType::UInt32 |
+        DataType::UInt64 |
+        DataType::Int8 |
+        DataType::Int16 |
+        DataType::Int32 |
+        DataType::Int64 => "INTEGER",
+        DataType::Float32 | DataType::Float64 => "REAL",
+        DataType::Binary => "BLOB",
+        _ => "TEXT",
+    }
+}
+
+/// insert rows of dataframe, with as many rows per statement as sqlite's parameter limit allows
+fn insert_rows(
+    tx: &Transaction<'_>,
+    table: &str,
+    chunk: &str,
+    df: &DataFrame,
+) -> Result<(), CollectError> {
+    let mut names: Vec<String> =
+        df.get_column_names().iter().map(|name| format!("\"{}\"", name)).collect();
+    names.push(CHUNK_COLUMN.to_string());
+    let rows_per_statement = (MAX_PARAMETERS / names.len()).max(1);
+    let insert_sql = |n_rows: usize| {
+        let row = format!("({})", vec!["?"; names.len()].join(", "));
+        let rows = vec![row; n_rows].join(", ");
+        format!("INSERT INTO \"{}\" ({}) VALUES {}", table, names.join(", "), rows)
+    };
+
+    let columns: Vec<Series> = df.get_columns().iter().map(|column| column.rechunk()).collect();
+    let mut iters: Vec<_> = columns.iter().map(|column| column.iter()).collect();
+    let mut statement = tx.prepare_cached(&insert_sql(rows_per_statement)).map_err(sqlite_err)?;
+    let mut remaining = df.height();
+    while remaining > 0 {
+        let n_rows = remaining.min(rows_per_statement);
+        let mut values = Vec::with_capacity(n_rows * names.len());
+        for _ in 0..n_rows {
+            for iter in iters.iter_mut() {
+                values.push(iter.next().map_or(Value::Null, to_sql_value));
+            }
+            values.push(Value::Text(chunk.to_string()));
+        }
+        if n_rows < rows_per_statement {
+            statement = tx.prepare_cached(&insert_sql(n_rows)).map_err(sqlite_err)?;
+        }
+        statement.execute(rusqlite::params_from_iter(values)).map_err(sqlite_err)?;
+        remaining -= n_rows;
+    }
+    Ok(())
+}
+
+fn to_sql_value(value: AnyValue<'_>) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(value) => Value::Integer(value as i64),
+        AnyValue::UInt8(value) => Value::Integer(value.into()),
+        AnyValue::UInt16(value) => Value::Integer(value.into()),
+        AnyValue::UInt32(value) => Value::Integer(value.into()),
+        AnyValue::UInt64(value) => match i64::try_from(value) {
+            Ok(value) => Value::Integer(value),
+            Err(_) => Value::Text(value.to_string()),
+        },
+        AnyValue::Int8(value) => Value::Integer(value.into()),
+        AnyValue::Int16(value) => Value::Integer(value.into()),
+        AnyValue::Int32(value) => Value::Integer(value.into()),
+        AnyValue::Int64(value) => Value::Integer(value),
+        AnyValue::Float32(value) => Value::Real(value.into()),
+        AnyValue::Float64(value) => Value::Real(value),
+        AnyValue::Utf8(value) => Value::Text(value.to_string()),
+        AnyValue::Utf8Owned(value) => Value::Text(value.to_string()),
+        AnyValue::Binary(value) => Value::Blob(value.to_vec()),
+        AnyValue::BinaryOwned(value) => Value::Blob(value),
+        value => Value::Text(value.to_string()),
+    }
+}
+
+fn sqlite_err(e: rusqlite::Error) -> CollectError {
+    CollectError::CollectError(format!("sqlite error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockChunk, ColumnEncoding, FileFormat};
+
+    fn test_sink(name: &str) -> SqliteSink {
+        let dir = std::env::temp_dir().join(format!("cryo_sqlite_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        SqliteSink::new(&FileOutput::new_test(dir, FileFormat::Parquet))
+    }
+
+    fn test_query(schemas: Schemas) -> Query {
+        Query {
+            datatypes: vec![],
+            schemas,
+            time_dimension: crate::TimeDimension::Blocks,
+            partitions: vec![],
+            partitioned_by: vec![crate::Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: crate::QueryLabels { align: false, reorg_buffer: 0 },
+        }
+    }
+
+    fn test_partition(start: u64, end: u64) -> Partition {
+        Partition { block_numbers: Some(vec![BlockChunk::Range(start, end)]), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_write_partition_is_idempotent() {
+        let sink = test_sink("idempotent");
+        let datatype = Datatype::Blocks;
+        let columns = Some(vec!["block_number".to_string(), "block_hash".to_string()]);
+        let table = datatype
+            .table_schema(&[], &ColumnEncoding::Binary, &None, &None, &columns, None, None)
+            .unwrap();
+        let query = test_query([(datatype, table)].into_iter().collect());
+        let partition = test_partition(0, 1);
+        let df = df!(
+            "block_number" => &[0u32, 1],
+            "block_hash" => &[vec![0u8; 32], vec![1u8; 32]],
+        )
+        .unwrap();
+
+        assert!(!sink.exists(&query, &partition, datatype).await.unwrap());
+        sink.write(&query, &partition, datatype, df.clone()).await.unwrap();
+        sink.write(&query, &partition, datatype, df.clone()).await.unwrap();
+        assert!(sink.exists(&query, &partition, datatype).await.unwrap());
+
+        // chunks of other labels are kept apart
+        let labeled = SqliteSink { suffix: Some("v2".to_string()), ..sink.clone() };
+        assert!(!labeled.exists(&query, &partition, datatype).await.unwrap());
+        labeled.write(&query, &partition, datatype, df).await.unwrap();
+
+        let conn = Connection::open(&sink.db_path).unwrap();
+        let n_rows: i64 =
+            conn.query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get(0)).unwrap();
+        assert_eq!(n_rows, 4);
+        std::fs::remove_dir_all(sink.db_path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_partition_schema() {
+        let sink = test_sink("schema");
+        let datatype = Datatype::Logs;
+        let query = test_query(Schemas::new());
+        let partition = test_partition(0, 999);
+
+        // rows beyond the parameter limit of one statement are inserted in several statements
+        let n_rows = 1000u32;
+        let df = df!(
+            "block_number" => (0..n_rows).collect::<Vec<u32>>(),
+            "log_index" => (0..n_rows).collect::<Vec<u32>>(),
+        )
+        .unwrap();
+        sink.write(&query, &partition, datatype, df).await.unwrap();
+        let conn = Connection::open(&sink.db_path).unwrap();
+        let (count, sum): (i64, i64) = conn
+            .query_row("SELECT COUNT(*), SUM(log_index) FROM logs", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((count, sum), (n_rows as i64, (0..n_rows as i64).sum()));
+
+        // existing tables without the columns of the dataframe are refused
+        let df = df!("block_number" => &[0u32], "event__value" => &[1u32]).unwrap();
+        let error = sink.write(&query, &partition, datatype, df).await.unwrap_err();
+        assert!(error.to_string().contains("event__value"));
+
+        std::fs::remove_dir_all(sink.db_path.parent().unwrap()).unwrap();
+    }
+}
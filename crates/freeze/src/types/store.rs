@@ -0,0 +1,228 @@
+use crate::{err, provenance, CollectError, Datatype, FileOutput, Partition, Query, Sink};
+use object_store::{
+    aws::AmazonS3Builder, local::LocalFileSystem, path::Path as ObjectPath, ObjectStore,
+};
+use polars::prelude::*;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// number of bytes read from staged files per part of multipart uploads
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// sink that writes output files to an object store, e.g. `s3://bucket/prefix` or `file:///dir`
+///
+/// files are written to a local staging directory and then uploaded with multipart uploads. an
+/// object only becomes visible once its upload is completed, and failed uploads are aborted,
+/// so readers never see partially written files, like with the `_tmp` files of local output
+#[derive(Clone)]
+pub struct ObjectStoreSink {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    /// file output that writes files to the staging directory
+    pub staging: FileOutput,
+}
+
+impl ObjectStoreSink {
+    /// create sink for output url, paths of staged files relative to the staging directory are
+    /// used as object paths below the path of the url
+    ///
+    /// s3 credentials, region, and endpoint are read from the standard `AWS_*` environment
+    /// variables, e.g. `AWS_ENDPOINT=http://localhost:9000` and `AWS_ALLOW_HTTP=true` for minio
+    pub fn new(url: &str, staging: FileOutput) -> Result<ObjectStoreSink, CollectError> {
+        let (scheme, location) =
+            url.split_once("://").ok_or_else(|| err(&format!("invalid output url: {}", url)))?;
+        let (store, prefix): (Arc<dyn ObjectStore>, ObjectPath) = match scheme {
+            "file" => {
+                std::fs::create_dir_all(location)
+                    .map_err(|_| err("could not create output directory"))?;
+                let store = LocalFileSystem::new_with_prefix(location).map_err(store_err)?;
+                (Arc::new(store), ObjectPath::default())
+            }
+            "s3" => {
+                let store = AmazonS3Builder::from_env().with_url(url).build().map_err(store_err)?;
+                let prefix = location.split_once('/').map(|(_, prefix)| prefix).unwrap_or("");
+                (Arc::new(store), ObjectPath::from(prefix))
+            }
+            _ => {
+                let message = format!("unsupported output url: {}, use s3:// or file://", url);
+                return Err(err(&message))
+            }
+        };
+        Ok(ObjectStoreSink { store, prefix, staging })
+    }
+
+    /// object path of staged file
+    fn object_path(&self, path: &Path) -> Result<ObjectPath, CollectError> {
+        let relative = path
+            .strip_prefix(&self.staging.output_dir)
+            .map_err(|_| err("file is not in staging directory"))?;
+        let mut object_path = self.prefix.clone();
+        for component in relative.iter() {
+            object_path = object_path.child(component.to_string_lossy().as_ref());
+        }
+        Ok(object_path)
+    }
+
+    /// upload staged file with a multipart upload, then remove it from staging directory
+    async fn upload(&self, path: &Path) -> Result<(), CollectError> {
+        let location = self.object_path(path)?;
+        let (id, mut writer) = self.store.put_multipart(&location).await.map_err(store_err)?;
+        let result = async {
+            let mut file = tokio::fs::File::open(path).await?;
+            let mut buffer = vec![0; PART_SIZE];
+            loop {
+                let n_bytes = file.read(&mut buffer).await?;
+                if n_bytes == 0 {
+                    break
+                }
+                writer.write_all(&buffer[..n_bytes]).await?;
+            }
+            writer.shutdown().await
+        }
+        .await;
+        if let Err(e) = result {
+            // abort so that the parts uploaded so far are not kept by the store
+            let _ = self.store.abort_multipart(&location, &id).await;
+            return Err(err(&format!("could not upload {}: {}", location, e)))
+        }
+        tokio::fs::remove_file(path).await.map_err(|_| err("could not remove staged file"))
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for ObjectStoreSink {
+    async fn exists(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+    ) -> Result<bool, CollectError> {
+        if self.staging.overwrite {
+            return Ok(false)
+        }
+        let path = self.staging.get_path(query, partition, datatype)?;
+        match self.store.head(&self.object_path(&path)?).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(store_err(e)),
+        }
+    }
+
+    async fn write(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        datatype: Datatype,
+        df: DataFrame,
+    ) -> Result<(), CollectError> {
+        let dfs = [(datatype, df)].into_iter().collect();
+        self.write_partition(query, partition, dfs).await.map(|_| ())
+    }
+
+    async fn write_partition(
+        &self,
+        query: &Query,
+        partition: &Partition,
+        dfs: HashMap<Datatype, DataFrame>,
+    ) -> Result<u64, CollectError> {
+        let mut paths = Vec::new();
+        for datatype in dfs.keys() {
+            paths.push(self.staging.get_path(query, partition, *datatype)?);
+        }
+        let n_rows = self.staging.write_partition(query, partition, dfs).await?;
+
+        // upload sidecars before their files, so that files never appear without provenance
+        let sidecars: Vec<PathBuf> = paths
+            .iter()
+            .map(|path| provenance::get_sidecar_path(path))
+            .filter(|sidecar| sidecar.exists())
+            .collect();
+        for path in sidecars.iter().chain(paths.iter()) {
+            self.upload(path).await?;
+        }
+        Ok(n_rows)
+    }
+
+    async fn finalize(&self) -> Result<(), CollectError> {
+        match tokio::fs::remove_dir_all(&self.staging.output_dir).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(err("could not remove staging directory"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn store_err(e: object_store::Error) -> CollectError {
+    err(&format!("object store error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileFormat;
+
+    fn staging_output(name: &str) -> FileOutput {
+        let output_dir = std::env::temp_dir().join(format!("cryo_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+        FileOutput::new_test(output_dir, FileFormat::Csv)
+    }
+
+    async fn check_write_and_exists(url: &str, staging: FileOutput) {
+        let sink = ObjectStoreSink::new(url, staging).unwrap();
+        let query = Query {
+            datatypes: vec![],
+            schemas: HashMap::new(),
+            time_dimension: crate::TimeDimension::Blocks,
+            partitions: vec![],
+            partitioned_by: vec![crate::Dim::BlockNumber],
+            exclude_failed: false,
+            js_tracer: None,
+            labels: crate::QueryLabels { align: false, reorg_buffer: 0 },
+        };
+        let partition = Partition {
+            block_numbers: Some(vec![crate::BlockChunk::Range(0, 1)]),
+            ..Default::default()
+        };
+        assert!(!sink.exists(&query, &partition, Datatype::Blocks).await.unwrap());
+        let df = df!("block_number" => &[0u32, 1]).unwrap();
+        sink.write(&query, &partition, Datatype::Blocks, df).await.unwrap();
+        assert!(sink.exists(&query, &partition, Datatype::Blocks).await.unwrap());
+        let staged = sink.staging.get_path(&query, &partition, Datatype::Blocks).unwrap();
+        assert!(!staged.exists());
+    }
+
+    #[test]
+    fn test_unsupported_url() {
+        let staging = staging_output("store_unsupported");
+        for url in ["gs://bucket/prefix", "http://localhost/prefix", "s4://bucket/prefix"] {
+            assert!(ObjectStoreSink::new(url, staging.clone()).is_err(), "{}", url);
+        }
+        std::fs::remove_dir_all(staging.output_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file_url() {
+        let staging = staging_output("store_staging");
+        let dir = std::env::temp_dir().join(format!("cryo_store_{}", std::process::id()));
+        let url = format!("file://{}", dir.display());
+        check_write_and_exists(&url, staging.clone()).await;
+        assert!(dir.join("ethereum__blocks__00000000_to_00000001.csv").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(staging.output_dir).unwrap();
+    }
+
+    /// run against a local s3-compatible server, e.g. minio with
+    /// `CRYO_TEST_S3_URL=s3://bucket/prefix AWS_ENDPOINT=http://localhost:9000 AWS_ALLOW_HTTP=true`
+    #[tokio::test]
+    #[ignore]
+    async fn test_s3_url() {
+        let url = std::env::var("CRYO_TEST_S3_URL").unwrap();
+        let staging = staging_output("store_s3_staging");
+        check_write_and_exists(&url, staging.clone()).await;
+        std::fs::remove_dir_all(staging.output_dir).unwrap();
+    }
+}
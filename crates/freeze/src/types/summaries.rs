@@ -5,9 +5,9 @@ use colored::Colorize;
 use thousands::Separable;
 
 use crate::{
-    chunks::chunk_ops::ValueToString, err, ChunkData, ChunkStats, CollectError, ColumnType,
-    Datatype, Dim, ExecutionEnv, FileOutput, MetaDatatype, MultiDatatype, Partition, Query, Source,
-    Table,
+    chunks::chunk_ops::ValueToString, err, Capabilities, ChunkData, ChunkStats, CollectError,
+    ColumnType, Datatype, Dim, ExecutionEnv, FileOutput, MetaDatatype, MultiDatatype, Partition,
+    Query, Source, Support, Table,
 };
 use std::path::PathBuf;
 
@@ -27,6 +27,8 @@ pub struct FreezeSummary {
     pub skipped: Vec<Partition>,
     /// partitions errored
     pub errored: Vec<(Option<Partition>, CollectError)>,
+    /// partitions that needed more than one attempt, with their number of attempts
+    pub retried: Vec<(Partition, u32)>,
     /// rows written
     pub n_rows: u64,
 }
@@ -157,7 +159,8 @@ fn print_bullet_indent<A: AsRef<str>, B: AsRef<str>>(key: A, value: B, indent: u
 pub(crate) fn print_cryo_intro(
     query: &Query,
     source: &Source,
-    sink: &FileOutput,
+    capabilities: Option<&Capabilities>,
+    sink: Option<&FileOutput>,
     env: &ExecutionEnv,
     n_chunks_remaining: u64,
 ) -> Result<(), CollectError> {
@@ -177,7 +180,9 @@ pub(crate) fn print_cryo_intro(
     }
 
     print_bullet("source", "");
-    print_bullet_indent("network", &sink.prefix, 4);
+    if let Some(sink) = sink {
+        print_bullet_indent("network", &sink.prefix, 4);
+    }
     print_bullet_indent("rpc url", &source.rpc_url, 4);
     match source.labels.max_requests_per_second {
         Some(max_requests_per_second) => print_bullet_indent(
@@ -195,6 +200,15 @@ pub(crate) fn print_cryo_intro(
         ),
         None => print_bullet_indent("max concurrent requests", "unlimited", 4),
     };
+    if let Some(max_batch_size) = source.labels.max_batch_size {
+        print_bullet_indent("max batch size", max_batch_size.separate_with_commas(), 4);
+    }
+    if source.adaptive_limiter.is_some() {
+        print_bullet_indent("adaptive limits", "enabled", 4);
+    }
+    if let Some(cache) = &source.request_cache {
+        print_bullet_indent("request cache", cache.dir.to_string_lossy(), 4);
+    }
     match source.max_concurrent_chunks {
         Some(max_concurrent_chunks) => print_bullet_indent(
             "max concurrent chunks",
@@ -219,10 +233,33 @@ pub(crate) fn print_cryo_intro(
         };
     }
 
+    if env.retry.max_attempts > 1 {
+        let text = format!(
+            "max {} attempts, initial backoff {}ms, on {}",
+            env.retry.max_attempts,
+            env.retry.initial_backoff.as_millis().separate_with_commas(),
+            env.retry.retryable.join(", "),
+        );
+        print_bullet_indent("chunk retries", text, 4);
+    }
+
     if query.schemas.contains_key(&Datatype::Logs) {
         print_bullet_indent("inner request size", source.inner_request_size.to_string(), 4);
     };
 
+    if let Some(capabilities) = capabilities {
+        for (capability, support) in capabilities.support.iter() {
+            let text = match support {
+                Support::Supported => {
+                    format!("supported at block {}", capabilities.block_number)
+                }
+                Support::Unsupported(message) => format!("not supported ({})", message),
+                Support::Unknown(message) => format!("unknown ({})", message),
+            };
+            print_bullet_indent(capability.name(), text, 4);
+        }
+    }
+
     print_bullet("output", "");
     if let Some(partition) = query.partitions.first() {
         let stats = partition.stats();
@@ -243,29 +280,35 @@ pub(crate) fn print_cryo_intro(
         (n_datatypes * query.partitions.len()).separate_with_commas()
     );
     print_bullet_indent("chunks to collect", chunk_text, 4);
-    print_bullet_indent("output format", sink.format.as_str(), 4);
-    print_bullet_indent("output dir", sink.output_dir.clone().to_string_lossy(), 4);
-
-    // print report path
-    let report_path = if env.report && n_chunks_remaining > 0 {
-        match super::reports::get_report_path(env, sink, true) {
-            Ok(report_path) => {
-                let stripped_path: PathBuf = match report_path.strip_prefix(sink.output_dir.clone())
-                {
-                    Ok(stripped) => PathBuf::from("$OUTPUT_DIR").join(PathBuf::from(stripped)),
-                    Err(_) => report_path,
-                };
-                Some(stripped_path)
+    if let Some(sink) = sink {
+        print_bullet_indent("output format", sink.format.as_str(), 4);
+        print_bullet_indent("output dir", sink.output_dir.clone().to_string_lossy(), 4);
+
+        // print report path
+        let report_path = if env.report && n_chunks_remaining > 0 {
+            match super::reports::get_report_path(env, sink, true) {
+                Ok(report_path) => {
+                    let stripped_path: PathBuf = match report_path
+                        .strip_prefix(sink.output_dir.clone())
+                    {
+                        Ok(stripped) => PathBuf::from("$OUTPUT_DIR").join(PathBuf::from(stripped)),
+                        Err(_) => report_path,
+                    };
+                    Some(stripped_path)
+                }
+                _ => None,
             }
-            _ => None,
-        }
+        } else {
+            None
+        };
+        match report_path {
+            None => print_bullet_indent("report file", "None", 4),
+            Some(path) => print_bullet_indent("report file", path.to_str().unwrap_or("none"), 4),
+        };
     } else {
-        None
-    };
-    match report_path {
-        None => print_bullet_indent("report file", "None", 4),
-        Some(path) => print_bullet_indent("report file", path.to_str().unwrap_or("none"), 4),
-    };
+        let disabled = "journal, resume, reports, delta commits, and path collision checks";
+        print_bullet_indent("disabled for sink", disabled, 4);
+    }
 
     // print schemas
     print_schemas(&query.datatypes, &query.schemas)?;
@@ -400,6 +443,22 @@ fn print_schema(name: &Datatype, schema: &Table) {
     println!("\nother available columns: {}", other_columns);
 }
 
+/// print counts of each distinct error of errored partitions
+pub(crate) fn print_error_summary(errored: &[(Option<Partition>, CollectError)]) {
+    print_header_error("error summary");
+    println!("(errors in {} chunks)", errored.len());
+    let mut error_counts: HashMap<String, usize> = HashMap::new();
+    for (_partition, error) in errored.iter() {
+        *error_counts.entry(error.to_string()).or_insert(0) += 1;
+    }
+    for (error, count) in error_counts.iter().take(10) {
+        println!("- {} ({}x)", error, count);
+    }
+    if error_counts.len() > 10 {
+        println!("...")
+    }
+}
+
 pub(crate) fn print_cryo_conclusion(
     freeze_summary: &FreezeSummary,
     query: &Query,
@@ -426,18 +485,7 @@ pub(crate) fn print_cryo_conclusion(
     println!();
 
     if !freeze_summary.errored.is_empty() {
-        print_header_error("error summary");
-        println!("(errors in {} chunks)", freeze_summary.errored.len());
-        let mut error_counts: HashMap<String, usize> = HashMap::new();
-        for (_partition, error) in freeze_summary.errored.iter() {
-            *error_counts.entry(error.to_string()).or_insert(0) += 1;
-        }
-        for (error, count) in error_counts.iter().take(10) {
-            println!("- {} ({}x)", error, count);
-        }
-        if error_counts.len() > 10 {
-            println!("...")
-        }
+        print_error_summary(&freeze_summary.errored);
         println!();
         println!();
     }
@@ -494,6 +542,21 @@ pub(crate) fn print_cryo_conclusion(
         4,
     );
 
+    if !freeze_summary.retried.is_empty() {
+        let n_attempts: u32 = freeze_summary.retried.iter().map(|(_, attempts)| attempts).sum();
+        print_bullet_indent(
+            "chunks retried",
+            format!(
+                "  {:>width$} / {} ({} attempts)",
+                freeze_summary.retried.len().separate_with_commas(),
+                n_chunks_str,
+                n_attempts.separate_with_commas(),
+                width = width
+            ),
+            4,
+        );
+    }
+
     print_chunks_speeds(
         freeze_summary.completed.clone(),
         &query.partitioned_by,
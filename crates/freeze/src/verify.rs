@@ -0,0 +1,192 @@
+use crate::{
+    dataframes::{self, StoredBlockHashes},
+    err,
+    freeze::collect_and_write,
+    split::SplitIndex,
+    CollectError, ExecutionEnv, FileFormat, FileOutput, Query, Source,
+};
+use futures::StreamExt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// summary of verifying that output files are still canonical
+#[derive(Debug, Default)]
+pub struct VerifySummary {
+    /// files whose block hashes all match the chain
+    pub canonical: Vec<PathBuf>,
+    /// files containing blocks that are no longer canonical
+    pub reorged: Vec<PathBuf>,
+    /// files that were re-collected and replaced
+    pub repaired: Vec<PathBuf>,
+    /// files without a block_hash column, which cannot be verified
+    pub unverifiable: Vec<PathBuf>,
+    /// files that could not be verified or repaired
+    pub errored: Vec<(PathBuf, CollectError)>,
+}
+
+/// max number of blocks fetched concurrently while verifying a file
+const MAX_CONCURRENT_BLOCKS: usize = 100;
+
+/// block hash and parent hash of canonical blocks, None if block is not on chain
+type CanonicalHashes = HashMap<u64, Option<(Vec<u8>, Vec<u8>)>>;
+
+/// check that existing output files of query are still canonical, replacing any that are not
+///
+/// stored block_hash (and parent_hash, if present) values are compared against the chain,
+/// and each chunk containing a reorged block is re-collected and atomically replaced,
+/// in dry mode reorged chunks are only reported
+pub async fn verify_canonical(
+    query: &Query,
+    source: Arc<Source>,
+    sink: &FileOutput,
+    env: &ExecutionEnv,
+) -> Result<VerifySummary, CollectError> {
+    query.is_valid()?;
+    if sink.format != FileFormat::Parquet {
+        return Err(err("can only verify parquet outputs"))
+    }
+
+    let arc_query = Arc::new(query.clone());
+    let mut canonical_hashes = CanonicalHashes::new();
+    let mut summary = VerifySummary::default();
+    // repairs only replace the files of their own partition, so the index stays valid
+    let mut splits = SplitIndex::default();
+    for datatype in query.datatypes.iter() {
+        for partition in query.partitions.iter() {
+            let paths = sink.get_paths(query, partition, Some(vec![datatype.clone()]))?;
+            let mut existing = Vec::new();
+            for path in paths.values() {
+                existing.extend(splits.output_files(path, sink)?.unwrap_or_default());
+            }
+
+            // verify each file of chunk
+            let mut reorged = false;
+            for path in existing.iter() {
+                match verify_file(path, &source, &mut canonical_hashes).await {
+                    Ok(Some(true)) => summary.canonical.push(path.clone()),
+                    Ok(Some(false)) => {
+                        if env.verbose >= 1 {
+                            println!("reorged: {}", path.to_string_lossy());
+                        }
+                        reorged = true;
+                        summary.reorged.push(path.clone());
+                    }
+                    Ok(None) => summary.unverifiable.push(path.clone()),
+                    Err(e) => summary.errored.push((path.clone(), e)),
+                }
+            }
+            if !reorged || env.dry {
+                continue
+            }
+
+            // re-collect whole chunk so that all of its files stay consistent
+            let result = collect_and_write(
+                partition.clone(),
+                datatype.clone(),
+                arc_query.clone(),
+                source.clone(),
+                sink,
+            )
+            .await;
+            match result {
+                Ok(_) => {
+                    if env.verbose >= 1 {
+                        for path in existing.iter() {
+                            println!("repaired: {}", path.to_string_lossy());
+                        }
+                    }
+                    summary.repaired.extend(existing)
+                }
+                Err(e) => {
+                    let path = existing.into_iter().next().unwrap_or_default();
+                    summary.errored.push((path, e))
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// check whether stored block hashes of file match the chain, None if file has no block hashes
+async fn verify_file(
+    path: &Path,
+    source: &Source,
+    canonical_hashes: &mut CanonicalHashes,
+) -> Result<Option<bool>, CollectError> {
+    let path_str = path.to_str().ok_or(err("could not convert path to str"))?;
+    let stored = match dataframes::read_block_hashes(path_str)? {
+        Some(stored) => stored,
+        None => return Ok(None),
+    };
+
+    // fetch canonical hashes of blocks not yet seen
+    let missing: BTreeSet<u64> = stored
+        .iter()
+        .map(|(block_number, _, _)| *block_number)
+        .filter(|block_number| !canonical_hashes.contains_key(block_number))
+        .collect();
+    let mut blocks = futures::stream::iter(missing.into_iter())
+        .map(|block_number| async move { (block_number, source.get_block(block_number).await) })
+        .buffer_unordered(MAX_CONCURRENT_BLOCKS);
+    while let Some((block_number, block)) = blocks.next().await {
+        let hashes = block?.and_then(|block| {
+            block.hash.map(|hash| (hash.as_bytes().to_vec(), block.parent_hash.as_bytes().to_vec()))
+        });
+        canonical_hashes.insert(block_number, hashes);
+    }
+
+    Ok(Some(is_canonical(&stored, canonical_hashes)))
+}
+
+/// whether all stored block hashes and parent hashes match the canonical hashes
+fn is_canonical(stored: &StoredBlockHashes, canonical_hashes: &CanonicalHashes) -> bool {
+    for (block_number, block_hash, parent_hash) in stored.iter() {
+        match canonical_hashes.get(block_number) {
+            Some(Some((canonical_hash, canonical_parent_hash))) => {
+                if block_hash != canonical_hash {
+                    return false
+                }
+                if let Some(parent_hash) = parent_hash {
+                    if parent_hash != canonical_parent_hash {
+                        return false
+                    }
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_canonical() {
+        let canonical: CanonicalHashes =
+            [(1, Some((vec![1], vec![0]))), (2, Some((vec![2], vec![1]))), (3, None)]
+                .into_iter()
+                .collect();
+        let stored = |entries: &[(u64, u8, Option<u8>)]| -> StoredBlockHashes {
+            entries
+                .iter()
+                .map(|(block, hash, parent)| (*block, vec![*hash], parent.map(|p| vec![p])))
+                .collect()
+        };
+
+        assert!(is_canonical(&stored(&[(1, 1, Some(0)), (2, 2, None)]), &canonical));
+
+        // mismatched block hash or parent hash
+        assert!(!is_canonical(&stored(&[(1, 1, Some(0)), (2, 9, None)]), &canonical));
+        assert!(!is_canonical(&stored(&[(2, 2, Some(9))]), &canonical));
+
+        // blocks that are no longer on chain or were never fetched
+        assert!(!is_canonical(&stored(&[(3, 3, None)]), &canonical));
+        assert!(!is_canonical(&stored(&[(4, 4, None)]), &canonical));
+    }
+}
@@ -32,6 +32,12 @@ def parse_cli_args(
         kwargs['json'] = True
     elif file_format == 'csv':
         kwargs['csv'] = True
+    elif file_format == 'ndjson':
+        kwargs['ndjson'] = True
+    elif file_format == 'arrow':
+        kwargs['arrow'] = True
+    elif file_format == 'sqlite':
+        kwargs['sqlite'] = True
     # elif file_format == 'avro':
     #     kwargs['avro'] = True
     else:
@@ -11,13 +11,14 @@ if typing.TYPE_CHECKING:
 
     ListOfDicts = list[dict[str, Any]]
     DictOfLists = dict[str, list[Any]]
+    Output = pl.DataFrame | pd.DataFrame | ListOfDicts | DictOfLists
 
 
 async def async_collect(
     datatype: _spec.Datatype,
     output_format: _spec.PythonOutput = 'polars',
     **kwargs: Unpack[_spec.CryoCliArgs],
-) -> pl.DataFrame | pd.DataFrame | ListOfDicts | DictOfLists:
+) -> Output | dict[str, Output]:
     """asynchronously collect data and return as dataframe"""
 
     from . import _args
@@ -29,10 +30,23 @@ async def async_collect(
     # fix chunk size
     cli_args['chunk_size'] = 20_000_000
 
-    # collect data
-    result: pl.DataFrame = await _cryo_rust._collect(datatype, **cli_args)
+    # collect data, multi-datatypes return a dataframe per datatype
+    result: pl.DataFrame | dict[str, pl.DataFrame] = await _cryo_rust._collect(
+        datatype, **cli_args
+    )
 
     # format output
+    if isinstance(result, dict):
+        return {
+            name: _format_output(df, output_format) for name, df in result.items()
+        }
+    else:
+        return _format_output(result, output_format)
+
+
+def _format_output(
+    result: pl.DataFrame, output_format: _spec.PythonOutput
+) -> Output:
     if output_format == 'polars':
         return result
     elif output_format == 'pandas':
@@ -49,7 +63,7 @@ def collect(
     datatype: _spec.Datatype,
     output_format: _spec.PythonOutput = 'polars',
     **kwargs: Unpack[_spec.CryoCliArgs],
-) -> pl.DataFrame | pd.DataFrame | ListOfDicts | DictOfLists:
+) -> Output | dict[str, Output]:
     """collect data and return as dataframe"""
 
     import asyncio
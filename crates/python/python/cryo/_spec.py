@@ -11,6 +11,9 @@ if typing.TYPE_CHECKING:
         Literal['parquet'],
         Literal['csv'],
         Literal['json'],
+        Literal['ndjson'],
+        Literal['arrow'],
+        Literal['sqlite'],
         Literal['avro'],
     ]
     PythonOutput = Union[
@@ -35,6 +38,10 @@ if typing.TYPE_CHECKING:
         requests_per_second: int | None
         max_concurrent_requests: int | None
         max_concurrent_chunks: int | None
+        max_batch_size: int | None
+        adaptive_limits: bool
+        cache_dir: str | None
+        cache_size: int
         dry: bool
         chunk_size: int | None
         n_chunks: int | None
@@ -43,10 +50,20 @@ if typing.TYPE_CHECKING:
         overwrite: bool
         csv: bool
         json: bool
+        ndjson: bool
+        arrow: bool
+        sqlite: bool
+        postgres: str | None
+        max_rows_per_file: int | None
+        max_file_size: str | None
         row_group_size: int | None
         n_row_groups: int | None
         no_stats: bool
+        page_size: str | None
+        dictionary: list[str]
+        bloom_filter: list[str] | None
         compression: str | None
+        delta: bool
         contract: str | bytes | None
         topic0: str | bytes | None
         topic1: str | bytes | None
@@ -19,6 +19,8 @@ file_formats = [
     ['csv', pl.read_csv],
     # ['avro', pl.read_avro],
     ['json', pl.read_json],
+    ['ndjson', pl.read_ndjson],
+    ['arrow', pl.read_ipc],
 ]
 
 
@@ -1,9 +1,14 @@
 use polars::prelude::*;
-use pyo3::{exceptions::PyTypeError, prelude::*};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyTypeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
 use pyo3_polars::PyDataFrame;
+use std::collections::HashMap;
 
 use cryo_cli::{parse_args, Args};
-use cryo_freeze::collect;
+use cryo_freeze::{collect, Datatype};
 
 #[pyfunction(
     signature = (
@@ -11,6 +16,7 @@ use cryo_freeze::collect;
         blocks = None,
         *,
         remember = false,
+        resume = false,
         command = None,
         timestamps = None,
         txs = None,
@@ -28,25 +34,45 @@ use cryo_freeze::collect;
         requests_per_second = None,
         max_concurrent_requests = None,
         max_concurrent_chunks = None,
+        max_batch_size = None,
+        adaptive_limits = false,
+        cache_dir = None,
+        cache_size = 10000,
         chunk_order = None,
         max_retries = 10,
         initial_backoff = 500,
+        chunk_retries = 0,
+        chunk_retry_backoff = 1000,
+        chunk_retry_errors = None,
         dry = false,
         chunk_size = 1000,
         n_chunks = None,
         partition_by = None,
         output_dir = ".".to_string(),
         subdirs = vec![],
+        layout = "flat".to_string(),
+        block_bucket_size = 10000,
         label = None,
         overwrite = false,
         csv = false,
         json = false,
+        ndjson = false,
+        sqlite = false,
+        arrow = false,
+        postgres = None,
+        max_rows_per_file = None,
+        max_file_size = None,
         row_group_size = None,
         n_row_groups = None,
         no_stats = false,
-        compression = vec!["lz4".to_string()],
+        page_size = None,
+        dictionary = vec![],
+        bloom_filter = None,
+        compression = None,
         report_dir = None,
         no_report = false,
+        no_manifest = false,
+        delta = false,
         address = None,
         to_address = None,
         from_address = None,
@@ -72,6 +98,7 @@ pub fn _collect(
     datatype: Option<String>,
     blocks: Option<Vec<String>>,
     remember: bool,
+    resume: bool,
     command: Option<String>,
     timestamps: Option<Vec<String>>,
     txs: Option<Vec<String>>,
@@ -89,25 +116,45 @@ pub fn _collect(
     requests_per_second: Option<u32>,
     max_concurrent_requests: Option<u64>,
     max_concurrent_chunks: Option<u64>,
+    max_batch_size: Option<u64>,
+    adaptive_limits: bool,
+    cache_dir: Option<String>,
+    cache_size: u64,
     chunk_order: Option<String>,
     max_retries: u32,
     initial_backoff: u64,
+    chunk_retries: u32,
+    chunk_retry_backoff: u64,
+    chunk_retry_errors: Option<Vec<String>>,
     dry: bool,
     chunk_size: u64,
     n_chunks: Option<u64>,
     partition_by: Option<Vec<String>>,
     output_dir: String,
     subdirs: Vec<String>,
+    layout: String,
+    block_bucket_size: u64,
     label: Option<String>,
     overwrite: bool,
     csv: bool,
     json: bool,
+    ndjson: bool,
+    sqlite: bool,
+    arrow: bool,
+    postgres: Option<String>,
+    max_rows_per_file: Option<usize>,
+    max_file_size: Option<String>,
     row_group_size: Option<usize>,
     n_row_groups: Option<usize>,
     no_stats: bool,
-    compression: Vec<String>,
+    page_size: Option<String>,
+    dictionary: Vec<String>,
+    bloom_filter: Option<Vec<String>>,
+    compression: Option<Vec<String>>,
     report_dir: Option<String>,
     no_report: bool,
+    no_manifest: bool,
+    delta: bool,
     address: Option<Vec<String>>,
     to_address: Option<Vec<String>>,
     from_address: Option<Vec<String>>,
@@ -128,20 +175,21 @@ pub fn _collect(
 ) -> PyResult<&PyAny> {
     if let Some(command) = command {
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            match run_execute(command).await {
-                Ok(df) => Ok(PyDataFrame(df)),
-                Err(_e) => Err(PyErr::new::<PyTypeError, _>("failed")),
-            }
+            let dfs = run_execute(command).await?;
+            to_py_dataframes(dfs)
         })
     } else if let Some(datatype) = datatype {
         let args = Args {
             datatype: vec![datatype],
             blocks,
             remember,
+            resume,
             timestamps,
             txs,
             align,
             reorg_buffer,
+            follow: false,
+            poll_interval: 12,
             include_columns,
             exclude_columns,
             columns,
@@ -154,25 +202,45 @@ pub fn _collect(
             requests_per_second,
             max_concurrent_requests,
             max_concurrent_chunks,
+            max_batch_size,
+            adaptive_limits,
+            cache_dir,
+            cache_size,
             chunk_order,
             max_retries,
             initial_backoff,
+            chunk_retries,
+            chunk_retry_backoff,
+            chunk_retry_errors,
             dry,
             chunk_size,
             n_chunks,
             partition_by,
             output_dir,
             subdirs,
+            layout,
+            block_bucket_size,
             label,
             overwrite,
             csv,
             json,
+            ndjson,
+            sqlite,
+            arrow,
+            postgres,
+            max_rows_per_file,
+            max_file_size,
             row_group_size,
             n_row_groups,
             no_stats,
+            page_size,
+            dictionary,
+            bloom_filter,
             compression,
             report_dir: report_dir.map(std::path::PathBuf::from),
             no_report,
+            no_manifest,
+            delta,
             address,
             to_address,
             from_address,
@@ -192,32 +260,44 @@ pub fn _collect(
             event_signature,
         };
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            match run_collect(args).await {
-                // Ok(df) => Ok(Python::with_gil(|py| py.None())),
-                Ok(df) => Ok(PyDataFrame(df)),
-                Err(_e) => Err(PyErr::new::<PyTypeError, _>("failed")),
-            }
+            let dfs = run_collect(args).await?;
+            to_py_dataframes(dfs)
         })
     } else {
         return Err(PyErr::new::<PyTypeError, _>("must specify datatype or command"))
     }
 }
 
-async fn run_collect(args: Args) -> PolarsResult<DataFrame> {
-    let (query, source, _sink, _env) = match parse_args(&args).await {
-        Ok(opts) => opts,
-        Err(e) => panic!("error parsing opts {:?}", e),
-    };
-    match collect(query.into(), source.into()).await {
-        Ok(df) => Ok(df),
-        Err(e) => panic!("error collecting {:?}", e),
-    }
+async fn run_collect(args: Args) -> PyResult<HashMap<Datatype, DataFrame>> {
+    let (query, source, _sink, _env) = parse_args(&args)
+        .await
+        .map_err(|e| PyErr::new::<PyValueError, _>(format!("error parsing opts: {}", e)))?;
+    collect(query.into(), source.into())
+        .await
+        .map_err(|e| PyErr::new::<PyRuntimeError, _>(format!("error collecting: {}", e)))
 }
 
-async fn run_execute(command: String) -> PolarsResult<DataFrame> {
-    let args = match cryo_cli::parse_str(command.as_str()).await {
-        Ok(opts) => opts,
-        Err(e) => panic!("error parsing opts {:?}", e),
-    };
+async fn run_execute(command: String) -> PyResult<HashMap<Datatype, DataFrame>> {
+    let args = cryo_cli::parse_str(command.as_str())
+        .await
+        .map_err(|e| PyErr::new::<PyValueError, _>(format!("error parsing opts: {}", e)))?;
     run_collect(args).await
 }
+
+/// single dataframe if query has one datatype, otherwise dict of dataframes by datatype name
+fn to_py_dataframes(mut dfs: HashMap<Datatype, DataFrame>) -> PyResult<PyObject> {
+    Python::with_gil(|py| {
+        if dfs.len() == 1 {
+            if let Some(datatype) = dfs.keys().next().cloned() {
+                if let Some(df) = dfs.remove(&datatype) {
+                    return Ok(PyDataFrame(df).into_py(py))
+                }
+            }
+        }
+        let dict = PyDict::new(py);
+        for (datatype, df) in dfs.into_iter() {
+            dict.set_item(datatype.name(), PyDataFrame(df).into_py(py))?;
+        }
+        Ok(dict.into_py(py))
+    })
+}
@@ -8,11 +8,14 @@ use cryo_cli::{run, Args};
         blocks = None,
         *,
         remember = false,
+        resume = false,
         command = None,
         timestamps = None,
         txs = None,
         align = false,
         reorg_buffer = 0,
+        follow = false,
+        poll_interval = 12,
         include_columns = None,
         exclude_columns = None,
         columns = None,
@@ -25,25 +28,45 @@ use cryo_cli::{run, Args};
         requests_per_second = None,
         max_concurrent_requests = None,
         max_concurrent_chunks = None,
+        max_batch_size = None,
+        adaptive_limits = false,
+        cache_dir = None,
+        cache_size = 10000,
         chunk_order = None,
         max_retries = 10,
         initial_backoff = 500,
+        chunk_retries = 0,
+        chunk_retry_backoff = 1000,
+        chunk_retry_errors = None,
         dry = false,
         chunk_size = 1000,
         n_chunks = None,
         partition_by = None,
         output_dir = ".".to_string(),
         subdirs = vec![],
+        layout = "flat".to_string(),
+        block_bucket_size = 10000,
         label = None,
         overwrite = false,
         csv = false,
         json = false,
+        ndjson = false,
+        sqlite = false,
+        arrow = false,
+        postgres = None,
+        max_rows_per_file = None,
+        max_file_size = None,
         row_group_size = None,
         n_row_groups = None,
         no_stats = false,
-        compression = vec!["lz4".to_string()],
+        page_size = None,
+        dictionary = vec![],
+        bloom_filter = None,
+        compression = None,
         report_dir = None,
         no_report = false,
+        no_manifest = false,
+        delta = false,
         address = None,
         to_address = None,
         from_address = None,
@@ -69,11 +92,14 @@ pub fn _freeze(
     datatype: Option<Vec<String>>,
     blocks: Option<Vec<String>>,
     remember: bool,
+    resume: bool,
     command: Option<String>,
     timestamps: Option<Vec<String>>,
     txs: Option<Vec<String>>,
     align: bool,
     reorg_buffer: u64,
+    follow: bool,
+    poll_interval: u64,
     include_columns: Option<Vec<String>>,
     exclude_columns: Option<Vec<String>>,
     columns: Option<Vec<String>>,
@@ -86,25 +112,45 @@ pub fn _freeze(
     requests_per_second: Option<u32>,
     max_concurrent_requests: Option<u64>,
     max_concurrent_chunks: Option<u64>,
+    max_batch_size: Option<u64>,
+    adaptive_limits: bool,
+    cache_dir: Option<String>,
+    cache_size: u64,
     chunk_order: Option<String>,
     max_retries: u32,
     initial_backoff: u64,
+    chunk_retries: u32,
+    chunk_retry_backoff: u64,
+    chunk_retry_errors: Option<Vec<String>>,
     dry: bool,
     chunk_size: u64,
     n_chunks: Option<u64>,
     partition_by: Option<Vec<String>>,
     output_dir: String,
     subdirs: Vec<String>,
+    layout: String,
+    block_bucket_size: u64,
     label: Option<String>,
     overwrite: bool,
     csv: bool,
     json: bool,
+    ndjson: bool,
+    sqlite: bool,
+    arrow: bool,
+    postgres: Option<String>,
+    max_rows_per_file: Option<usize>,
+    max_file_size: Option<String>,
     row_group_size: Option<usize>,
     n_row_groups: Option<usize>,
     no_stats: bool,
-    compression: Vec<String>,
+    page_size: Option<String>,
+    dictionary: Vec<String>,
+    bloom_filter: Option<Vec<String>>,
+    compression: Option<Vec<String>>,
     report_dir: Option<String>,
     no_report: bool,
+    no_manifest: bool,
+    delta: bool,
     address: Option<Vec<String>>,
     to_address: Option<Vec<String>>,
     from_address: Option<Vec<String>>,
@@ -130,10 +176,13 @@ pub fn _freeze(
             datatype,
             blocks,
             remember,
+            resume,
             txs,
             timestamps,
             align,
             reorg_buffer,
+            follow,
+            poll_interval,
             include_columns,
             exclude_columns,
             columns,
@@ -146,25 +195,45 @@ pub fn _freeze(
             requests_per_second,
             max_concurrent_requests,
             max_concurrent_chunks,
+            max_batch_size,
+            adaptive_limits,
+            cache_dir,
+            cache_size,
             chunk_order,
             max_retries,
             initial_backoff,
+            chunk_retries,
+            chunk_retry_backoff,
+            chunk_retry_errors,
             dry,
             chunk_size,
             n_chunks,
             partition_by,
             output_dir,
             subdirs,
+            layout,
+            block_bucket_size,
             label,
             overwrite,
             csv,
             json,
+            ndjson,
+            sqlite,
+            arrow,
+            postgres,
+            max_rows_per_file,
+            max_file_size,
             row_group_size,
             n_row_groups,
             no_stats,
+            page_size,
+            dictionary,
+            bloom_filter,
             compression,
             report_dir: report_dir.map(std::path::PathBuf::from),
             no_report,
+            no_manifest,
+            delta,
             address,
             to_address,
             from_address,